@@ -0,0 +1,70 @@
+// Vertex shader
+struct SceneUniform {
+    view_proj: mat4x4<f32>
+};
+
+@group(0) @binding(0)
+var<uniform> scene: SceneUniform;
+
+@group(1) @binding(0)
+var<uniform> time: vec4<f32>;
+
+struct VertexInput {
+    @location(0) position  : vec3<f32>,
+    @location(1) uv_coords : vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) size_offset : vec3<f32>,
+    @location(3) velocity    : f32,
+    @location(4) color       : vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position : vec4<f32>,
+    @location(0)       color         : vec4<f32>,
+}
+
+// Scrolls the same way circles do in `taiko.wgsl`
+@vertex
+fn vs_main(
+    vertex   : VertexInput,
+    instance : InstanceInput,
+) -> VertexOutput {
+    let so = instance.size_offset;
+    let model_matrix = mat4x4<f32>(
+        vec4(so.x,  0.0, 0.0, 0.0),
+        vec4( 0.0, so.y, 0.0, 0.0),
+        vec4( 0.0,  0.0, 1.0, 0.0),
+        vec4(so.z,  0.0, 0.0, 1.0),
+    );
+
+    let time_matrix = mat4x4<f32>(
+        vec4(   1.0, 0.0, 0.0, 0.0),
+        vec4(   0.0, 1.0, 0.0, 0.0),
+        vec4(   0.0, 0.0, 1.0, 0.0),
+        vec4(time.x, 0.0, 0.0, 1.0),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = time_matrix * model_matrix * vec4<f32>(vertex.position, 1.0);
+    out.clip_position.x *= instance.velocity;
+    out.clip_position = scene.view_proj * out.clip_position;
+    out.color = instance.color;
+
+    return out;
+}
+
+fn to_srgb(srgba: vec4<f32>) -> vec4<f32> {
+    let srgb = srgba.rgb;
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return vec4(select(higher, lower, cutoff), srgba.a);
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return to_srgb(in.color);
+}
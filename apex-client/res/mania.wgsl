@@ -0,0 +1,66 @@
+// Vertex shader
+struct SceneUniform {
+    view_proj: mat4x4<f32>
+};
+
+@group(0) @binding(0)
+var<uniform> scene: SceneUniform;
+
+@group(1) @binding(0)
+var<uniform> time: vec4<f32>;
+
+struct VertexInput {
+    @location(0) position  : vec3<f32>,
+    @location(1) uv_coords : vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) rect   : vec4<f32>,
+    @location(3) color  : vec4<f32>,
+    @location(4) scroll : f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position : vec4<f32>,
+    @location(0)       uv_coords     : vec2<f32>,
+    @location(1)       color         : vec4<f32>,
+    @location(2)       size          : vec2<f32>,
+}
+
+@vertex
+fn vs_main(
+    vertex   : VertexInput,
+    instance : InstanceInput,
+) -> VertexOutput {
+    let rect = instance.rect;
+    var position = vec4(rect.xy + vertex.position.xy * rect.zw, 0.0, 1.0);
+    position.y += time.x * instance.scroll;
+
+    var out: VertexOutput;
+    out.clip_position = scene.view_proj * position;
+    out.uv_coords = vertex.uv_coords;
+    out.color = instance.color;
+    out.size = rect.zw;
+
+    return out;
+}
+
+// Fragment shader
+fn to_srgb(srgba: vec4<f32>) -> vec4<f32> {
+    let srgb = srgba.rgb;
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return vec4(select(higher, lower, cutoff), srgba.a);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Darkened 2px outline, measured in playfield pixels so it doesn't stretch with the rectangle
+    let distance = min(in.uv_coords, vec2(1.0) - in.uv_coords) * in.size;
+    if min(distance.x, distance.y) < 2.0 {
+        return to_srgb(vec4(in.color.rgb * 0.6, in.color.a));
+    }
+
+    return to_srgb(in.color);
+}
@@ -19,6 +19,7 @@ struct InstanceInput {
     @location(3) velocity    : f32,
     @location(4) color       : vec4<f32>,
     @location(5) finisher    : u32,
+    @location(6) kind        : u32,
 };
 
 struct VertexOutput {
@@ -26,6 +27,8 @@ struct VertexOutput {
     @location(0)       uv_coords     : vec2<f32>,
     @location(1)       color         : vec4<f32>,
     @location(2)       finisher      : u32,
+    @location(3)       kind          : u32,
+    @location(4)       glow          : f32,
 }
 
 @vertex
@@ -55,6 +58,8 @@ fn vs_main(
     out.uv_coords = vertex.uv_coords;
     out.color = instance.color;
     out.finisher = instance.finisher;
+    out.kind = instance.kind;
+    out.glow = time.y;
 
     return out;
 }
@@ -72,6 +77,9 @@ fn vs_main(
 @group(5) @binding(0) var t3 : texture_2d<f32>;
 @group(5) @binding(1) var s3 : sampler;
 
+@group(6) @binding(0) var t4 : texture_2d<f32>;
+@group(6) @binding(1) var s4 : sampler;
+
 fn to_srgb(srgba: vec4<f32>) -> vec4<f32> {
     let srgb = srgba.rgb;
     let cutoff = srgb < vec3<f32>(0.04045);
@@ -80,19 +88,48 @@ fn to_srgb(srgba: vec4<f32>) -> vec4<f32> {
     return vec4(select(higher, lower, cutoff), srgba.a);
 }
 
+// Object kinds must be kept in sync with `TaikoHitObjectKind`
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let texture_finisher = textureSample(t2, s2, in.uv_coords);
     let overlay_finisher = textureSample(t3, s3, in.uv_coords);
     let texture_circle = textureSample(t0, s0, in.uv_coords);
     let overlay_circle = textureSample(t1, s1, in.uv_coords);
+    let swell_ring = textureSample(t4, s4, in.uv_coords);
+
+    // Drumroll body, a plain rectangle with an outline on the top and bottom edges
+    if in.kind == u32(1) {
+        let edge = abs(in.uv_coords.y - 0.5) * 2.0;
+        if edge > 0.92 {
+            return to_srgb(vec4(1.0, 1.0, 1.0, 1.0));
+        }
+
+        return to_srgb(in.color);
+    }
+
+    var out: vec4<f32>;
     if in.finisher == u32(0) {
-        let out = overlay_circle              * overlay_circle.a
-                + (texture_circle * in.color) * (1.0 - overlay_circle.a);
-        return to_srgb(out);
+        out = overlay_circle              * overlay_circle.a
+            + (texture_circle * in.color) * (1.0 - overlay_circle.a);
     } else {
-        let out = overlay_finisher              * overlay_finisher.a
-                + (texture_finisher * in.color) * (1.0 - overlay_finisher.a);
-        return to_srgb(out);
+        out = overlay_finisher              * overlay_finisher.a
+            + (texture_finisher * in.color) * (1.0 - overlay_finisher.a);
     }
-}
\ No newline at end of file
+
+    // Swell, a circle with a spinner ring around it
+    if in.kind == u32(2) {
+        out = swell_ring * swell_ring.a + out * (1.0 - swell_ring.a);
+    }
+
+    // Kiai glow, brightens the circle and adds a soft halo around it
+    if in.glow > 0.0 {
+        let distance = length(in.uv_coords - vec2(0.5, 0.5)) * 2.0;
+        let halo = (1.0 - smoothstep(0.6, 1.0, distance)) * in.glow * 0.6;
+        let lit = out.rgb + (vec3(1.0, 1.0, 1.0) - out.rgb) * in.glow * 0.3;
+        let alpha = out.a + halo * (1.0 - out.a);
+        let color = (lit * out.a + vec3(1.0, 0.85, 0.55) * halo * (1.0 - out.a)) / max(alpha, 0.001);
+        out = vec4(color, alpha);
+    }
+
+    return to_srgb(out);
+}
@@ -1,13 +1,17 @@
-use wcore::{graphics::{context::Graphics, gui::view::View, layer::Layer}, egui::Egui, binds::{KeyCombination, KeyCode, Actions, Action}};
-use winit::{window::Window, event::{WindowEvent, VirtualKeyCode, ElementState, ModifiersState}, event_loop::EventLoop};
+use std::collections::HashSet;
 
-use crate::{config::Config, view::{window::{timeline::TimelineWindow, file_dialog::FileDialogWindow}, menu::MenuView, sidebar::SidebarView}, state::AppState, graphics::util::new_graphics};
+use wcore::{graphics::{context::Graphics, gui::{view::View, window::Window as _}, layer::Layer}, egui::Egui, binds::{KeyCombination, KeyCode, Actions, Action}, clock::Clock};
+use cgmath::vec2;
+use winit::{window::Window, event::{WindowEvent, VirtualKeyCode, ElementState, ModifiersState, MouseButton}, event_loop::EventLoop};
+
+use crate::{config::Config, view::{window::{timeline::TimelineWindow, file_dialog::FileDialogWindow, save_dialog::SaveDialogWindow, difficulty_picker::DifficultyPickerWindow, performance::PerformanceWindow, checks::ChecksWindow, compare::CompareWindow, timing::TimingWindow, velocity::VelocityWindow, history::HistoryWindow, transform::TransformWindow, properties::PropertiesWindow}, menu::MenuView, sidebar::SidebarView}, state::AppState, graphics::util::new_graphics, taiko::taiko_circle::TaikoColor};
 
 pub struct App {
     // graphics
-    pub window   : Window,
-    pub graphics : Graphics,
-    pub actions  : Actions<AppState>,
+    pub window    : Window,
+    pub graphics  : Graphics,
+    pub actions   : Actions<AppState>,
+    pub held_keys : HashSet<VirtualKeyCode>,
 
     // egui
     pub egui     : Egui,
@@ -17,6 +21,17 @@ pub struct App {
     pub timeline    : TimelineWindow,
 
     pub file_dialog : FileDialogWindow,
+    pub save_dialog : SaveDialogWindow,
+
+    pub difficulty_picker : DifficultyPickerWindow,
+    pub performance       : PerformanceWindow,
+    pub checks            : ChecksWindow,
+    pub compare           : CompareWindow,
+    pub timing            : TimingWindow,
+    pub velocity          : VelocityWindow,
+    pub history           : HistoryWindow,
+    pub transform         : TransformWindow,
+    pub properties        : PropertiesWindow,
 
     // layers
     pub state : AppState,
@@ -41,6 +56,121 @@ impl App {
             })
         );
 
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::W), modifier: ModifiersState::default() },
+            Action::new(String::from("toggle color"), String::from("switches the hovered object, or the next placed one, between don and kat"), |state: &mut AppState| {
+                state.taiko_layer.toggle_color(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::E), modifier: ModifiersState::default() },
+            Action::new(String::from("toggle big"), String::from("switches the hovered object, or the next placed one, between small and big"), |state: &mut AppState| {
+                state.taiko_layer.toggle_big(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Z), modifier: ModifiersState::CTRL },
+            Action::new(String::from("undo"), String::from("reverts the last edit"), |state: &mut AppState| {
+                state.taiko_layer.undo(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Y), modifier: ModifiersState::CTRL },
+            Action::new(String::from("redo"), String::from("applies the last undone edit again"), |state: &mut AppState| {
+                state.taiko_layer.redo(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Z), modifier: ModifiersState::CTRL | ModifiersState::SHIFT },
+            Action::new(String::from("redo"), String::from("applies the last undone edit again"), |state: &mut AppState| {
+                state.taiko_layer.redo(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Left), modifier: ModifiersState::default() },
+            Action::new(String::from("step back"), String::from("moves back to the previous tick of the snap divisor"), |state: &mut AppState| {
+                state.taiko_layer.timeline_move_back(&mut state.taiko, 1.0);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Right), modifier: ModifiersState::default() },
+            Action::new(String::from("step forward"), String::from("moves forward to the next tick of the snap divisor"), |state: &mut AppState| {
+                state.taiko_layer.timeline_move_forward(&mut state.taiko, 1.0);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::A), modifier: ModifiersState::CTRL },
+            Action::new(String::from("select all"), String::from("selects every object"), |state: &mut AppState| {
+                state.taiko_layer.select_all(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Escape), modifier: ModifiersState::default() },
+            Action::new(String::from("clear selection"), String::from("deselects all objects"), |state: &mut AppState| {
+                state.taiko_layer.clear_selection(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Delete), modifier: ModifiersState::default() },
+            Action::new(String::from("delete selection"), String::from("deletes the selected objects"), |state: &mut AppState| {
+                state.taiko_layer.delete_selection(&mut state.taiko);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Left), modifier: ModifiersState::CTRL },
+            Action::new(String::from("nudge back"), String::from("moves the selection back by one tick of the snap divisor"), |state: &mut AppState| {
+                state.taiko_layer.nudge_selection(&mut state.taiko, -1);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Right), modifier: ModifiersState::CTRL },
+            Action::new(String::from("nudge forward"), String::from("moves the selection forward by one tick of the snap divisor"), |state: &mut AppState| {
+                state.taiko_layer.nudge_selection(&mut state.taiko, 1);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Left), modifier: ModifiersState::CTRL | ModifiersState::SHIFT },
+            Action::new(String::from("shift back"), String::from("moves the selection back by one beat"), |state: &mut AppState| {
+                state.taiko_layer.shift_selection(&mut state.taiko, -1);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::Right), modifier: ModifiersState::CTRL | ModifiersState::SHIFT },
+            Action::new(String::from("shift forward"), String::from("moves the selection forward by one beat"), |state: &mut AppState| {
+                state.taiko_layer.shift_selection(&mut state.taiko, 1);
+            })
+        );
+
+        actions.insert(
+            KeyCombination { key: KeyCode::from(VirtualKeyCode::R), modifier: ModifiersState::default() },
+            Action::new(String::from("toggle recording"), String::from("plays the song and turns drum hits into objects, or stops recording"), |state: &mut AppState| {
+                state.taiko_layer.toggle_recording(&mut state.taiko);
+            }).without_repeat()
+        );
+
+        // Drum keys while recording, same layout as osu!
+        for (key, color) in [(VirtualKeyCode::D, TaikoColor::KAT), (VirtualKeyCode::F, TaikoColor::DON), (VirtualKeyCode::J, TaikoColor::DON), (VirtualKeyCode::K, TaikoColor::KAT)] {
+            actions.insert(
+                KeyCombination { key: KeyCode::from(key), modifier: ModifiersState::default() },
+                Action::new(format!("record {color:?}"), String::from("places an object at the current time while recording"), move |state: &mut AppState| {
+                    state.taiko_layer.record_tap(&mut state.taiko, color);
+                }).without_repeat()
+            );
+        }
+
         // egui
         let scale = graphics.scale;
         let inner_size = graphics.size;
@@ -51,6 +181,16 @@ impl App {
         let sidebar = SidebarView::new();
         let timeline = TimelineWindow::new();
         let file_dialog = FileDialogWindow::new();
+        let save_dialog = SaveDialogWindow::new();
+        let difficulty_picker = DifficultyPickerWindow::new();
+        let performance = PerformanceWindow::new();
+        let checks = ChecksWindow::new();
+        let compare = CompareWindow::new();
+        let timing = TimingWindow::new();
+        let velocity = VelocityWindow::new();
+        let history = HistoryWindow::new();
+        let transform = TransformWindow::new();
+        let properties = PropertiesWindow::new();
 
         // common state
         let state = AppState::new(&graphics);
@@ -59,6 +199,7 @@ impl App {
             window,
             graphics,
             actions,
+            held_keys: HashSet::new(),
 
             egui,
 
@@ -67,6 +208,17 @@ impl App {
             timeline,
     
             file_dialog,
+            save_dialog,
+
+            difficulty_picker,
+            performance,
+            checks,
+            compare,
+            timing,
+            velocity,
+            history,
+            transform,
+            properties,
 
             state,
         };
@@ -83,13 +235,60 @@ impl App {
             label: Some("Render Encoder"),
         });
         
+        // Sets with several difficulties ask which one to open
+        if let Some(set) = &mut self.state.beatmap_set && std::mem::take(&mut set.open_picker) {
+            self.difficulty_picker.set_visible(true);
+        }
+
+        // Edits made last frame, dragging edits every frame so the rating catches up once it ends
+        if self.state.taiko_layer.editor.dragging.is_none() {
+            self.state.refresh_star_rating();
+        }
+
         let (clipped_primitives, commands) = self.egui.prepare(&self.window, &mut self.graphics, &mut encoder, |graphics, ctx| {
-            View::show(&mut self.menu,       (&mut self.state, &mut self.file_dialog), &view, graphics, ctx);
-            View::show(&mut self.timeline,    &mut self.state.taiko_layer,             &view, graphics, ctx);
-            View::show(&mut self.sidebar,     &mut self.state,                         &view, graphics, ctx);
-            View::show(&mut self.file_dialog, &mut self.state,                         &view, graphics, ctx);
+            // Clipboard shortcuts arrive through egui, which owns the clipboard. Text fields get them first.
+            if !ctx.wants_keyboard_input() {
+                for event in ctx.input(|x| x.events.clone()) {
+                    match event {
+                        egui::Event::Copy => if let Some(text) = self.state.taiko_layer.copy() {
+                            ctx.output_mut(|x| x.copied_text = text);
+                        },
+                        egui::Event::Cut => if let Some(text) = self.state.taiko_layer.cut(&mut self.state.taiko) {
+                            ctx.output_mut(|x| x.copied_text = text);
+                        },
+                        egui::Event::Paste(text) => self.state.taiko_layer.paste(&mut self.state.taiko, &text),
+                        _ => {}
+                    }
+                }
+            }
+
+            View::show(&mut self.menu,       (&mut self.state, &mut self.file_dialog, &mut self.save_dialog, &mut self.performance, &mut self.checks, &mut self.compare, &mut self.timing, &mut self.velocity, &mut self.history, &mut self.transform, &mut self.properties), &view, graphics, ctx);
+            View::show(&mut self.timeline,    (&mut self.state.taiko_layer, &mut self.state.taiko),       &view, graphics, ctx);
+            View::show(&mut self.sidebar,     &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.file_dialog, &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.save_dialog, &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.difficulty_picker, &mut self.state,                                      &view, graphics, ctx);
+            View::show(&mut self.performance, &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.checks,      &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.compare,     &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.timing,      &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.velocity,    &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.history,     &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.transform,   &mut self.state,                                            &view, graphics, ctx);
+            View::show(&mut self.properties,  &mut self.state,                                            &view, graphics, ctx);
+
+            // Selection box on the conveyor, which is in physical pixels
+            if let Some((from, to)) = self.state.taiko_layer.selection_box() {
+                let scale = ctx.pixels_per_point();
+                let rect = egui::Rect::from_two_pos(egui::pos2(from.x / scale, from.y / scale), egui::pos2(to.x / scale, to.y / scale));
+                ctx.layer_painter(egui::LayerId::background())
+                   .rect(rect, 0.0, egui::Color32::from_white_alpha(16), egui::Stroke::new(1.0, egui::Color32::WHITE));
+            }
         });
 
+        // Mania shares the taiko clock
+        let mania_time = self.state.taiko_layer.clock.get_time() as i64 - self.state.taiko.audio_offset;
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
@@ -110,6 +309,7 @@ impl App {
             });
 
             Layer::draw(&mut self.state.taiko_layer, &mut self.state.taiko, &mut render_pass, &mut self.graphics);
+            Layer::draw(&mut self.state.mania_layer, (&mut self.state.mania, mania_time), &mut render_pass, &mut self.graphics);
             self.egui.render(&self.graphics, &mut render_pass, &clipped_primitives, commands);
         }
     
@@ -125,12 +325,44 @@ impl App {
         match event {
             WindowEvent::KeyboardInput { input, .. } => {
                 if let Some(key) = input.virtual_keycode && input.state == ElementState::Pressed {
+                    // Held keys keep sending presses, keys typed into a text field aren't shortcuts
+                    let repeated = !self.held_keys.insert(key);
+                    let typing = self.egui.context.wants_keyboard_input();
                     let mods = input.modifiers;
                     let combination = KeyCombination::from((key, mods));
-                    if let Some(action) = self.actions.get_mut(&combination) {
+                    if !typing && let Some(action) = self.actions.get_mut(&combination) && (action.repeat || !repeated) {
                         action.invoke(&mut self.state);
                     }
                 }
+
+                if let Some(key) = input.virtual_keycode && input.state == ElementState::Released {
+                    self.held_keys.remove(&key);
+                }
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let position = vec2(position.x as f32, position.y as f32);
+                self.state.taiko_layer.cursor_moved(&mut self.state.taiko, Some(position));
+            }
+
+            // Releases outside of the window never arrive
+            WindowEvent::Focused(false) => {
+                self.held_keys.clear();
+            }
+
+            WindowEvent::CursorLeft { .. } => {
+                self.state.taiko_layer.cursor_moved(&mut self.state.taiko, None);
+            }
+
+            // Clicks on the conveyor edit the beatmap, unless they land on the UI
+            WindowEvent::MouseInput { state, button, modifiers, .. } => {
+                let over_ui = self.egui.context.is_pointer_over_area() || self.egui.context.is_using_pointer();
+                match (button, state) {
+                    (MouseButton::Left, ElementState::Pressed) if !over_ui => self.state.taiko_layer.primary_pressed(&mut self.state.taiko, *modifiers),
+                    (MouseButton::Left, ElementState::Released) => self.state.taiko_layer.primary_released(&mut self.state.taiko, *modifiers),
+                    (MouseButton::Right, ElementState::Pressed) if !over_ui => self.state.taiko_layer.secondary_pressed(&mut self.state.taiko),
+                    _ => {}
+                }
             }
 
             _ => {}
@@ -149,12 +381,14 @@ impl App {
         }
         
         self.state.taiko_layer.resize(new_size);
+        self.state.mania_layer.resize(new_size);
     }
 
     pub fn scale(&mut self, scale: f64) {
         self.graphics.scale = scale;
         self.egui.scale(scale);
         self.state.taiko_layer.scale(scale);
+        self.state.mania_layer.scale(scale);
     } 
 
     pub fn get_window(&self) -> &Window {
@@ -0,0 +1,254 @@
+use cgmath::{Quaternion, vec3, Zero, vec2, Vector4, vec4};
+use wcore::{graphics::{scene::Scene, camera::{ProjectionOrthographic, Camera2D, Camera}, uniform::Uniform, common::vertex::Vertex, context::Graphics, instance::Instance, bindable::Bindable}, color::Color};
+use wgpu::util::DeviceExt;
+
+use crate::{layer::mania::ManiaState, mania::vsrg_note::VsrgNote, taiko::parser::Beatmap};
+
+use super::model::ManiaNoteModel;
+
+/// Lanes reach far enough to cover the screen at any reasonable scale
+const LANE_HEIGHT: f32 = 8192.0;
+
+pub struct Lanes {
+    pub scene         : Scene<ProjectionOrthographic, Camera2D>,
+    pub time_uniform  : Uniform<Vector4<f32>>,
+    pub note_pipeline : wgpu::RenderPipeline,
+
+    pub vertex_buffer      : wgpu::Buffer,
+    pub vertex_buffer_data : Vec<Vertex>,
+
+    pub note_instance_buffer : wgpu::Buffer,
+    pub note_instances       : Vec<ManiaNoteModel>,
+
+    /// Amount of instances each object is made of, in object order
+    pub object_instances : Vec<usize>,
+
+    pub cull_back           : usize,
+    pub cull_back_instances : usize,
+}
+
+impl Lanes {
+    pub fn new(graphics: &Graphics) -> Self {
+        // Vertices
+        let vertex_buffer_data = Vertex::vertices_quad(-0.5, 0.5);
+        let vertex_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Vertex Buffer"),
+                contents : bytemuck::cast_slice(&vertex_buffer_data),
+                usage    : wgpu::BufferUsages::VERTEX,
+            }
+        );
+
+        // Note instances
+        let note_instances = vec![];
+        let note_instance_data = note_instances.iter().map(Instance::bake).collect::<Vec<_>>();
+        let note_instance_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Instance Buffer"),
+                contents : bytemuck::cast_slice(&note_instance_data),
+                usage    : wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
+        // Scene
+        let scene = Scene::<ProjectionOrthographic, Camera2D> {
+            projection : ProjectionOrthographic::new(graphics.config.width, graphics.config.height, -100.0, 100.0),
+            camera     : Camera2D::new(vec3(0.0, 0.0, -50.0), Quaternion::zero(), vec3(graphics.scale as f32, graphics.scale as f32, 1.0)),
+            uniform    : Uniform::new(&graphics.device),
+        };
+
+        // Time uniform
+        let time_uniform = Uniform::new(&graphics.device);
+
+        // Note pipeline
+        let shader = graphics.device.create_shader_module(wgpu::include_wgsl!("../../../res/mania.wgsl"));
+        let render_pipeline_layout = graphics.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[
+                scene        . layout(),
+                time_uniform . layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let note_pipeline = graphics.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label  : Some("Render Pipeline"),
+            layout : Some(&render_pipeline_layout),
+
+            vertex: wgpu::VertexState {
+                module      : &shader,
+                entry_point : "vs_main",
+                buffers     : &[
+                    Vertex::describe(),
+                    ManiaNoteModel::describe(),
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module      : &shader,
+                entry_point : "fs_main",
+                targets     : &[Some(wgpu::ColorTargetState {
+                    format     : graphics.config.format,
+                    blend      : Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask : wgpu::ColorWrites::ALL,
+                })],
+            }),
+
+            primitive: wgpu::PrimitiveState {
+                topology           : wgpu::PrimitiveTopology::TriangleList,
+                front_face         : wgpu::FrontFace::Ccw,
+                cull_mode          : Some(wgpu::Face::Back),
+                polygon_mode       : wgpu::PolygonMode::Fill, // Others require Features::NON_FILL_POLYGON_MODE
+                unclipped_depth    : false,                   // Requires Features::DEPTH_CLIP_CONTROL
+                conservative       : false,                   // Requires Features::CONSERVATIVE_RASTERIZATION
+                strip_index_format : None,
+            },
+
+            multisample: wgpu::MultisampleState {
+                count                     : 1,
+                mask                      : !0,
+                alpha_to_coverage_enabled : false,
+            },
+
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        return Self {
+            scene,
+            time_uniform,
+            note_pipeline,
+
+            vertex_buffer,
+            vertex_buffer_data,
+
+            note_instance_buffer,
+            note_instances,
+
+            object_instances: vec![],
+
+            cull_back           : 0,
+            cull_back_instances : 0,
+        };
+    }
+
+    pub fn draw<'a: 'b, 'b>(&'a mut self, rebuild_instances: bool, state: &ManiaState, beatmap: &Beatmap<VsrgNote>, time_ms: i64, render_pass: &mut wgpu::RenderPass<'b>, graphics: &mut Graphics) {
+        if rebuild_instances { self.rebuild_instances_beatmap(state, beatmap, graphics); }
+
+        // Note culling, notes are kept until they fully pass the judgement line
+        let margin_ms = (state.note_height / state.scroll_speed) as i64;
+        while self.cull_back > 0 && let Some(note) = beatmap.objects.get(self.cull_back - 1) {
+            if note.end_time().to_ms() as i64 + margin_ms > time_ms { // rewind
+                self.cull_back -= 1;
+                self.cull_back_instances -= self.object_instances.get(self.cull_back).copied().unwrap_or(1);
+            } else { break }
+        }
+
+        while let Some(note) = beatmap.objects.get(self.cull_back) {
+            if note.end_time().to_ms() as i64 + margin_ms <= time_ms {
+                self.cull_back_instances += self.object_instances.get(self.cull_back).copied().unwrap_or(1);
+                self.cull_back += 1;
+            } else { break }
+        }
+
+        // Update scene matrix
+        let scale = graphics.scale as f32 * state.scale;
+        self.scene.camera.set_scale(vec3(scale, scale, 1.0));
+        self.scene.camera.set_x(state.hit_position.x);
+        self.scene.camera.set_y(state.hit_position.y);
+        self.scene.update(&graphics.queue);
+
+        // Update time matrix
+        let time_offset = time_ms as f32 * state.scroll_speed;
+        self.time_uniform.update(&graphics.queue, &vec4(time_offset, 0.0, 0.0, 0.0));
+
+        // Lanes and notes
+        render_pass.set_pipeline(&self.note_pipeline);
+
+        self.scene.bind(render_pass, 0);
+        self.time_uniform.bind(render_pass, 1);
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.note_instance_buffer.slice(..));
+        render_pass.draw(0 .. self.vertex_buffer_data.len() as u32,
+                         0 .. self.note_instances.len().saturating_sub(self.cull_back_instances) as u32);
+    }
+
+    pub fn reset_culling(&mut self) {
+        self.cull_back = 0;
+        self.cull_back_instances = 0;
+    }
+
+    fn rebuild_instances_beatmap(&mut self, state: &ManiaState, beatmap: &Beatmap<VsrgNote>, graphics: &Graphics) {
+        self.note_instances.clear();
+        self.object_instances.clear();
+
+        let key_count = beatmap.key_count();
+        let column_x = |key: usize| (key as f32 + 0.5 - key_count as f32 / 2.0) * state.column_width;
+
+        // Lane backgrounds and the judgement line don't scroll, they go first so they are drawn below notes
+        for key in 0 .. key_count {
+            self.note_instances.push(ManiaNoteModel {
+                position : vec2(column_x(key), -LANE_HEIGHT / 2.0 + state.note_height),
+                size     : vec2(state.column_width, LANE_HEIGHT),
+                color    : state.lane_color,
+                scroll   : false,
+            });
+        }
+
+        self.note_instances.push(ManiaNoteModel {
+            position : vec2(0.0, 0.0),
+            size     : vec2(state.column_width * key_count as f32, 4.0),
+            color    : state.judgement_color,
+            scroll   : false,
+        });
+
+        // Notes are drawn back to front, so culled notes end up at the end of the buffer
+        for note in beatmap.objects.iter().rev() {
+            let y = -(note.time.to_seconds() as f32 * 1000.0 * state.scroll_speed) - state.note_height / 2.0;
+            let color = note_color(state, note.key, key_count);
+            let instances_before = self.note_instances.len();
+
+            if note.is_hold() {
+                let length = note.duration.to_seconds() as f32 * 1000.0 * state.scroll_speed;
+                self.note_instances.push(ManiaNoteModel {
+                    position : vec2(column_x(note.key), y - length / 2.0),
+                    size     : vec2(state.column_width * 0.8, length),
+                    color    : Color::new(color.r * 0.7, color.g * 0.7, color.b * 0.7, 0.9),
+                    scroll   : true,
+                });
+            }
+
+            self.note_instances.push(ManiaNoteModel {
+                position : vec2(column_x(note.key), y),
+                size     : vec2(state.column_width, state.note_height),
+                color    : color,
+                scroll   : true,
+            });
+
+            self.object_instances.push(self.note_instances.len() - instances_before);
+        }
+
+        // Counts were collected back to front
+        self.object_instances.reverse();
+        self.cull_back_instances = self.object_instances.iter().take(self.cull_back).sum();
+
+        let instance_data = self.note_instances.iter().map(Instance::bake).collect::<Vec<_>>();
+        self.note_instance_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Instance Buffer"),
+                contents : bytemuck::cast_slice(&instance_data),
+                usage    : wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+    }
+}
+
+/// Columns are colored symmetrically around the center, odd key counts get a distinct middle column
+fn note_color(state: &ManiaState, key: usize, key_count: usize) -> Color {
+    if key_count % 2 == 1 && key == key_count / 2 {
+        return state.center_color;
+    }
+
+    let mirrored = key.min(key_count - 1 - key);
+    return if mirrored % 2 == 1 { state.secondary_color } else { state.primary_color };
+}
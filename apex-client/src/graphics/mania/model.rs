@@ -0,0 +1,49 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Vector4, Vector2, vec4};
+use wcore::{graphics::instance::Instance, color::Color};
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct BakedManiaNoteModel {
+    pub rect   : Vector4<f32>,
+    pub color  : Vector4<f32>,
+    pub scroll : f32,
+}
+
+/// Axis aligned rectangle, `position` is the center of it.
+/// Instances with `scroll` set move along with time, others stay in place.
+pub struct ManiaNoteModel {
+    pub position : Vector2<f32>,
+    pub size     : Vector2<f32>,
+    pub color    : Color,
+    pub scroll   : bool,
+}
+
+impl ManiaNoteModel {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32,
+    ];
+
+    pub fn describe() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<BakedManiaNoteModel>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS
+        }
+    }
+}
+
+impl Instance for ManiaNoteModel {
+    type Baked = BakedManiaNoteModel;
+
+    fn bake(&self) -> Self::Baked {
+        return BakedManiaNoteModel {
+            rect   : vec4(self.position.x, self.position.y, self.size.x, self.size.y),
+            color  : self.color.into(),
+            scroll : if self.scroll { 1.0 } else { 0.0 },
+        };
+    }
+}
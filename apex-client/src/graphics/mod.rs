@@ -1,2 +1,3 @@
 pub mod taiko;
+pub mod mania;
 pub mod util;
\ No newline at end of file
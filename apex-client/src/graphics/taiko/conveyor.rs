@@ -1,12 +1,14 @@
-use cgmath::{Quaternion, vec3, Zero, vec2, Vector4, vec4};
-use wcore::graphics::{texture::Texture, scene::Scene, camera::{ProjectionOrthographic, Camera2D, Camera}, uniform::Uniform, common::{vertex::Vertex, model::Model}, context::Graphics, instance::Instance, bindable::Bindable};
+use cgmath::{Quaternion, vec3, Zero, vec2, Vector2, Vector4, vec4};
+use wcore::{time::Time, color::Color, graphics::{texture::Texture, scene::Scene, camera::{ProjectionOrthographic, Camera2D, Camera}, uniform::Uniform, common::{vertex::Vertex, model::Model}, context::Graphics, instance::Instance, bindable::Bindable}};
 use wgpu::util::DeviceExt;
 
-use crate::{layer::taiko::TaikoState, taiko::{parser::Beatmap, taiko_circle::TaikoColor}};
+use crate::{layer::taiko::TaikoState, taiko::{parser::Beatmap, taiko_circle::{TaikoColor, TaikoKind}}};
 
-use super::model::TaikoHitObjectModel;
+use super::model::{TaikoHitObjectModel, TaikoHitObjectKind, BarlineModel};
 
 const CIRCLE_SIZE: f32 = 128.0;
+const FLASH_SIZE: f32 = 320.0;
+const BARLINE_WIDTH: f32 = 3.0;
 
 pub struct Conveyor {
     pub t_hitcircle  : Texture,
@@ -19,6 +21,8 @@ pub struct Conveyor {
     pub time_uniform    : Uniform<Vector4<f32>>,
     pub circle_pipeline : wgpu::RenderPipeline,
     pub hitpos_pipeline : wgpu::RenderPipeline,
+    pub flash_pipeline  : wgpu::RenderPipeline,
+    pub barline_pipeline : wgpu::RenderPipeline,
 
     pub vertex_buffer      : wgpu::Buffer,
     pub vertex_buffer_data : Vec<Vertex>,
@@ -29,7 +33,17 @@ pub struct Conveyor {
     pub hitpos_instance_buffer : wgpu::Buffer,
     pub hitpos_instances       : Vec<Model>,
 
-    pub cull_back : usize,
+    pub flash_instance_buffer : wgpu::Buffer,
+    pub flash_instances       : Vec<Model>,
+
+    pub barline_instance_buffer : wgpu::Buffer,
+    pub barline_instances       : Vec<BarlineModel>,
+
+    /// Amount of instances each object is made of, in object order
+    pub object_instances : Vec<usize>,
+
+    pub cull_back           : usize,
+    pub cull_back_instances : usize,
 }
 
 impl Conveyor {
@@ -62,6 +76,28 @@ impl Conveyor {
             }
         );
 
+        // Kiai flash instances
+        let flash_instances = vec![ Model { position: vec3(0.0, 0.0, 0.0), scale: vec3(FLASH_SIZE, FLASH_SIZE, 1.0), rotation: Quaternion::zero() } ];
+        let flash_instance_data = flash_instances.iter().map(Instance::bake).collect::<Vec<_>>();
+        let flash_instance_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Instance Buffer"),
+                contents : bytemuck::cast_slice(&flash_instance_data),
+                usage    : wgpu::BufferUsages::VERTEX,
+            }
+        );
+
+        // Barline instances
+        let barline_instances = vec![];
+        let barline_instance_data = barline_instances.iter().map(Instance::bake).collect::<Vec<_>>();
+        let barline_instance_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Instance Buffer"),
+                contents : bytemuck::cast_slice(&barline_instance_data),
+                usage    : wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+
         // Circle instances
         let circle_instances = vec![];
         let circle_instance_data = circle_instances.iter().map(Instance::bake).collect::<Vec<_>>();
@@ -135,6 +171,112 @@ impl Conveyor {
             multiview: None,
         });
 
+        // Kiai flash pipeline
+        let shader = graphics.device.create_shader_module(wgpu::include_wgsl!("../../../res/flash.wgsl"));
+        let render_pipeline_layout = graphics.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[
+                scene        . layout(),
+                time_uniform . layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let flash_pipeline = graphics.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label  : Some("Render Pipeline"),
+            layout : Some(&render_pipeline_layout),
+
+            vertex: wgpu::VertexState {
+                module      : &shader,
+                entry_point : "vs_main",
+                buffers     : &[
+                    Vertex::describe(),
+                    Model::describe(),
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module      : &shader,
+                entry_point : "fs_main",
+                targets     : &[Some(wgpu::ColorTargetState {
+                    format     : graphics.config.format,
+                    blend      : Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask : wgpu::ColorWrites::ALL,
+                })],
+            }),
+
+            primitive: wgpu::PrimitiveState {
+                topology           : wgpu::PrimitiveTopology::TriangleList,
+                front_face         : wgpu::FrontFace::Ccw,
+                cull_mode          : Some(wgpu::Face::Back),
+                polygon_mode       : wgpu::PolygonMode::Fill, // Others require Features::NON_FILL_POLYGON_MODE
+                unclipped_depth    : false,                   // Requires Features::DEPTH_CLIP_CONTROL
+                conservative       : false,                   // Requires Features::CONSERVATIVE_RASTERIZATION
+                strip_index_format : None,
+            },
+
+            multisample: wgpu::MultisampleState {
+                count                     : 1,
+                mask                      : !0,
+                alpha_to_coverage_enabled : false,
+            },
+
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        // Barline pipeline
+        let shader = graphics.device.create_shader_module(wgpu::include_wgsl!("../../../res/barline.wgsl"));
+        let render_pipeline_layout = graphics.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[
+                scene        . layout(),
+                time_uniform . layout(),
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let barline_pipeline = graphics.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label  : Some("Render Pipeline"),
+            layout : Some(&render_pipeline_layout),
+
+            vertex: wgpu::VertexState {
+                module      : &shader,
+                entry_point : "vs_main",
+                buffers     : &[
+                    Vertex::describe(),
+                    BarlineModel::describe(),
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module      : &shader,
+                entry_point : "fs_main",
+                targets     : &[Some(wgpu::ColorTargetState {
+                    format     : graphics.config.format,
+                    blend      : Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask : wgpu::ColorWrites::ALL,
+                })],
+            }),
+
+            primitive: wgpu::PrimitiveState {
+                topology           : wgpu::PrimitiveTopology::TriangleList,
+                front_face         : wgpu::FrontFace::Ccw,
+                cull_mode          : Some(wgpu::Face::Back),
+                polygon_mode       : wgpu::PolygonMode::Fill, // Others require Features::NON_FILL_POLYGON_MODE
+                unclipped_depth    : false,                   // Requires Features::DEPTH_CLIP_CONTROL
+                conservative       : false,                   // Requires Features::CONSERVATIVE_RASTERIZATION
+                strip_index_format : None,
+            },
+
+            multisample: wgpu::MultisampleState {
+                count                     : 1,
+                mask                      : !0,
+                alpha_to_coverage_enabled : false,
+            },
+
+            depth_stencil: None,
+            multiview: None,
+        });
+
         // Circle pipeline
         let shader = graphics.device.create_shader_module(wgpu::include_wgsl!("../../../res/taiko.wgsl"));
         let render_pipeline_layout = graphics.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -146,6 +288,7 @@ impl Conveyor {
                 hitoverlay  . layout(),
                 bigcircle   . layout(),
                 bigoverlay  . layout(),
+                hitposition . layout(),
             ],
             push_constant_ranges: &[],
         });
@@ -203,6 +346,8 @@ impl Conveyor {
             time_uniform,
             circle_pipeline,
             hitpos_pipeline,
+            flash_pipeline,
+            barline_pipeline,
 
             vertex_buffer,
             vertex_buffer_data,
@@ -211,18 +356,24 @@ impl Conveyor {
             circle_instances,
             hitpos_instance_buffer,
             hitpos_instances,
+            flash_instance_buffer,
+            flash_instances,
+            barline_instance_buffer,
+            barline_instances,
 
-            cull_back: 0,
+            object_instances: vec![],
+
+            cull_back           : 0,
+            cull_back_instances : 0,
         };
     }
 
-    pub fn draw<'a: 'b, 'b>(&'a mut self, rebuild_instances: bool, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, render_pass: &mut wgpu::RenderPass<'b>, graphics: &mut Graphics) {
-        if rebuild_instances { self.rebuild_instances_beatmap(state, beatmap, graphics); }
-
+    pub fn draw<'a: 'b, 'b>(&'a mut self, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, render_pass: &mut wgpu::RenderPass<'b>, graphics: &mut Graphics) {
         // Circle culling
         if state.hit_circles {
             while let Some(circle) = beatmap.objects.get(self.cull_back) {
-                if circle.time.to_ms() as i64 + state.audio_offset <= time_ms as i64 {
+                if circle.end_time().to_ms() as i64 + state.audio_offset <= time_ms as i64 {
+                    self.cull_back_instances += self.object_instances.get(self.cull_back).copied().unwrap_or(1);
                     self.cull_back += 1;
                 } else { break }
             }
@@ -237,8 +388,22 @@ impl Conveyor {
         
         // Update time matrix
         let time_offset = (- (time_ms as f32) + state.audio_offset as f32) * state.zoom * beatmap.velocity_multiplier;
-        self.time_uniform.update(&graphics.queue, &vec4(time_offset, 0.0, 0.0, 0.0));
-        
+        let kiai = kiai_pulse(beatmap, Time::from_ms(time_ms as f64 - state.audio_offset as f64));
+        self.time_uniform.update(&graphics.queue, &vec4(time_offset, kiai, 0.0, 0.0));
+
+        // Kiai flash, below everything else
+        if kiai > 0.0 {
+            render_pass.set_pipeline(&self.flash_pipeline);
+
+            self.scene.bind(render_pass, 0);
+            self.time_uniform.bind(render_pass, 1);
+
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, self.flash_instance_buffer.slice(..));
+            render_pass.draw(0 .. self.vertex_buffer_data.len() as u32,
+                             0 .. self.flash_instances.len() as u32);
+        }
+
         // Hit position
         render_pass.set_pipeline(&self.hitpos_pipeline);
 
@@ -250,6 +415,17 @@ impl Conveyor {
         render_pass.draw(0 .. self.vertex_buffer_data.len() as u32, 
                          0 .. self.hitpos_instances.len() as u32);
 
+        // Barlines, behind circles
+        render_pass.set_pipeline(&self.barline_pipeline);
+
+        self.scene.bind(render_pass, 0);
+        self.time_uniform.bind(render_pass, 1);
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.barline_instance_buffer.slice(..));
+        render_pass.draw(0 .. self.vertex_buffer_data.len() as u32,
+                         0 .. self.barline_instances.len() as u32);
+
         // Circles
         render_pass.set_pipeline(&self.circle_pipeline);
         
@@ -259,34 +435,167 @@ impl Conveyor {
         render_pass.set_bind_group(3, &self.t_hitoverlay.bind_group, &[]);
         render_pass.set_bind_group(4, &self.t_bigcircle.bind_group, &[]);
         render_pass.set_bind_group(5, &self.t_bigoverlay.bind_group, &[]);
+        render_pass.set_bind_group(6, &self.t_hitposition.bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_vertex_buffer(1, self.circle_instance_buffer.slice(..));
         render_pass.draw(0 ..  self.vertex_buffer_data  .len()                   as u32, 
-                         0 .. (self.circle_instances.len() - self.cull_back) as u32);
+                         0 .. self.circle_instances.len().saturating_sub(self.cull_back_instances) as u32);
+    }
+
+    pub fn reset_culling(&mut self) {
+        self.cull_back = 0;
+        self.cull_back_instances = 0;
+    }
+
+    /// Screen position of an object at `time`, in physical pixels
+    pub fn screen_x(&mut self, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, time: Time) -> f32 {
+        let scale = self.scene.camera.get_scale().x;
+        let distance = (time.to_seconds() * 1000.0 - time_ms as f64 + state.audio_offset as f64) as f32;
+        return scale * (distance * state.zoom * beatmap.velocity_multiplier * scroll_velocity(beatmap, time) + state.hit_position.x);
+    }
+
+    /// Inverse of [`Conveyor::screen_x`], where velocity changes objects may overlap so this picks the one closest to the current time
+    pub fn time_at(&mut self, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, x: f32) -> Time {
+        let scale = self.scene.camera.get_scale().x;
+        let now = time_ms as f64 - state.audio_offset as f64;
+        let distance = (x / scale - state.hit_position.x) / (state.zoom * beatmap.velocity_multiplier);
+
+        // Velocity depends on the answer, a second pass settles it
+        let mut time = now + (distance / scroll_velocity(beatmap, Time::from_ms(now))) as f64;
+        time = now + (distance / scroll_velocity(beatmap, Time::from_ms(time))) as f64;
+        return Time::from_ms(time);
+    }
+
+    /// Object drawn under the given screen position, in physical pixels
+    pub fn object_at(&mut self, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, x: f32, y: f32) -> Option<usize> {
+        let scale = self.scene.camera.get_scale().x;
+        let center = scale * state.hit_position.y;
+
+        let mut closest = None::<(usize, f32)>;
+        for (idx, object) in beatmap.objects.iter().enumerate().skip(self.cull_back) {
+            let radius = scale * if object.big || matches!(object.kind, TaikoKind::Swell { .. }) { CIRCLE_SIZE * 1.55 } else { CIRCLE_SIZE } / 2.0;
+            if (y - center).abs() > radius { continue }
+
+            let start = self.screen_x(state, beatmap, time_ms, object.time);
+            let end = self.screen_x(state, beatmap, time_ms, object.end_time());
+            let distance = if x >= start && x <= end { 0.0 } else { (x - start).abs().min((x - end).abs()) };
+            if distance <= radius && !closest.is_some_and(|(_, x)| distance > x) {
+                closest = Some((idx, distance));
+            }
+        }
+
+        return closest.map(|(idx, _)| idx);
+    }
+
+    /// Objects touching the rectangle between two screen positions, in physical pixels
+    pub fn objects_in(&mut self, state: &TaikoState, beatmap: &Beatmap, time_ms: u32, a: Vector2<f32>, b: Vector2<f32>) -> Vec<usize> {
+        let scale = self.scene.camera.get_scale().x;
+        let center = scale * state.hit_position.y;
+        let (min, max) = (vec2(a.x.min(b.x), a.y.min(b.y)), vec2(a.x.max(b.x), a.y.max(b.y)));
+
+        let mut indices = vec![];
+        for (idx, object) in beatmap.objects.iter().enumerate().skip(self.cull_back) {
+            let radius = scale * if object.big || matches!(object.kind, TaikoKind::Swell { .. }) { CIRCLE_SIZE * 1.55 } else { CIRCLE_SIZE } / 2.0;
+            if center + radius < min.y || center - radius > max.y { continue }
+
+            let start = self.screen_x(state, beatmap, time_ms, object.time) - radius;
+            let end = self.screen_x(state, beatmap, time_ms, object.end_time()) + radius;
+            if end >= min.x && start <= max.x {
+                indices.push(idx);
+            }
+        }
+
+        return indices;
     }
 
-    fn rebuild_instances_beatmap(&mut self, state: &TaikoState, beatmap: &Beatmap, graphics: &Graphics) {
+    /// Selected objects are highlighted
+    pub fn rebuild_instances_beatmap(&mut self, state: &TaikoState, beatmap: &Beatmap, selection: &[usize], graphics: &Graphics) {
         self.circle_instances.clear();
-        
-        let mut idx = beatmap.velocity.len() - 1;
-        for obj in beatmap.objects.iter().rev() {
-            while beatmap.velocity[idx].time > obj.time && idx != 0 { idx -= 1; }
-            let velocity = beatmap.velocity[idx].velocity;
-
-            self.circle_instances.push(TaikoHitObjectModel {
-                time: obj.time.to_ms() as f32 * state.zoom * beatmap.velocity_multiplier,
-                size: if obj.big { vec2(CIRCLE_SIZE * 1.55 / velocity as f32, CIRCLE_SIZE * 1.55) }
-                      else       { vec2(CIRCLE_SIZE        / velocity as f32, CIRCLE_SIZE       ) },
-
-                color: if obj.color == TaikoColor::KAT { state.kat_color }  // vec4(0.0, 0.47, 0.67, 1.0)
-                       else                            { state.don_color }, // vec4(0.92, 0.0, 0.27, 1.0)
-                
-                finisher: obj.big,
-                velocity: velocity as f32,
-            });
+        self.object_instances.clear();
+
+        let scale = state.zoom * beatmap.velocity_multiplier;
+        self.rebuild_instances_barlines(state, beatmap, scale, graphics);
+
+        let mut idx = beatmap.velocity.len();
+        for (object_idx, obj) in beatmap.objects.iter().enumerate().rev() {
+            while idx > 1 && beatmap.velocity[idx - 1].time > obj.time { idx -= 1; }
+            let velocity = beatmap.velocity.get(idx.wrapping_sub(1)).map(|x| x.velocity).unwrap_or(1.0) as f32;
+
+            let time = obj.time.to_seconds() as f32 * 1000.0 * scale;
+            let size = if obj.big { CIRCLE_SIZE * 1.55 } else { CIRCLE_SIZE };
+            let instances_before = self.circle_instances.len();
+            let selected = selection.binary_search(&object_idx).is_ok();
+
+            // Instances are drawn back to front, so parts which should be on top go last
+            match obj.kind {
+                TaikoKind::Circle => {
+                    self.circle_instances.push(TaikoHitObjectModel {
+                        time     : time,
+                        size     : vec2(size / velocity, size),
+                        color    : highlight(if obj.color == TaikoColor::KAT { state.kat_color }  // vec4(0.0, 0.47, 0.67, 1.0)
+                                             else                            { state.don_color }, // vec4(0.92, 0.0, 0.27, 1.0)
+                                             selected),
+                        finisher : obj.big,
+                        velocity : velocity,
+                        kind     : TaikoHitObjectKind::Circle,
+                    });
+                }
+
+                TaikoKind::Drumroll { duration, .. } => {
+                    let length = duration.to_seconds() as f32 * 1000.0 * scale;
+
+                    // Tail cap
+                    self.circle_instances.push(TaikoHitObjectModel {
+                        time     : time + length,
+                        size     : vec2(size / velocity, size),
+                        color    : highlight(state.roll_color, selected),
+                        finisher : obj.big,
+                        velocity : velocity,
+                        kind     : TaikoHitObjectKind::Circle,
+                    });
+
+                    // Body
+                    self.circle_instances.push(TaikoHitObjectModel {
+                        time     : time + length / 2.0,
+                        size     : vec2(length, size * 0.8),
+                        color    : highlight(state.roll_color, selected),
+                        finisher : obj.big,
+                        velocity : velocity,
+                        kind     : TaikoHitObjectKind::Body,
+                    });
+
+                    // Head
+                    self.circle_instances.push(TaikoHitObjectModel {
+                        time     : time,
+                        size     : vec2(size / velocity, size),
+                        color    : highlight(state.roll_color, selected),
+                        finisher : obj.big,
+                        velocity : velocity,
+                        kind     : TaikoHitObjectKind::Circle,
+                    });
+                }
+
+                TaikoKind::Swell { .. } => {
+                    let size = CIRCLE_SIZE * 1.55;
+                    self.circle_instances.push(TaikoHitObjectModel {
+                        time     : time,
+                        size     : vec2(size / velocity, size),
+                        color    : highlight(state.swell_color, selected),
+                        finisher : true,
+                        velocity : velocity,
+                        kind     : TaikoHitObjectKind::Swell,
+                    });
+                }
+            }
+
+            self.object_instances.push(self.circle_instances.len() - instances_before);
         }
 
+        // Counts were collected back to front
+        self.object_instances.reverse();
+        self.cull_back_instances = self.object_instances.iter().take(self.cull_back).sum();
+
         let instance_data = self.circle_instances.iter().map(Instance::bake).collect::<Vec<_>>();
         self.circle_instance_buffer = graphics.device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
@@ -297,4 +606,47 @@ impl Conveyor {
         );
     }
 
-}
\ No newline at end of file
+    fn rebuild_instances_barlines(&mut self, state: &TaikoState, beatmap: &Beatmap, scale: f32, graphics: &Graphics) {
+        self.barline_instances = beatmap.barlines().into_iter().map(|time| {
+            // Same lookup circles use, so barlines scroll along with them
+            let velocity = scroll_velocity(beatmap, time);
+            BarlineModel {
+                time     : time.to_seconds() as f32 * 1000.0 * scale,
+                size     : vec2(BARLINE_WIDTH / velocity, CIRCLE_SIZE * 1.55),
+                color    : state.barline_color,
+                velocity : velocity,
+            }
+        }).collect();
+
+        let instance_data = self.barline_instances.iter().map(Instance::bake).collect::<Vec<_>>();
+        self.barline_instance_buffer = graphics.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label    : Some("Instance Buffer"),
+                contents : bytemuck::cast_slice(&instance_data),
+                usage    : wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            }
+        );
+    }
+}
+
+/// Selected objects are drawn lighter
+fn highlight(color: Color, selected: bool) -> Color {
+    if !selected { return color }
+    let lighten = |x: f32| x + (1.0 - x) * 0.6;
+    return Color::new(lighten(color.r), lighten(color.g), lighten(color.b), color.a);
+}
+
+/// Velocity objects at `time` scroll with, the first point also covers everything before it
+fn scroll_velocity(beatmap: &Beatmap, time: Time) -> f32 {
+    let idx = beatmap.velocity.partition_point(|x| x.time <= time);
+    return beatmap.velocity.get(idx.saturating_sub(1)).map(|x| x.velocity).unwrap_or(1.0) as f32;
+}
+
+/// Kiai strength at the given time, peaks on every beat and fades out until the next one
+fn kiai_pulse(beatmap: &Beatmap, time: Time) -> f32 {
+    if !beatmap.is_kiai(time) { return 0.0 }
+    let Some(point) = beatmap.timing_at(time) else { return 1.0 };
+
+    let beat = (time.to_seconds() - point.time.to_seconds()) * 1000.0 / point.beat_length();
+    return 1.0 - beat.rem_euclid(1.0) as f32;
+}
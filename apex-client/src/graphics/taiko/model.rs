@@ -9,6 +9,15 @@ pub struct BakedTaikoHitObjectModel {
     pub velocity    : f32,
     pub color       : Vector4<f32>,
     pub finisher    : u32,
+    pub kind        : u32,
+}
+
+/// Must be kept in sync with `taiko.wgsl`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TaikoHitObjectKind {
+    Circle = 0,
+    Body   = 1,
+    Swell  = 2,
 }
 
 pub struct TaikoHitObjectModel {
@@ -17,14 +26,16 @@ pub struct TaikoHitObjectModel {
     pub color    : Color,
     pub finisher : bool,
     pub velocity : f32,
+    pub kind     : TaikoHitObjectKind,
 }
 
 impl TaikoHitObjectModel {
-    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
         2 => Float32x3,
         3 => Float32,
         4 => Float32x4,
         5 => Uint32,
+        6 => Uint32,
     ];
 
     pub fn describe() -> wgpu::VertexBufferLayout<'static> {
@@ -46,6 +57,51 @@ impl Instance for TaikoHitObjectModel {
             velocity    : self.velocity,
             color       : self.color.into(),
             finisher    : if self.finisher { 1 } else { 0 },
+            kind        : self.kind as u32,
+        };
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct BakedBarlineModel {
+    pub size_offset : Vector3<f32>,
+    pub velocity    : f32,
+    pub color       : Vector4<f32>,
+}
+
+pub struct BarlineModel {
+    pub time     : f32,
+    pub size     : cgmath::Vector2<f32>,
+    pub color    : Color,
+    pub velocity : f32,
+}
+
+impl BarlineModel {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        2 => Float32x3,
+        3 => Float32,
+        4 => Float32x4,
+    ];
+
+    pub fn describe() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<BakedBarlineModel>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS
+        }
+    }
+}
+
+impl Instance for BarlineModel {
+    type Baked = BakedBarlineModel;
+
+    fn bake(&self) -> Self::Baked {
+        return BakedBarlineModel {
+            size_offset : vec3(self.size.x, self.size.y, self.time),
+            velocity    : self.velocity,
+            color       : self.color.into(),
         };
     }
 }
\ No newline at end of file
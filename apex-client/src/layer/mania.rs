@@ -0,0 +1,84 @@
+use cgmath::{vec3, vec2, Vector2};
+use wcore::{graphics::{context::Graphics, camera::{Projection, Camera}, layer::Layer}, color::Color};
+use winit::dpi::PhysicalSize;
+
+use crate::{mania::vsrg_note::VsrgNote, taiko::parser::Beatmap, graphics::mania::lanes::Lanes};
+
+pub struct ManiaState {
+    // Settings
+    pub scale           : f32,
+    pub scroll_speed    : f32, // px per ms
+    pub hit_position    : Vector2<f32>,
+    pub column_width    : f32,
+    pub note_height     : f32,
+    pub primary_color   : Color,
+    pub secondary_color : Color,
+    pub center_color    : Color,
+    pub lane_color      : Color,
+    pub judgement_color : Color,
+
+    // Internal
+    pub rebuild_pending : bool,
+}
+
+impl ManiaState {
+    pub fn new() -> Self {
+        return Self {
+            scale           : 0.8,
+            scroll_speed    : 1.2,
+            hit_position    : vec2(625.0, 440.0),
+            column_width    : 72.0,
+            note_height     : 24.0,
+            primary_color   : Color::new(0.902, 0.902, 0.902, 1.0),
+            secondary_color : Color::new(0.400, 0.702, 0.961, 1.0),
+            center_color    : Color::new(0.976, 0.780, 0.310, 1.0),
+            lane_color      : Color::new(0.020, 0.020, 0.024, 0.9),
+            judgement_color : Color::new(0.800, 0.800, 0.800, 1.0),
+
+            rebuild_pending : false,
+        };
+    }
+}
+
+/// Shares audio and clock with the taiko layer, only one of them has a beatmap loaded at a time.
+pub struct ManiaLayer {
+    pub beatmap : Option<Beatmap<VsrgNote>>,
+
+    pub lanes : Lanes,
+}
+
+impl ManiaLayer {
+    pub fn new(graphics: &Graphics) -> Self {
+        return Self {
+            beatmap : None,
+
+            lanes : Lanes::new(graphics),
+        };
+    }
+
+    pub fn close_beatmap(&mut self) {
+        self.beatmap = None;
+        self.lanes.reset_culling();
+    }
+}
+
+/// Time is in ms, with audio offset already applied
+impl Layer<(&mut ManiaState, i64)> for ManiaLayer {
+    fn draw<'a: 'b, 'b>(&'a mut self, (state, time_ms): (&mut ManiaState, i64), render_pass: &mut wgpu::RenderPass<'b>, graphics: &mut Graphics) {
+        let rebuild_instances = state.rebuild_pending;
+        if state.rebuild_pending { state.rebuild_pending = false; }
+
+        let Some(beatmap) = &self.beatmap else { return };
+        self.lanes.draw(rebuild_instances, state, beatmap, time_ms, render_pass, graphics);
+    }
+
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        let PhysicalSize::<u32> { width, height } = new_size;
+        self.lanes.scene.projection.resize(width, height);
+    }
+
+    fn scale(&mut self, scale: f64) {
+        let scale = scale as f32;
+        self.lanes.scene.camera.set_scale(vec3(scale, scale, 1.0));
+    }
+}
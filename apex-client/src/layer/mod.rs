@@ -1 +1,2 @@
-pub mod taiko;
\ No newline at end of file
+pub mod taiko;
+pub mod mania;
\ No newline at end of file
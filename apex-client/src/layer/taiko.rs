@@ -1,11 +1,13 @@
 use std::time::Duration;
 
-use cgmath::{vec3, vec2, Vector2};
+use cgmath::{vec3, vec2, Vector2, InnerSpace};
 use wcore::{audio::Audio, clock::{SyncClock, Clock}, time::Time, graphics::{context::Graphics, camera::{Projection, Camera}, layer::Layer}, color::Color};
-use winit::dpi::PhysicalSize;
+use winit::{dpi::PhysicalSize, event::ModifiersState};
 
-use crate::{taiko::{parser::Beatmap}, graphics::taiko::{conveyor::Conveyor}};
+use crate::{taiko::{parser::Beatmap, taiko_circle::TaikoColor, editor, history::History, transform::{self, Transform}}, graphics::taiko::{conveyor::Conveyor}};
 
+/// Distance in physical pixels a press on empty space has to move to start a selection box
+const BOX_THRESHOLD: f32 = 4.0;
 
 pub struct TaikoState {
     // Settings
@@ -15,6 +17,12 @@ pub struct TaikoState {
     pub zoom         : f32,
     pub don_color    : Color,
     pub kat_color    : Color,
+    pub roll_color   : Color,
+    pub swell_color  : Color,
+    pub barline_color : Color,
+
+    // Editing
+    pub snap_divisor : u32,
     
     // Debug
     pub force_rebuild : bool,
@@ -24,6 +32,9 @@ pub struct TaikoState {
 
     // Internal
     pub rebuild_pending : bool,
+
+    /// Bumped whenever the beatmap changes, caches built from it compare against this
+    pub revision : u64,
 }
 
 impl TaikoState {
@@ -35,12 +46,66 @@ impl TaikoState {
             zoom         : 1.0,
             don_color    : Color::new(0.973, 0.596, 0.651, 1.0),
             kat_color    : Color::new(0.741, 0.698, 0.827, 1.0),
+            roll_color   : Color::new(0.988, 0.839, 0.522, 1.0),
+            swell_color  : Color::new(0.976, 0.702, 0.494, 1.0),
+            barline_color : Color::new(0.85, 0.85, 0.85, 1.0),
+
+            snap_divisor : 4,
 
             force_rebuild: false,
 
             hit_circles : true,
 
             rebuild_pending : false,
+
+            revision : 0,
+        };
+    }
+}
+
+/// Mouse editing on the conveyor
+pub struct TaikoEditor {
+    /// Physical pixels, `None` while the pointer is outside of the window
+    pub cursor    : Option<Vector2<f32>>,
+
+    /// Sorted object indices
+    pub selection : Vec<usize>,
+
+    /// Where shift clicks select from
+    pub anchor    : Option<usize>,
+
+    /// Object the drag started on, the rest of the selection follows it
+    pub dragging  : Option<usize>,
+
+    /// Press on empty space, turns into a selection box once the cursor moves away
+    pub pressed   : Option<Vector2<f32>>,
+    pub boxing    : bool,
+
+    /// Placed by the next click
+    pub color     : TaikoColor,
+    pub big       : bool,
+
+    /// Taps place objects while the song plays, `take` holds the times tapped since recording started
+    pub recording : bool,
+    pub take      : Vec<Time>,
+}
+
+impl TaikoEditor {
+    pub fn new() -> Self {
+        return Self {
+            cursor    : None,
+
+            selection : vec![],
+            anchor    : None,
+            dragging  : None,
+            pressed   : None,
+            boxing    : false,
+
+            color     : TaikoColor::DON,
+            big       : false,
+
+            recording : false,
+            take      : vec![],
         };
     }
 }
@@ -52,6 +117,8 @@ pub struct TaikoLayer {
     pub beatmap : Option<Beatmap>,
 
     pub conveyor : Conveyor,
+    pub editor   : TaikoEditor,
+    pub history  : History,
 }
 
 impl TaikoLayer {
@@ -63,6 +130,8 @@ impl TaikoLayer {
             beatmap : None,
 
             conveyor : Conveyor::new(graphics),
+            editor   : TaikoEditor::new(),
+            history  : History::new(),
         };
     }
 }
@@ -74,7 +143,8 @@ impl Layer<&mut TaikoState> for TaikoLayer {
         
         let Some(beatmap) = &self.beatmap else { return };
         let time_ms = self.clock.get_time();
-        self.conveyor.draw(rebuild_instances, state, beatmap, time_ms, render_pass, graphics);
+        if rebuild_instances { self.conveyor.rebuild_instances_beatmap(state, beatmap, &self.editor.selection, graphics); }
+        self.conveyor.draw(state, beatmap, time_ms, render_pass, graphics);
     }
 
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -101,13 +171,48 @@ impl TaikoLayer {
 
         // Beatmap
         self.beatmap = None;
+        self.editor = TaikoEditor::new();
+        self.history = History::new();
     }
 
     // Timeline
-    pub fn timeline_move_forward(&mut self, _state: &mut TaikoState, _value: f32) {
+    /// Steps forward by `value` ticks of the snap divisor
+    pub fn timeline_move_forward(&mut self, state: &mut TaikoState, value: f32) {
+        self.timeline_move(state, value.round() as i32);
     }
 
-    pub fn timeline_move_back(&mut self, _state: &mut TaikoState, _value: f32) {
+    /// Steps back by `value` ticks of the snap divisor
+    pub fn timeline_move_back(&mut self, state: &mut TaikoState, value: f32) {
+        self.timeline_move(state, -value.round() as i32);
+    }
+
+    /// Positions off the grid move to the closest tick in that direction first
+    fn timeline_move(&mut self, state: &TaikoState, steps: i32) {
+        let Some(beatmap) = &self.beatmap else { return };
+        let mut time = self.clock.get_time() as f64 - state.audio_offset as f64;
+
+        for _ in 0 .. steps.unsigned_abs() {
+            // Going back across a timing point uses the grid of the previous one
+            let lookup = if steps < 0 { time - 1.0 } else { time };
+            let Some(point) = beatmap.timing_at(Time::from_ms(lookup)) else { return };
+            let step = point.beat_length() / state.snap_divisor.max(1) as f64;
+            if !step.is_finite() || step <= 0.0 { return }
+
+            let origin = point.time.to_seconds() * 1000.0;
+            // Ticks are rounded to whole milliseconds, so anything within one counts as on the grid
+            let position = (time - origin) / step;
+            let tolerance = 1.0 / step;
+            let target = if steps > 0 { (position + tolerance).floor() + 1.0 } else { (position - tolerance).ceil() - 1.0 };
+            time = (origin + target * step).round();
+
+            // Don't skip over the next timing point, its grid takes over there
+            if steps > 0 && let Some(next) = beatmap.timing.iter().find(|x| x.time.to_seconds() * 1000.0 > lookup + 1e-3)
+            && next.time.to_seconds() * 1000.0 < time {
+                time = next.time.to_seconds() * 1000.0;
+            }
+        }
+
+        self.seek(state, Time::from_ms(time));
     }
 
     // Time
@@ -135,8 +240,16 @@ impl TaikoLayer {
         self.audio.set_time(time);
 
         // In case of rewind
-        self.conveyor.cull_back = 0;
+        self.conveyor.reset_culling();
     }
+
+    /// Pauses playback where an object at `time` sits on the hit position
+    pub fn seek(&mut self, state: &TaikoState, time: Time) {
+        let time = time.to_seconds() * 1000.0 + state.audio_offset as f64;
+        self.set_paused(true);
+        self.set_time(time.clamp(0.0, self.get_length() as f64) as u32);
+    }
+
     pub fn get_time(&mut self) -> Time {
         return Time::from_ms(self.clock.get_time());
     }
@@ -144,4 +257,316 @@ impl TaikoLayer {
     pub fn get_length(&self) -> u32 {
         return self.clock.get_length();
     }
+
+    // Editing
+    pub fn cursor_moved(&mut self, state: &mut TaikoState, position: Option<Vector2<f32>>) {
+        self.editor.cursor = position;
+
+        // Presses on empty space become a selection box after a few pixels
+        if let (Some(pressed), Some(cursor)) = (self.editor.pressed, position) && (cursor - pressed).magnitude() > BOX_THRESHOLD {
+            self.editor.boxing = true;
+        }
+
+        // Dragged objects follow the cursor in time, keeping their distance to each other
+        let time_ms = self.clock.get_time();
+        let Some(beatmap) = &self.beatmap else { return };
+        let (Some(idx), Some(cursor)) = (self.editor.dragging, position) else { return };
+        let Some(object) = beatmap.objects.get(idx) else { return };
+
+        let time = beatmap.snap(self.conveyor.time_at(state, beatmap, time_ms, cursor.x), state.snap_divisor);
+        if time == object.time { return }
+
+        let delta = time - object.time;
+        let times = self.editor.selection.iter().filter_map(|x| beatmap.objects.get(*x)).map(|x| Time::from_ms(((x.time + delta).to_seconds() * 1000.0).round())).collect::<Vec<_>>();
+        let position = self.editor.selection.iter().position(|x| *x == idx);
+        self.move_selection(state, "Move objects", true, times);
+        self.editor.dragging = position.and_then(|x| self.editor.selection.get(x).copied());
+    }
+
+    /// Left click selects and starts dragging objects, or places a new one on release.
+    /// Ctrl toggles single objects, shift selects everything up to the clicked one.
+    pub fn primary_pressed(&mut self, state: &mut TaikoState, modifiers: ModifiersState) {
+        let time_ms = self.clock.get_time();
+        let Some(beatmap) = &self.beatmap else { return };
+        let Some(cursor) = self.editor.cursor else { return };
+
+        let Some(idx) = self.conveyor.object_at(state, beatmap, time_ms, cursor.x, cursor.y) else {
+            self.editor.pressed = Some(cursor);
+            return;
+        };
+
+        let selection = &mut self.editor.selection;
+        if modifiers.ctrl() {
+            match selection.binary_search(&idx) {
+                Ok(position)  => { selection.remove(position); }
+                Err(position) => selection.insert(position, idx),
+            }
+            self.editor.anchor = Some(idx);
+        } else if modifiers.shift() && let Some(anchor) = self.editor.anchor {
+            *selection = (anchor.min(idx) ..= anchor.max(idx)).collect();
+        } else {
+            if selection.binary_search(&idx).is_err() { *selection = vec![idx]; }
+            self.editor.anchor = Some(idx);
+            self.editor.dragging = Some(idx);
+        }
+
+        state.rebuild_pending = true;
+    }
+
+    /// Ends a drag, which is a single step in the history
+    pub fn primary_released(&mut self, state: &mut TaikoState, modifiers: ModifiersState) {
+        let pressed = self.editor.pressed.take();
+        let boxing = std::mem::take(&mut self.editor.boxing);
+        self.editor.dragging = None;
+        self.history.seal();
+
+        let time_ms = self.clock.get_time();
+        let Some(beatmap) = &self.beatmap else { return };
+        let (Some(pressed), Some(cursor)) = (pressed, self.editor.cursor) else { return };
+
+        // Boxes add to the selection while ctrl or shift is held
+        if boxing {
+            let mut selection = self.conveyor.objects_in(state, beatmap, time_ms, pressed, cursor);
+            if modifiers.ctrl() || modifiers.shift() {
+                selection.extend(self.editor.selection.iter());
+                selection.sort_unstable();
+                selection.dedup();
+            }
+
+            self.editor.selection = selection;
+            state.rebuild_pending = true;
+            return;
+        }
+
+        let time = beatmap.snap(self.conveyor.time_at(state, beatmap, time_ms, pressed.x), state.snap_divisor);
+        if editor::find(beatmap, time).is_some() { return }
+
+        let object = editor::circle(time, self.editor.color, self.editor.big);
+        let idx = self.edit_objects(state, "Place object", false, |beatmap| editor::insert(beatmap, object));
+        self.editor.selection = idx.into_iter().collect();
+        self.editor.anchor = idx;
+    }
+
+    /// Right click deletes the object under the cursor
+    pub fn secondary_pressed(&mut self, state: &mut TaikoState) {
+        let Some(idx) = self.hovered_object(state) else { return };
+        self.edit_objects(state, "Delete object", false, |beatmap| editor::remove(beatmap, idx));
+        self.clear_selection(state);
+    }
+
+    /// Rectangle of the selection box being dragged, in physical pixels
+    pub fn selection_box(&self) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        if !self.editor.boxing { return None }
+        return self.editor.pressed.zip(self.editor.cursor);
+    }
+
+    // Selection
+    pub fn select_all(&mut self, state: &mut TaikoState) {
+        let Some(beatmap) = &self.beatmap else { return };
+        self.editor.selection = (0 .. beatmap.objects.len()).collect();
+        state.rebuild_pending = true;
+    }
+
+    pub fn clear_selection(&mut self, state: &mut TaikoState) {
+        self.editor.selection.clear();
+        self.editor.anchor = None;
+        self.editor.dragging = None;
+        state.rebuild_pending = true;
+    }
+
+    /// Selects objects starting within `start ..= end`, optionally keeping the current selection
+    pub fn select_range(&mut self, state: &mut TaikoState, start: Time, end: Time, add: bool) {
+        let Some(beatmap) = &self.beatmap else { return };
+        let mut selection = editor::in_range(beatmap, start, end);
+        if add {
+            selection.extend(self.editor.selection.iter());
+            selection.sort_unstable();
+            selection.dedup();
+        }
+
+        self.editor.selection = selection;
+        state.rebuild_pending = true;
+    }
+
+    /// Selected objects as .osu hit object lines, `None` without a selection
+    pub fn copy(&self) -> Option<String> {
+        let beatmap = self.beatmap.as_ref()?;
+        if self.editor.selection.is_empty() { return None }
+        return Some(editor::to_text(beatmap, &self.editor.selection));
+    }
+
+    pub fn cut(&mut self, state: &mut TaikoState) -> Option<String> {
+        let text = self.copy()?;
+        let selection = std::mem::take(&mut self.editor.selection);
+        self.edit_objects(state, "Cut objects", false, |beatmap| editor::remove_all(beatmap, &selection));
+        self.clear_selection(state);
+        return Some(text);
+    }
+
+    /// Pastes hit object lines so the first one starts at the playhead, the pasted objects become the selection
+    pub fn paste(&mut self, state: &mut TaikoState, text: &str) {
+        let now = self.clock.get_time() as f64 - state.audio_offset as f64;
+        let Some(beatmap) = &self.beatmap else { return };
+
+        let objects = editor::from_text(beatmap, text, beatmap.snap(Time::from_ms(now), state.snap_divisor));
+        if objects.is_empty() { return }
+
+        let selection = self.edit_objects(state, "Paste objects", false, |beatmap| editor::insert_all(beatmap, objects));
+        self.editor.selection = selection.unwrap_or_default();
+        self.editor.anchor = self.editor.selection.first().copied();
+    }
+
+    pub fn delete_selection(&mut self, state: &mut TaikoState) {
+        if self.editor.selection.is_empty() { return }
+        let selection = std::mem::take(&mut self.editor.selection);
+        self.edit_objects(state, "Delete objects", false, |beatmap| editor::remove_all(beatmap, &selection));
+        self.clear_selection(state);
+    }
+
+    /// Moves the selection by `ticks` of the snap divisor
+    pub fn nudge_selection(&mut self, state: &mut TaikoState, ticks: i32) {
+        let Some(beatmap) = &self.beatmap else { return };
+        let times = editor::nudged_times(beatmap, &self.editor.selection, ticks, state.snap_divisor);
+        self.move_selection(state, "Nudge objects", false, times);
+    }
+
+    /// Moves the selection by whole beats, snapped to the divisor
+    pub fn shift_selection(&mut self, state: &mut TaikoState, beats: i32) {
+        let Some(beatmap) = &self.beatmap else { return };
+        let divisor = state.snap_divisor.max(1);
+        let times = editor::nudged_times(beatmap, &self.editor.selection, beats * divisor as i32, divisor);
+        self.move_selection(state, "Shift objects", false, times);
+    }
+
+    /// Runs a pattern transform on the selection as a single edit, returns whether anything was done
+    pub fn transform_selection(&mut self, state: &mut TaikoState, transform: Transform) -> bool {
+        if self.editor.selection.is_empty() { return false }
+        let selection = self.editor.selection.clone();
+        let divisor = state.snap_divisor;
+        let Some(Some(transformed)) = self.edit_objects(state, transform.name(), false, |beatmap| transform.apply(beatmap, &selection, divisor)) else { return false };
+
+        self.editor.anchor = None;
+        self.editor.selection = transformed;
+        return true;
+    }
+
+    // Recording
+    /// Recording plays the song, stopping pauses it and ends the take as one step in the history
+    pub fn toggle_recording(&mut self, state: &mut TaikoState) {
+        if self.beatmap.is_none() { return }
+        self.editor.recording = !self.editor.recording;
+        self.editor.take.clear();
+        self.history.seal();
+
+        if self.editor.recording {
+            self.clear_selection(state);
+            if self.is_paused() { self.toggle_paused(); }
+        } else {
+            self.set_paused(true);
+        }
+    }
+
+    /// Drum hit while recording, placed on the closest tick. Objects which were there before are replaced,
+    /// a second hit of the same color on one tick makes the object big.
+    pub fn record_tap(&mut self, state: &mut TaikoState, color: TaikoColor) {
+        if !self.editor.recording || self.is_paused() { return }
+
+        let time = self.playhead(state);
+        let Some(beatmap) = &self.beatmap else { return };
+        let time = beatmap.snap(time, state.snap_divisor);
+        let existing = editor::find(beatmap, time);
+        let doubled = existing.filter(|x| self.editor.take.contains(&time) && beatmap.objects[*x].color == color);
+
+        self.edit_objects(state, "Record taps", true, |beatmap| {
+            if let Some(idx) = doubled {
+                transform::set_big(beatmap, &[idx], true);
+                return;
+            }
+
+            if let Some(idx) = existing { editor::remove(beatmap, idx); }
+            editor::insert(beatmap, editor::circle(time, color, false));
+        });
+
+        self.editor.take.push(time);
+    }
+
+    /// Chart time under the playhead
+    pub fn playhead(&mut self, state: &TaikoState) -> Time {
+        return Time::from_ms(self.clock.get_time() as f64 - state.audio_offset as f64);
+    }
+
+    /// Nothing happens if the selection would run into other objects
+    fn move_selection(&mut self, state: &mut TaikoState, name: &str, merge: bool, times: Vec<Time>) {
+        if self.editor.selection.is_empty() { return }
+        let selection = self.editor.selection.clone();
+        let Some(Some(moved)) = self.edit_objects(state, name, merge, |beatmap| editor::move_all(beatmap, &selection, &times)) else { return };
+
+        self.editor.anchor = self.editor.anchor.and_then(|x| selection.iter().position(|y| *y == x)).and_then(|x| moved.get(x).copied());
+        self.editor.selection = moved;
+    }
+
+    /// Toggles the object under the cursor, or the color of the next placed one
+    pub fn toggle_color(&mut self, state: &mut TaikoState) {
+        match self.hovered_object(state) {
+            Some(idx) => { self.edit_objects(state, "Toggle color", false, |beatmap| editor::toggle_color(beatmap, idx)); }
+            None      => self.editor.color.toggle(),
+        }
+    }
+
+    /// Toggles the object under the cursor, or the size of the next placed one
+    pub fn toggle_big(&mut self, state: &mut TaikoState) {
+        match self.hovered_object(state) {
+            Some(idx) => { self.edit_objects(state, "Toggle big", false, |beatmap| editor::toggle_big(beatmap, idx)); }
+            None      => self.editor.big = !self.editor.big,
+        }
+    }
+
+    fn hovered_object(&mut self, state: &TaikoState) -> Option<usize> {
+        let time_ms = self.clock.get_time();
+        let beatmap = self.beatmap.as_ref()?;
+        let cursor = self.editor.cursor?;
+        return self.conveyor.object_at(state, beatmap, time_ms, cursor.x, cursor.y);
+    }
+
+    /// Runs an edit on the objects and records it in the history
+    fn edit_objects<R>(&mut self, state: &mut TaikoState, name: &str, merge: bool, edit: impl FnOnce(&mut Beatmap) -> R) -> Option<R> {
+        let beatmap = self.beatmap.as_mut()?;
+        // Drags and recorded taps keep comparing against the objects from before they started, instead of copying them every step
+        let before = self.history.objects_snapshot(name, merge, beatmap);
+        let result = edit(beatmap);
+        self.history.record(name, merge, before, beatmap);
+        self.edited(state);
+        return Some(result);
+    }
+
+    /// Beatmap changed, culling starts over since indices moved
+    pub fn edited(&mut self, state: &mut TaikoState) {
+        self.conveyor.reset_culling();
+        state.rebuild_pending = true;
+        state.revision += 1;
+    }
+
+    // History
+    pub fn undo(&mut self, state: &mut TaikoState) {
+        let Some(beatmap) = &mut self.beatmap else { return };
+        if self.history.undo(beatmap) { self.history_moved(state); }
+    }
+
+    pub fn redo(&mut self, state: &mut TaikoState) {
+        let Some(beatmap) = &mut self.beatmap else { return };
+        if self.history.redo(beatmap) { self.history_moved(state); }
+    }
+
+    /// Undoes or redoes until `position` commands are applied
+    pub fn jump_to(&mut self, state: &mut TaikoState, position: usize) {
+        let Some(beatmap) = &mut self.beatmap else { return };
+        if position == self.history.position() { return }
+        self.history.jump_to(position, beatmap);
+        self.history_moved(state);
+    }
+
+    fn history_moved(&mut self, state: &mut TaikoState) {
+        self.clear_selection(state);
+        self.edited(state);
+    }
 }
\ No newline at end of file
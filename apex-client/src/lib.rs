@@ -23,6 +23,7 @@ pub mod graphics;
 pub mod config;
 pub mod view;
 pub mod taiko;
+pub mod mania;
 pub mod layer;
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen)]
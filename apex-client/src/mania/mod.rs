@@ -0,0 +1 @@
+pub mod vsrg_note;
@@ -0,0 +1,20 @@
+use wcore::time::Time;
+
+#[derive(Clone, Default)]
+pub struct VsrgNote {
+    pub time     : Time,
+    pub key      : usize,
+
+    /// Zero for regular notes
+    pub duration : Time,
+}
+
+impl VsrgNote {
+    pub fn is_hold(&self) -> bool {
+        return self.duration > Time::zero();
+    }
+
+    pub fn end_time(&self) -> Time {
+        return self.time + self.duration;
+    }
+}
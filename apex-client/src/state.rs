@@ -1,13 +1,63 @@
+use std::{collections::{HashMap, HashSet}, path::{PathBuf, Path}};
+
 use egui::{Ui, panel::Side};
-use wcore::{color::Color, graphics::context::Graphics};
+use log::warn;
+use wcore::{color::Color, graphics::context::Graphics, time::Time};
+
+use crate::{view::sidebar::SidebarState, layer::{taiko::{TaikoState, TaikoLayer, TaikoEditor}, mania::{ManiaState, ManiaLayer}}, taiko::{parser::{OsuBeatmap, Beatmap}, taiko_circle::TaikoCircle, hitsound::{self, ResolvedSample}, difficulty, lint, history::History}};
+
+pub struct Difficulty {
+    pub version : String,
+    pub objects : usize,
+    pub length  : Time,
+
+    /// Taiko star rating, kept up to date while editing
+    pub stars   : Option<f64>,
+
+    /// `None` for the difficulty which is currently loaded into a layer
+    pub beatmap : Option<OsuBeatmap>,
+}
 
-use crate::{view::sidebar::SidebarState, layer::taiko::{TaikoState, TaikoLayer}};
+/// Every difficulty of the opened archive, they all share the audio which was decoded on load
+pub struct BeatmapSet {
+    pub difficulties : Vec<Difficulty>,
+    pub active       : usize,
+    pub audio        : PathBuf,
+
+    /// Custom hit samples shipped with the beatmap, keyed by lowercase file name
+    pub samples      : HashMap<String, Vec<u8>>,
+
+    /// Lowercase names of every file in the archive
+    pub files        : HashSet<String>,
+
+    /// Set once after loading, the difficulty picker opens itself
+    pub open_picker  : bool,
+}
 
 pub struct AppState {    
     pub sidebar : SidebarState,
     pub taiko   : TaikoState,
+    pub mania   : ManiaState,
 
     pub taiko_layer : TaikoLayer,
+    pub mania_layer : ManiaLayer,
+
+    pub beatmap_set : Option<BeatmapSet>,
+
+    /// Beatmap revision the star rating of the active difficulty was calculated at
+    stars_revision : u64,
+}
+
+impl BeatmapSet {
+    /// Sample files of an object, custom ones are looked up in the beatmap archive
+    pub fn resolve_samples(&self, beatmap: &Beatmap, object: &TaikoCircle) -> Vec<ResolvedSample> {
+        return hitsound::resolve(beatmap, object, |filename| self.samples.contains_key(&filename.to_lowercase()));
+    }
+
+    /// Whether the archive contains a file, osu! ignores case
+    pub fn has_file(&self, path: &Path) -> bool {
+        return self.files.contains(&path.to_string_lossy().to_lowercase());
+    }
 }
 
 impl AppState {
@@ -15,11 +65,114 @@ impl AppState {
         return Self {
             sidebar : SidebarState::new(),
             taiko   : TaikoState::new(),
+            mania   : ManiaState::new(),
 
             taiko_layer : TaikoLayer::new(graphics),
+            mania_layer : ManiaLayer::new(graphics),
+
+            beatmap_set : None,
+
+            stars_revision : 0,
         };
     }
 
+    /// Loads the first difficulty, audio has to be loaded separately
+    pub fn load_beatmap_set(&mut self, beatmaps: Vec<OsuBeatmap>, samples: HashMap<String, Vec<u8>>, files: HashSet<String>) {
+        let Some(audio) = beatmaps.first().map(|x| x.audio().clone()) else { return };
+        let difficulties = beatmaps.into_iter().map(|beatmap| Difficulty {
+            version : beatmap.metadata().version.clone(),
+            objects : beatmap.object_count(),
+            length  : beatmap.length(),
+            stars   : star_rating(&beatmap),
+            beatmap : Some(beatmap),
+        }).collect::<Vec<_>>();
+
+        self.beatmap_set = Some(BeatmapSet {
+            open_picker  : difficulties.len() > 1,
+            difficulties : difficulties,
+            active       : 0,
+            audio        : audio,
+            samples      : samples,
+            files        : files,
+        });
+
+        self.select_difficulty(0);
+    }
+
+    /// Swaps the beatmap in the layers, the active one is kept around with any changes made to it
+    pub fn select_difficulty(&mut self, idx: usize) {
+        let Some(set) = &mut self.beatmap_set else { return };
+        let Some(beatmap) = set.difficulties.get_mut(idx).and_then(|x| x.beatmap.take()) else { return };
+
+        let previous = match (self.taiko_layer.beatmap.take(), self.mania_layer.beatmap.take()) {
+            (Some(beatmap), _) => Some(OsuBeatmap::Taiko(beatmap)),
+            (_, Some(beatmap)) => Some(OsuBeatmap::Mania(beatmap)),
+            (None, None)       => None,
+        };
+
+        if let Some(previous) = previous && let Some(difficulty) = set.difficulties.get_mut(set.active) {
+            difficulty.objects = previous.object_count();
+            difficulty.length  = previous.length();
+            difficulty.stars   = star_rating(&previous);
+            difficulty.beatmap = Some(previous);
+        }
+
+        if beatmap.audio() != &set.audio {
+            warn!("{} uses a different audio file, keeping {}", set.difficulties[idx].version, set.audio.display());
+        }
+
+        // Every difficulty starts with a clean history
+        set.active = idx;
+        self.taiko_layer.history = History::new();
+        self.taiko_layer.editor = TaikoEditor::new();
+        match beatmap {
+            OsuBeatmap::Taiko(beatmap) => self.taiko_layer.beatmap = Some(beatmap),
+            OsuBeatmap::Mania(beatmap) => self.mania_layer.beatmap = Some(beatmap),
+        }
+
+        // Objects changed, culling starts over from the current time. The star rating is still up to date.
+        self.taiko_layer.edited(&mut self.taiko);
+        self.mania_layer.lanes.reset_culling();
+        self.mania.rebuild_pending = true;
+        self.stars_revision = self.taiko.revision;
+    }
+
+    /// Recomputes the star rating of the loaded taiko difficulty, if it changed since the last time
+    pub fn refresh_star_rating(&mut self) {
+        if self.stars_revision == self.taiko.revision { return }
+        self.stars_revision = self.taiko.revision;
+
+        let Some(beatmap) = &self.taiko_layer.beatmap else { return };
+        let Some(set) = &mut self.beatmap_set else { return };
+        if let Some(difficulty) = set.difficulties.get_mut(set.active) {
+            difficulty.stars = Some(difficulty::calculate(beatmap).star_rating);
+        }
+    }
+
+    /// Sample files the first selected object triggers
+    pub fn render_samples(&self, ui: &mut Ui) {
+        ui.heading("Hit samples");
+        let Some(beatmap) = &self.taiko_layer.beatmap else { return };
+        let Some(set) = &self.beatmap_set else { return };
+        let Some(object) = self.taiko_layer.editor.selection.first().and_then(|x| beatmap.objects.get(*x)) else {
+            ui.label("Select an object to see its samples");
+            return;
+        };
+
+        egui::Grid::new("samples")
+          .num_columns(3)
+          .spacing([40.0, 4.0])
+          .striped(true)
+          .show(ui, |ui| {
+            for sample in set.resolve_samples(beatmap, object) {
+                ui.label(&sample.filename);
+                ui.label(format!("{}%", sample.volume));
+                ui.label(if sample.custom { "beatmap" } else { "skin" });
+                ui.end_row();
+            }
+        });
+    }
+
     pub fn render_settings(&mut self, ui: &mut Ui) {
         egui::Grid::new("settings")
           .num_columns(2)
@@ -66,6 +219,16 @@ impl AppState {
             };
             ui.end_row();
 
+            ui.label("Snap divisor");
+            egui::ComboBox::from_id_source("snap_divisor")
+              .selected_text(format!("1/{}", self.taiko.snap_divisor))
+              .show_ui(ui, |ui| {
+                for divisor in lint::SNAP_DIVISORS {
+                    ui.selectable_value(&mut self.taiko.snap_divisor, divisor, format!("1/{divisor}"));
+                }
+            });
+            ui.end_row();
+
             let mut color = [
                 (self.taiko.don_color.r * 255.0).round() as u8,
                 (self.taiko.don_color.g * 255.0).round() as u8,
@@ -92,6 +255,78 @@ impl AppState {
             };
             ui.end_row();
 
+            color = [
+                (self.taiko.roll_color.r * 255.0).round() as u8,
+                (self.taiko.roll_color.g * 255.0).round() as u8,
+                (self.taiko.roll_color.b * 255.0).round() as u8,
+            ];
+
+            ui.label("Drumroll color");
+            if egui::color_picker::color_edit_button_srgb(ui, &mut color).changed() {
+                self.taiko.roll_color = Color::from_rgb(color[0], color[1], color[2]);
+                self.taiko.rebuild_pending = true;
+            };
+            ui.end_row();
+
+            color = [
+                (self.taiko.swell_color.r * 255.0).round() as u8,
+                (self.taiko.swell_color.g * 255.0).round() as u8,
+                (self.taiko.swell_color.b * 255.0).round() as u8,
+            ];
+
+            ui.label("Swell color");
+            if egui::color_picker::color_edit_button_srgb(ui, &mut color).changed() {
+                self.taiko.swell_color = Color::from_rgb(color[0], color[1], color[2]);
+                self.taiko.rebuild_pending = true;
+            };
+            ui.end_row();
+
+            color = [
+                (self.taiko.barline_color.r * 255.0).round() as u8,
+                (self.taiko.barline_color.g * 255.0).round() as u8,
+                (self.taiko.barline_color.b * 255.0).round() as u8,
+            ];
+
+            ui.label("Barline color");
+            if egui::color_picker::color_edit_button_srgb(ui, &mut color).changed() {
+                self.taiko.barline_color = Color::from_rgb(color[0], color[1], color[2]);
+                self.taiko.rebuild_pending = true;
+            };
+            ui.end_row();
+
+            // Mania
+            ui.heading("Mania");
+            ui.end_row();
+
+            ui.label("Hit position");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.mania.hit_position.x).suffix("px"));
+                ui.add(egui::DragValue::new(&mut self.mania.hit_position.y).suffix("px"));
+            });
+            ui.end_row();
+
+            ui.label("Scale");
+            ui.add(egui::DragValue::new(&mut self.mania.scale).speed(0.01).min_decimals(2));
+            ui.end_row();
+
+            ui.label("Scroll speed");
+            if ui.add(egui::DragValue::new(&mut self.mania.scroll_speed).speed(0.01).min_decimals(2).clamp_range(0.1 ..= 10.0)).changed() {
+                self.mania.rebuild_pending = true;
+            };
+            ui.end_row();
+
+            ui.label("Column width");
+            if ui.add(egui::DragValue::new(&mut self.mania.column_width).suffix("px").clamp_range(8.0 ..= 256.0)).changed() {
+                self.mania.rebuild_pending = true;
+            };
+            ui.end_row();
+
+            ui.label("Note height");
+            if ui.add(egui::DragValue::new(&mut self.mania.note_height).suffix("px").clamp_range(1.0 ..= 128.0)).changed() {
+                self.mania.rebuild_pending = true;
+            };
+            ui.end_row();
+
             // Debug
             ui.heading("Debug");
             ui.end_row();
@@ -103,4 +338,11 @@ impl AppState {
         });
         
     }
-}
\ No newline at end of file
+}
+
+fn star_rating(beatmap: &OsuBeatmap) -> Option<f64> {
+    return match beatmap {
+        OsuBeatmap::Taiko(beatmap) => Some(difficulty::calculate(beatmap).star_rating),
+        OsuBeatmap::Mania(_) => None,
+    };
+}
@@ -0,0 +1,299 @@
+use std::fmt::{Display, Write};
+
+use wcore::time::Time;
+
+use super::{parser::Beatmap, taiko_circle::{TaikoCircle, TaikoColor, TaikoKind}};
+
+/// Objects and points this close to each other are considered the same
+const TOLERANCE_MS: f64 = 1.0;
+
+#[derive(Clone, Debug)]
+pub enum Change {
+    Added(TaikoCircle),
+    Removed(TaikoCircle),
+    Recolored { from: TaikoColor, to: TaikoColor },
+    Resized { from: bool, to: bool },
+    Lengthened { from: Time, to: Time },
+
+    TimingAdded { bpm: f64, meter: u32 },
+    TimingRemoved { bpm: f64, meter: u32 },
+    TimingChanged { from: (f64, u32), to: (f64, u32) },
+
+    VelocityAdded(f64),
+    VelocityRemoved(f64),
+    VelocityChanged { from: f64, to: f64 },
+}
+
+#[derive(Clone, Debug)]
+pub struct Difference {
+    pub time   : Time,
+    pub change : Change,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = |big: bool| if big { "big" } else { "small" };
+        write!(f, "{}ms: ", self.time.to_ms())?;
+        return match &self.change {
+            Change::Added(object)   => write!(f, "added {}", describe(object)),
+            Change::Removed(object) => write!(f, "removed {}", describe(object)),
+            Change::Recolored { from, to } => write!(f, "recolored {from:?} to {to:?}"),
+            Change::Resized { from, to }   => write!(f, "resized {} to {}", size(*from), size(*to)),
+            Change::Lengthened { from, to } => write!(f, "length changed from {}ms to {}ms", from.to_ms(), to.to_ms()),
+
+            Change::TimingAdded { bpm, meter }   => write!(f, "added timing point {bpm:.2}bpm {meter}/4"),
+            Change::TimingRemoved { bpm, meter } => write!(f, "removed timing point {bpm:.2}bpm {meter}/4"),
+            Change::TimingChanged { from, to }   => write!(f, "timing changed from {:.2}bpm {}/4 to {:.2}bpm {}/4", from.0, from.1, to.0, to.1),
+
+            Change::VelocityAdded(velocity)   => write!(f, "added slider velocity {velocity:.2}x"),
+            Change::VelocityRemoved(velocity) => write!(f, "removed slider velocity {velocity:.2}x"),
+            Change::VelocityChanged { from, to } => write!(f, "slider velocity changed from {from:.2}x to {to:.2}x"),
+        };
+    }
+}
+
+fn describe(object: &TaikoCircle) -> String {
+    let size = if object.big { "big " } else { "" };
+    return match object.kind {
+        TaikoKind::Circle => format!("{size}{:?}", object.color),
+        TaikoKind::Drumroll { duration, .. } => format!("{size}drumroll ({}ms)", duration.to_ms()),
+        TaikoKind::Swell { duration, .. } => format!("swell ({}ms)", duration.to_ms()),
+    };
+}
+
+/// Changes needed to turn `old` into `new`, objects and points are matched by time
+pub fn diff(old: &Beatmap, new: &Beatmap) -> Vec<Difference> {
+    let mut out = Vec::<Difference>::new();
+
+    merge(&old.objects, &new.objects, |x| x.time, |old, new| match (old, new) {
+        (Some(old), Some(new)) => diff_objects(old, new, &mut out),
+        (Some(old), None) => out.push(Difference { time: old.time, change: Change::Removed(old.clone()) }),
+        (None, Some(new)) => out.push(Difference { time: new.time, change: Change::Added(new.clone()) }),
+        (None, None) => {}
+    });
+
+    merge(&old.timing, &new.timing, |x| x.time, |old, new| {
+        let change = match (old, new) {
+            (Some(old), Some(new)) if (old.bpm - new.bpm).abs() > 1e-6 || old.meter != new.meter => {
+                Change::TimingChanged { from: (old.bpm, old.meter), to: (new.bpm, new.meter) }
+            }
+
+            (Some(old), None) => Change::TimingRemoved { bpm: old.bpm, meter: old.meter },
+            (None, Some(new)) => Change::TimingAdded { bpm: new.bpm, meter: new.meter },
+            _ => return,
+        };
+
+        let time = new.or(old).map(|x| x.time).unwrap_or_default();
+        out.push(Difference { time, change });
+    });
+
+    merge(&old.velocity, &new.velocity, |x| x.time, |old, new| {
+        let change = match (old, new) {
+            (Some(old), Some(new)) if (old.velocity - new.velocity).abs() > 1e-6 => {
+                Change::VelocityChanged { from: old.velocity, to: new.velocity }
+            }
+
+            (Some(old), None) => Change::VelocityRemoved(old.velocity),
+            (None, Some(new)) => Change::VelocityAdded(new.velocity),
+            _ => return,
+        };
+
+        let time = new.or(old).map(|x| x.time).unwrap_or_default();
+        out.push(Difference { time, change });
+    });
+
+    out.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    return out;
+}
+
+/// One difference per line
+pub fn to_text(differences: &[Difference]) -> String {
+    let mut out = String::new();
+    for difference in differences {
+        let _ = writeln!(out, "{difference}");
+    }
+
+    return out;
+}
+
+fn diff_objects(old: &TaikoCircle, new: &TaikoCircle, out: &mut Vec<Difference>) {
+    let time = new.time;
+    match (old.kind, new.kind) {
+        (TaikoKind::Circle, TaikoKind::Circle) => {}
+        (TaikoKind::Drumroll { duration: from, .. }, TaikoKind::Drumroll { duration: to, .. })
+        | (TaikoKind::Swell { duration: from, .. }, TaikoKind::Swell { duration: to, .. }) => {
+            if (from.to_seconds() - to.to_seconds()).abs() * 1000.0 > TOLERANCE_MS {
+                out.push(Difference { time, change: Change::Lengthened { from, to } });
+            }
+        }
+
+        // Different kinds of objects are unrelated
+        _ => {
+            out.push(Difference { time: old.time, change: Change::Removed(old.clone()) });
+            out.push(Difference { time: new.time, change: Change::Added(new.clone()) });
+            return;
+        }
+    }
+
+    if old.kind == TaikoKind::Circle && old.color != new.color {
+        out.push(Difference { time, change: Change::Recolored { from: old.color, to: new.color } });
+    }
+
+    if old.big != new.big {
+        out.push(Difference { time, change: Change::Resized { from: old.big, to: new.big } });
+    }
+}
+
+/// Walks both sorted lists at once, pairing up items at the same time
+fn merge<T>(old: &[T], new: &[T], time: impl Fn(&T) -> Time, mut f: impl FnMut(Option<&T>, Option<&T>)) {
+    let (mut i, mut j) = (0, 0);
+    loop {
+        match (old.get(i), new.get(j)) {
+            (Some(a), Some(b)) => {
+                let delta = (time(a).to_seconds() - time(b).to_seconds()) * 1000.0;
+                if delta.abs() <= TOLERANCE_MS { f(Some(a), Some(b)); i += 1; j += 1; }
+                else if delta < 0.0 { f(Some(a), None); i += 1; }
+                else { f(None, Some(b)); j += 1; }
+            }
+
+            (Some(a), None) => { f(Some(a), None); i += 1; }
+            (None, Some(b)) => { f(None, Some(b)); j += 1; }
+            (None, None) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::{parser, editor};
+
+    fn rolls() -> Beatmap {
+        return parser::try_parse(include_str!("../../tests/fixtures/rolls.osu")).unwrap();
+    }
+
+    fn changes(old: &Beatmap, new: &Beatmap) -> Vec<(u64, Change)> {
+        return diff(old, new).into_iter().map(|x| (x.time.to_ms(), x.change)).collect();
+    }
+
+    #[test]
+    fn same_beatmap_has_no_changes() {
+        assert!(diff(&rolls(), &rolls()).is_empty());
+        assert_eq!(to_text(&[]), "");
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let (old, mut new) = (rolls(), rolls());
+        new.objects.pop();
+        new.objects.insert(2, editor::circle(Time::from_ms(2000), TaikoColor::KAT, true));
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(&changes[0], (2000, Change::Added(x)) if x.big && x.color == TaikoColor::KAT));
+        assert!(matches!(&changes[1], (14000, Change::Removed(x)) if x.kind == TaikoKind::Circle));
+        assert_eq!(to_text(&diff(&old, &new)), "2000ms: added big KAT\n14000ms: removed DON\n");
+    }
+
+    #[test]
+    fn recolored_and_resized() {
+        let (old, mut new) = (rolls(), rolls());
+        new.objects[0].color.toggle();
+        new.objects[0].big = true;
+
+        // Drumrolls have no color of their own
+        new.objects[1].color.toggle();
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(changes[0], (500, Change::Recolored { from: TaikoColor::DON, to: TaikoColor::KAT })));
+        assert!(matches!(changes[1], (500, Change::Resized { from: false, to: true })));
+        assert_eq!(to_text(&diff(&old, &new)), "500ms: recolored DON to KAT\n500ms: resized small to big\n");
+    }
+
+    #[test]
+    fn lengthened() {
+        let (old, mut new) = (rolls(), rolls());
+        let TaikoKind::Drumroll { duration, ticks } = old.objects[1].kind else { panic!("expected a drumroll") };
+        new.objects[1].kind = TaikoKind::Drumroll { duration: duration + Time::from_ms(100), ticks };
+
+        let TaikoKind::Swell { duration, hits } = old.objects[4].kind else { panic!("expected a swell") };
+        new.objects[4].kind = TaikoKind::Swell { duration: duration + Time::from_ms(0.5), hits };
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 1);
+        let (900, Change::Lengthened { from, to }) = changes[0] else { panic!("expected a length change") };
+        assert_eq!((to - from).to_ms(), 100);
+    }
+
+    #[test]
+    fn kind_change_is_removed_and_added() {
+        let (old, mut new) = (rolls(), rolls());
+        new.objects[0].kind = TaikoKind::Drumroll { duration: Time::from_ms(200), ticks: 3 };
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(&changes[0], (500, Change::Removed(x)) if x.kind == TaikoKind::Circle));
+        assert!(matches!(&changes[1], (500, Change::Added(x)) if matches!(x.kind, TaikoKind::Drumroll { .. })));
+        assert_eq!(to_text(&diff(&old, &new)), "500ms: removed DON\n500ms: added drumroll (200ms)\n");
+    }
+
+    #[test]
+    fn matches_within_a_millisecond() {
+        let (old, mut new) = (rolls(), rolls());
+        new.objects[0].time = new.objects[0].time + Time::from_ms(0.9);
+        new.timing[1].time = new.timing[1].time - Time::from_ms(0.9);
+        assert!(diff(&old, &new).is_empty());
+
+        new.objects[0].time = old.objects[0].time + Time::from_ms(2);
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(changes[0], (500, Change::Removed(_))));
+        assert!(matches!(changes[1], (502, Change::Added(_))));
+    }
+
+    #[test]
+    fn timing_changes() {
+        let (old, mut new) = (rolls(), rolls());
+        new.timing[1].bpm = 180.0;
+        new.timing[1].meter = 4;
+        new.timing.remove(0);
+
+        let mut point = new.timing[0].clone();
+        point.time = Time::from_ms(6000);
+        new.timing.insert(0, point);
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(changes[0], (500, Change::TimingRemoved { meter: 4, .. })));
+        assert!(matches!(changes[1], (6000, Change::TimingAdded { bpm, meter: 4 }) if bpm == 180.0));
+        assert!(matches!(changes[2], (12500, Change::TimingChanged { from: (_, 3), to: (bpm, 4) }) if bpm == 180.0));
+
+        let text = to_text(&diff(&old, &new));
+        assert_eq!(text.lines().nth(2), Some("12500ms: timing changed from 200.00bpm 3/4 to 180.00bpm 4/4"));
+    }
+
+    #[test]
+    fn velocity_changes() {
+        let (old, mut new) = (rolls(), rolls());
+        let first = new.velocity.iter().position(|x| x.time.to_ms() == 4500).unwrap();
+        new.velocity[first].velocity = 1.5;
+
+        let mut point = new.velocity[first].clone();
+        point.time = Time::from_ms(6000);
+        point.velocity = 0.75;
+        new.velocity.insert(first + 1, point);
+        new.velocity.retain(|x| x.time.to_ms() != 8500);
+
+        let changes = changes(&old, &new);
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(changes[0], (4500, Change::VelocityChanged { from, to }) if from == 2.0 && to == 1.5));
+        assert!(matches!(changes[1], (6000, Change::VelocityAdded(x)) if x == 0.75));
+        assert!(matches!(changes[2], (8500, Change::VelocityRemoved(x)) if x == 0.5));
+        assert_eq!(to_text(&diff(&old, &new)), concat!(
+            "4500ms: slider velocity changed from 2.00x to 1.50x\n",
+            "6000ms: added slider velocity 0.75x\n",
+            "8500ms: removed slider velocity 0.50x\n",
+        ));
+    }
+}
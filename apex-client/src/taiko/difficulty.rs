@@ -0,0 +1,491 @@
+use std::collections::VecDeque;
+
+use super::{parser::Beatmap, taiko_circle::{TaikoColor, TaikoKind}};
+
+/// Strain peaks are collected per section of this length
+const SECTION_LENGTH: f64 = 400.0; // ms
+
+/// Weight of each following peak when summing sorted peaks
+const DECAY_WEIGHT: f64 = 0.9;
+
+const RHYTHM_MULTIPLIER  : f64 = 0.2   * FINAL_MULTIPLIER;
+const COLOUR_MULTIPLIER  : f64 = 0.375 * FINAL_MULTIPLIER;
+const STAMINA_MULTIPLIER : f64 = 0.375 * FINAL_MULTIPLIER;
+const FINAL_MULTIPLIER   : f64 = 0.0625;
+
+/// Interval ratios between consecutive objects, with the difficulty of switching to them
+const COMMON_RHYTHMS: [(f64, f64); 9] = [
+    (1.0,       0.0),
+    (2.0 / 1.0, 0.3),
+    (1.0 / 2.0, 0.5),
+    (3.0 / 1.0, 0.3),
+    (1.0 / 3.0, 0.35),
+    (3.0 / 2.0, 0.6), // Higher on purpose, requires a hand switch when fully alternating
+    (2.0 / 3.0, 0.4),
+    (5.0 / 4.0, 0.5),
+    (4.0 / 5.0, 0.7),
+];
+
+const RHYTHM_HISTORY_LENGTH: usize = 8;
+const MAX_REPETITION_INTERVAL: usize = 16;
+
+/// Star rating of a beatmap and the skills it's made of
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DifficultyAttributes {
+    pub star_rating : f64,
+    pub colour      : f64,
+    pub rhythm      : f64,
+    pub stamina     : f64,
+
+    /// Combined strain before it's rescaled into stars
+    pub peak        : f64,
+    pub max_combo   : usize,
+}
+
+/// Star rating at normal speed
+pub fn calculate(beatmap: &Beatmap) -> DifficultyAttributes {
+    return calculate_with_rate(beatmap, 1.0);
+}
+
+/// Star rating with the beatmap played `clock_rate` times faster
+pub fn calculate_with_rate(beatmap: &Beatmap, clock_rate: f64) -> DifficultyAttributes {
+    let max_combo = beatmap.objects.iter().filter(|x| x.kind == TaikoKind::Circle).count();
+    if beatmap.objects.is_empty() {
+        return DifficultyAttributes::default();
+    }
+
+    let objects = difficulty_objects(beatmap, clock_rate);
+    let colour_values = colour_difficulties(&objects);
+
+    let mut colour = StrainSkill::new(0.12, 0.8); // Decays slower, only the first note of each pattern has any difficulty
+    let mut rhythm = StrainSkill::new(10.0, 0.0);
+    let mut stamina = StrainSkill::new(1.1, 0.4);
+    let mut rhythm_state = RhythmState::default();
+
+    for (idx, object) in objects.iter().enumerate() {
+        let previous = idx.checked_sub(1).map(|idx| &objects[idx]);
+        colour.process(object, previous, colour_values[idx]);
+        rhythm.process(object, previous, rhythm_state.strain_of(object));
+        stamina.process(object, previous, stamina_of(object));
+    }
+
+    let colour_rating = colour.difficulty() * COLOUR_MULTIPLIER;
+    let rhythm_rating = rhythm.difficulty() * RHYTHM_MULTIPLIER;
+    let stamina_rating = stamina.difficulty() * STAMINA_MULTIPLIER;
+
+    // Skills are combined per section, before the peaks are summed up
+    let colour_peaks = colour.peaks();
+    let rhythm_peaks = rhythm.peaks();
+    let stamina_peaks = stamina.peaks();
+    let peaks = (0 .. colour_peaks.len()).map(|idx| {
+        let peak = norm(1.5, colour_peaks[idx] * COLOUR_MULTIPLIER, stamina_peaks[idx] * STAMINA_MULTIPLIER);
+        return norm(2.0, peak, rhythm_peaks[idx] * RHYTHM_MULTIPLIER);
+    }).collect::<Vec<_>>();
+
+    let combined_rating = weighted_sum(peaks);
+    let mut star_rating = rescale(combined_rating * 1.4);
+
+    // Converts can be played with more than two inputs per colour, which isn't detected yet
+    if beatmap.general.converted {
+        star_rating *= 0.925;
+        if colour_rating < 2.0 && stamina_rating > 8.0 {
+            star_rating *= 0.8;
+        }
+    }
+
+    return DifficultyAttributes {
+        star_rating : star_rating,
+        colour      : colour_rating,
+        rhythm      : rhythm_rating,
+        stamina     : stamina_rating,
+        peak        : combined_rating,
+        max_combo   : max_combo,
+    };
+}
+
+struct DifficultyObject {
+    index        : usize,
+    start_time   : f64, // ms, adjusted for clock rate
+    delta_time   : f64,
+
+    /// Index into `COMMON_RHYTHMS`
+    rhythm       : usize,
+
+    /// `None` for drumrolls and swells
+    color        : Option<TaikoColor>,
+
+    /// Start time of the previous note hit with the same key, two notes of the same color back
+    key_previous : Option<f64>,
+
+    /// Index among notes, `None` for drumrolls and swells
+    note_index   : Option<usize>,
+}
+
+/// The first two objects only serve as reference for the following ones
+fn difficulty_objects(beatmap: &Beatmap, clock_rate: f64) -> Vec<DifficultyObject> {
+    let start_time = |idx: usize| beatmap.objects[idx].time.to_seconds() * 1000.0 / clock_rate;
+
+    let mut objects = Vec::<DifficultyObject>::with_capacity(beatmap.objects.len());
+    let mut mono_times = [Vec::<f64>::new(), Vec::<f64>::new()];
+    let mut note_count = 0;
+    for idx in 2 .. beatmap.objects.len() {
+        let object = &beatmap.objects[idx];
+        let delta_time = start_time(idx) - start_time(idx - 1);
+        let ratio = delta_time / (start_time(idx - 1) - start_time(idx - 2));
+        let rhythm = COMMON_RHYTHMS.iter()
+            .enumerate()
+            .min_by(|a, b| (a.1.0 - ratio).abs().total_cmp(&(b.1.0 - ratio).abs()))
+            .map(|x| x.0)
+            .unwrap_or(0);
+
+        let color = if object.kind == TaikoKind::Circle { Some(object.color) } else { None };
+        let mut key_previous = None;
+        let mut note_index = None;
+        if let Some(color) = color {
+            let times = &mut mono_times[if color == TaikoColor::DON { 0 } else { 1 }];
+            key_previous = times.len().checked_sub(2).map(|idx| times[idx]);
+            times.push(start_time(idx));
+
+            note_index = Some(note_count);
+            note_count += 1;
+        }
+
+        objects.push(DifficultyObject {
+            index        : objects.len(),
+            start_time   : start_time(idx),
+            delta_time   : delta_time,
+            rhythm       : rhythm,
+            color        : color,
+            note_index   : note_index,
+            key_previous : key_previous,
+        });
+    }
+
+    return objects;
+}
+
+struct StrainSkill {
+    multiplier   : f64,
+    decay_base   : f64,
+    strain       : f64,
+    section_peak : f64,
+    section_end  : f64,
+    peaks        : Vec<f64>,
+}
+
+impl StrainSkill {
+    fn new(multiplier: f64, decay_base: f64) -> Self {
+        return Self {
+            multiplier   : multiplier,
+            decay_base   : decay_base,
+            strain       : 0.0,
+            section_peak : 0.0,
+            section_end  : 0.0,
+            peaks        : vec![],
+        };
+    }
+
+    fn decay(&self, ms: f64) -> f64 {
+        return self.decay_base.powf(ms / 1000.0);
+    }
+
+    fn process(&mut self, object: &DifficultyObject, previous: Option<&DifficultyObject>, value: f64) {
+        // The first object doesn't generate a strain, sections start after it
+        if object.index == 0 {
+            self.section_end = (object.start_time / SECTION_LENGTH).ceil() * SECTION_LENGTH;
+        }
+
+        while object.start_time > self.section_end {
+            self.peaks.push(self.section_peak);
+
+            // New sections start with the strain left over from the previous object
+            let previous_time = previous.map(|x| x.start_time).unwrap_or(object.start_time);
+            self.section_peak = self.strain * self.decay(self.section_end - previous_time);
+            self.section_end += SECTION_LENGTH;
+        }
+
+        self.strain *= self.decay(object.delta_time);
+        self.strain += value * self.multiplier;
+        self.section_peak = self.section_peak.max(self.strain);
+    }
+
+    fn peaks(&self) -> Vec<f64> {
+        return self.peaks.iter().copied().chain(std::iter::once(self.section_peak)).collect();
+    }
+
+    fn difficulty(&self) -> f64 {
+        return weighted_sum(self.peaks());
+    }
+}
+
+/// Highest peaks count the most, sections without strain are ignored
+fn weighted_sum(mut peaks: Vec<f64>) -> f64 {
+    peaks.retain(|x| *x > 0.0);
+    peaks.sort_by(|a, b| b.total_cmp(a));
+
+    let mut difficulty = 0.0;
+    let mut weight = 1.0;
+    for peak in peaks {
+        difficulty += peak * weight;
+        weight *= DECAY_WEIGHT;
+    }
+
+    return difficulty;
+}
+
+fn norm(p: f64, a: f64, b: f64) -> f64 {
+    return (a.powf(p) + b.powf(p)).powf(1.0 / p);
+}
+
+fn rescale(value: f64) -> f64 {
+    if value < 0.0 { return value }
+    return 10.43 * (value / 8.0 + 1.0).ln();
+}
+
+// Stamina
+fn stamina_of(object: &DifficultyObject) -> f64 {
+    let Some(key_previous) = object.key_previous else { return 0.0 };
+
+    // Capped at 50ms per key, which is 600bpm 1/4
+    let interval = (object.start_time - key_previous).max(50.0);
+    return 0.5 + 30.0 / interval;
+}
+
+// Rhythm
+#[derive(Default)]
+struct RhythmState {
+    /// Object index and rhythm of the latest objects
+    history            : VecDeque<(usize, usize)>,
+    strain             : f64,
+    notes_since_change : usize,
+}
+
+impl RhythmState {
+    fn strain_of(&mut self, object: &DifficultyObject) -> f64 {
+        // Drumrolls and swells are exempt
+        if object.color.is_none() {
+            self.reset();
+            return 0.0;
+        }
+
+        self.strain *= 0.96;
+        self.notes_since_change += 1;
+
+        // Rhythm didn't change
+        let difficulty = COMMON_RHYTHMS[object.rhythm].1;
+        if difficulty == 0.0 {
+            return 0.0;
+        }
+
+        let mut object_strain = difficulty;
+        object_strain *= self.repetition_penalties(object);
+        object_strain *= pattern_length_penalty(self.notes_since_change);
+        object_strain *= self.speed_penalty(object.delta_time);
+
+        self.notes_since_change = 0;
+        self.strain += object_strain;
+        return self.strain;
+    }
+
+    /// Repeating the same rhythm changes is easier, the more recent the repetition the bigger the penalty
+    fn repetition_penalties(&mut self, object: &DifficultyObject) -> f64 {
+        if self.history.len() == RHYTHM_HISTORY_LENGTH { self.history.pop_front(); }
+        self.history.push_back((object.index, object.rhythm));
+
+        let mut penalty = 1.0;
+        for length in 2 ..= RHYTHM_HISTORY_LENGTH / 2 {
+            let Some(last) = self.history.len().checked_sub(length + 1) else { continue };
+            for start in (0 ..= last).rev() {
+                let same = (0 .. length).all(|i| self.history[start + i].1 == self.history[self.history.len() - length + i].1);
+                if !same { continue }
+
+                let notes_since = object.index - self.history[start].0;
+                penalty *= (0.032 * notes_since as f64).min(1.0);
+                break;
+            }
+        }
+
+        return penalty;
+    }
+
+    /// Slow rhythm changes are easy, very slow ones reset the strain entirely
+    fn speed_penalty(&mut self, delta_time: f64) -> f64 {
+        if delta_time < 80.0 { return 1.0 }
+        if delta_time < 210.0 { return (1.4 - 0.005 * delta_time).max(0.0) }
+
+        self.reset();
+        return 0.0;
+    }
+
+    fn reset(&mut self) {
+        self.strain = 0.0;
+        self.notes_since_change = 0;
+    }
+}
+
+fn pattern_length_penalty(length: usize) -> f64 {
+    let short_pattern_penalty = (0.15 * length as f64).min(1.0);
+    let long_pattern_penalty = (2.5 - 0.15 * length as f64).clamp(0.0, 1.0);
+    return short_pattern_penalty.min(long_pattern_penalty);
+}
+
+// Colour
+//
+// Objects are grouped into mono streaks (notes of the same color), alternating patterns (consecutive
+// streaks of the same length) and repeating patterns (alternating patterns which repeat every other one).
+// Only the first object of each group is given the group's difficulty.
+struct AlternatingPattern {
+    /// Mono streaks, as lists of object indices
+    streaks : Vec<Vec<usize>>,
+}
+
+impl AlternatingPattern {
+    fn first_length(&self) -> usize {
+        return self.streaks[0].len();
+    }
+
+    fn is_repetition_of(&self, other: &AlternatingPattern, objects: &[DifficultyObject]) -> bool {
+        return self.first_length() == other.first_length()
+            && self.streaks.len() == other.streaks.len()
+            && objects[self.streaks[0][0]].color == objects[other.streaks[0][0]].color;
+    }
+}
+
+fn colour_difficulties(objects: &[DifficultyObject]) -> Vec<f64> {
+    // Mono streaks, drumrolls and swells always start a new one. Notes are compared with the previous
+    // note, so a note of the same color after a drumroll continues the drumroll's streak, like in osu!
+    let mut streaks = Vec::<Vec<usize>>::new();
+    let mut previous_note = None::<usize>;
+    for object in objects {
+        let previous = object.note_index.and(previous_note).map(|idx| &objects[idx]);
+        if streaks.is_empty() || previous.map(|x| x.color) != Some(object.color) {
+            streaks.push(vec![]);
+        }
+
+        if let Some(streak) = streaks.last_mut() { streak.push(object.index); }
+        if object.note_index.is_some() { previous_note = Some(object.index); }
+    }
+
+    // Alternating patterns
+    let mut patterns = Vec::<AlternatingPattern>::new();
+    for streak in streaks {
+        match patterns.last_mut() {
+            Some(pattern) if pattern.streaks.last().map(Vec::len) == Some(streak.len()) => pattern.streaks.push(streak),
+            _ => patterns.push(AlternatingPattern { streaks: vec![streak] }),
+        }
+    }
+
+    // Repeating patterns, as ranges of alternating patterns
+    let mut repeating = Vec::<(usize, usize)>::new();
+    let coupled = |idx: usize| idx + 2 < patterns.len() && patterns[idx].is_repetition_of(&patterns[idx + 2], objects);
+    let mut idx = 0;
+    while idx < patterns.len() {
+        let start = idx;
+        if coupled(idx) {
+            while coupled(idx) { idx += 1; }
+            idx += 1;
+        }
+
+        repeating.push((start, idx + 1));
+        idx += 1;
+    }
+
+    let mut difficulties = vec![0.0; objects.len()];
+    for (idx, &(start, end)) in repeating.iter().enumerate() {
+        let interval = repetition_interval(&patterns, &repeating, idx);
+        let repeating_difficulty = 2.0 * (1.0 - sigmoid(interval as f64, 2.0, 2.0, 0.5, 1.0));
+
+        for (pattern_idx, pattern) in patterns[start .. end].iter().enumerate() {
+            let pattern_difficulty = sigmoid(pattern_idx as f64, 2.0, 2.0, 0.5, 1.0) * repeating_difficulty;
+
+            for (streak_idx, streak) in pattern.streaks.iter().enumerate() {
+                let streak_difficulty = sigmoid(streak_idx as f64, 2.0, 2.0, 0.5, 1.0) * pattern_difficulty * 0.5;
+
+                let first = streak[0];
+                difficulties[first] += streak_difficulty;
+                if streak_idx == 0 { difficulties[first] += pattern_difficulty; }
+                if streak_idx == 0 && pattern_idx == 0 { difficulties[first] += repeating_difficulty; }
+            }
+        }
+    }
+
+    return difficulties;
+}
+
+/// Amount of repeating patterns since the last one with the same streak lengths, capped above 16
+fn repetition_interval(patterns: &[AlternatingPattern], repeating: &[(usize, usize)], idx: usize) -> usize {
+    let same = |a: (usize, usize), b: (usize, usize)| {
+        if a.1 - a.0 != b.1 - b.0 { return false }
+        return (0 .. (a.1 - a.0).min(2)).all(|i| patterns[a.0 + i].first_length() == patterns[b.0 + i].first_length());
+    };
+
+    for interval in 1 .. MAX_REPETITION_INTERVAL {
+        let Some(other) = idx.checked_sub(interval) else { break };
+        if same(repeating[idx], repeating[other]) {
+            return interval;
+        }
+    }
+
+    return MAX_REPETITION_INTERVAL + 1;
+}
+
+fn sigmoid(value: f64, center: f64, width: f64, middle: f64, height: f64) -> f64 {
+    let sigmoid = (std::f64::consts::E * -(value - center) / width).tanh();
+    return sigmoid * (height / 2.0) + middle;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::parser;
+
+    const STREAM : &str = include_str!("../../tests/fixtures/stream.osu");
+    const ROLLS  : &str = include_str!("../../tests/fixtures/rolls.osu");
+
+    /// Values this port gives, pinned so changes to it show up.
+    /// These are not lazer's reference values, those still have to be compared against osu!.
+    const PINNED: [(&str, &str, f64); 2] = [
+        ("stream.osu", STREAM, 3.381524),
+        ("rolls.osu",  ROLLS,  0.166997),
+    ];
+
+    #[test]
+    fn matches_pinned_values() {
+        for (name, data, stars) in PINNED {
+            let beatmap = parser::try_parse(data).unwrap();
+            let star_rating = calculate(&beatmap).star_rating;
+            assert!((star_rating - stars).abs() < 1e-3, "{name}: {star_rating}");
+        }
+    }
+
+    #[test]
+    fn empty_beatmap_has_no_difficulty() {
+        let mut beatmap = parser::try_parse(STREAM).unwrap();
+        beatmap.objects.truncate(2);
+        assert_eq!(calculate(&beatmap).star_rating, 0.0);
+
+        beatmap.objects.clear();
+        assert_eq!(calculate(&beatmap).star_rating, 0.0);
+    }
+
+    #[test]
+    fn max_combo_counts_circles() {
+        let beatmap = parser::try_parse(ROLLS).unwrap();
+        assert_eq!(calculate(&beatmap).max_combo, 2);
+    }
+
+    #[test]
+    fn faster_rate_is_harder() {
+        let beatmap = parser::try_parse(STREAM).unwrap();
+        assert!(calculate_with_rate(&beatmap, 1.5).star_rating > calculate(&beatmap).star_rating);
+        assert!(calculate_with_rate(&beatmap, 0.75).star_rating < calculate(&beatmap).star_rating);
+    }
+
+    #[test]
+    fn converts_are_penalized() {
+        let mut beatmap = parser::try_parse(STREAM).unwrap();
+        let star_rating = calculate(&beatmap).star_rating;
+        beatmap.general.converted = true;
+        assert!((calculate(&beatmap).star_rating - star_rating * 0.925).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,159 @@
+use wcore::time::Time;
+
+use super::{parser::{self, Beatmap}, taiko_circle::{TaikoCircle, TaikoColor, TaikoKind}, hitsound, writer};
+
+/// Objects closer than this are considered to be at the same time
+const SAME_TIME_MS: f64 = 1.0;
+
+/// Plain circle, hit sounds match the color
+pub fn circle(time: Time, color: TaikoColor, big: bool) -> TaikoCircle {
+    return TaikoCircle {
+        time      : time,
+        big       : big,
+        color     : color,
+        kind      : TaikoKind::Circle,
+        hit_sound : hitsound::hit_sound(color, big),
+        sample    : Default::default(),
+        combo     : 0,
+    };
+}
+
+/// Index of the object starting at `time`
+pub fn find(beatmap: &Beatmap, time: Time) -> Option<usize> {
+    let time = time.to_seconds() * 1000.0;
+    let idx = beatmap.objects.partition_point(|x| x.time.to_seconds() * 1000.0 < time - SAME_TIME_MS);
+    return beatmap.objects.get(idx)
+        .filter(|x| (x.time.to_seconds() * 1000.0 - time).abs() < SAME_TIME_MS)
+        .map(|_| idx);
+}
+
+/// Keeps objects sorted, returns where the object ended up
+pub fn insert(beatmap: &mut Beatmap, object: TaikoCircle) -> usize {
+    let idx = beatmap.objects.partition_point(|x| x.time <= object.time);
+    beatmap.objects.insert(idx, object);
+    return idx;
+}
+
+pub fn remove(beatmap: &mut Beatmap, idx: usize) -> Option<TaikoCircle> {
+    if idx >= beatmap.objects.len() { return None }
+    return Some(beatmap.objects.remove(idx));
+}
+
+/// Moves an object in time, returns its new index
+pub fn move_to(beatmap: &mut Beatmap, idx: usize, time: Time) -> usize {
+    let Some(mut object) = remove(beatmap, idx) else { return idx };
+    object.time = time;
+    return insert(beatmap, object);
+}
+
+/// Only circles have a color, hit sounds are updated to match
+pub fn toggle_color(beatmap: &mut Beatmap, idx: usize) {
+    let Some(object) = beatmap.objects.get_mut(idx) else { return };
+    if object.kind != TaikoKind::Circle { return }
+
+    object.color.toggle();
+    object.hit_sound = writer::object_hit_sound(object);
+}
+
+/// Swells are always big
+pub fn toggle_big(beatmap: &mut Beatmap, idx: usize) {
+    let Some(object) = beatmap.objects.get_mut(idx) else { return };
+    if matches!(object.kind, TaikoKind::Swell { .. }) { return }
+
+    object.big = !object.big;
+    object.hit_sound = writer::object_hit_sound(object);
+}
+
+/// Objects starting within `start ..= end`
+pub fn in_range(beatmap: &Beatmap, start: Time, end: Time) -> Vec<usize> {
+    let first = beatmap.objects.partition_point(|x| x.time < start);
+    return (first .. beatmap.objects.len()).take_while(|x| beatmap.objects[*x].time <= end).collect();
+}
+
+/// Indices of the objects starting at `times`, objects which aren't found are left out
+fn indices_of(beatmap: &Beatmap, times: impl IntoIterator<Item = Time>) -> Vec<usize> {
+    let mut indices = times.into_iter().filter_map(|x| find(beatmap, x)).collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices.dedup();
+    return indices;
+}
+
+/// Objects as lines of the [HitObjects] section
+pub fn to_text(beatmap: &Beatmap, indices: &[usize]) -> String {
+    let mut out = String::new();
+    for object in indices.iter().filter_map(|x| beatmap.objects.get(*x)) {
+        writer::write_hit_object(&mut out, beatmap, object);
+    }
+
+    return out;
+}
+
+/// Parses hit object lines and moves them so the first one starts at `time`
+pub fn from_text(beatmap: &Beatmap, text: &str, time: Time) -> Vec<TaikoCircle> {
+    let mut objects = parser::parse_hit_objects(text, beatmap);
+    objects.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+
+    let Some(first) = objects.first().map(|x| x.time) else { return objects };
+    for object in &mut objects {
+        object.time = Time::from_ms(((object.time - first + time).to_seconds() * 1000.0).round());
+    }
+
+    return objects;
+}
+
+/// Inserts objects, skipping the ones which would land on an existing object. Returns where they ended up.
+pub fn insert_all(beatmap: &mut Beatmap, objects: Vec<TaikoCircle>) -> Vec<usize> {
+    let mut times = vec![];
+    for object in objects {
+        if find(beatmap, object.time).is_some() { continue }
+        times.push(object.time);
+        insert(beatmap, object);
+    }
+
+    return indices_of(beatmap, times);
+}
+
+/// `indices` has to be sorted
+pub fn remove_all(beatmap: &mut Beatmap, indices: &[usize]) {
+    for idx in indices.iter().rev() {
+        remove(beatmap, *idx);
+    }
+}
+
+/// Moves every object of `indices` to the time at the same position in `times`.
+/// Nothing moves if two of them would end up together or on an object which stays, otherwise returns the new indices.
+pub fn move_all(beatmap: &mut Beatmap, indices: &[usize], times: &[Time]) -> Option<Vec<usize>> {
+    let mut objects = indices.iter().filter_map(|x| beatmap.objects.get(*x).cloned()).collect::<Vec<_>>();
+    for (object, time) in objects.iter_mut().zip(times) {
+        object.time = *time;
+    }
+
+    return replace_all(beatmap, indices, objects);
+}
+
+/// Swaps the objects of `indices` for `objects`, with the same rules as [`move_all`]
+pub fn replace_all(beatmap: &mut Beatmap, indices: &[usize], objects: Vec<TaikoCircle>) -> Option<Vec<usize>> {
+    let replaced = |idx: usize| indices.binary_search(&idx).is_ok();
+    if objects.iter().any(|x| find(beatmap, x.time).is_some_and(|idx| !replaced(idx))) { return None }
+
+    let mut sorted = objects.iter().map(|x| x.time.to_seconds() * 1000.0).collect::<Vec<_>>();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    if sorted.windows(2).any(|x| x[1] - x[0] < SAME_TIME_MS) { return None }
+
+    remove_all(beatmap, indices);
+    let times = objects.iter().map(|x| x.time).collect::<Vec<_>>();
+    for object in objects {
+        insert(beatmap, object);
+    }
+
+    return Some(indices_of(beatmap, times));
+}
+
+/// Times `ticks` 1/`divisor` beats away from each object, using the timing point the object is in
+pub fn nudged_times(beatmap: &Beatmap, indices: &[usize], ticks: i32, divisor: u32) -> Vec<Time> {
+    return indices.iter().filter_map(|x| beatmap.objects.get(*x)).map(|object| {
+        let Some(point) = beatmap.timing_at(object.time) else { return object.time };
+        let step = point.beat_length() / divisor.max(1) as f64;
+        beatmap.snap(Time::from_ms(object.time.to_seconds() * 1000.0 + ticks as f64 * step), divisor)
+    }).collect();
+}
@@ -0,0 +1,493 @@
+use super::{parser::{Beatmap, TimingPoint, VelocityPoint, BeatmapMetadata, BeatmapDifficulty, BeatmapGeneral}, taiko_circle::TaikoCircle};
+
+/// Older commands are dropped once the history grows past this
+const MAX_COMMANDS: usize = 500;
+
+/// Replaces `removed` at `start` with `inserted`, only the part of a list which actually changed is kept around
+#[derive(Clone, Debug)]
+pub struct Splice<T> {
+    pub start    : usize,
+    pub removed  : Vec<T>,
+    pub inserted : Vec<T>,
+}
+
+impl<T: Clone + PartialEq> Splice<T> {
+    /// `None` if both lists are equal
+    pub fn between(before: &[T], after: &[T]) -> Option<Self> {
+        let prefix = before.iter().zip(after).take_while(|(a, b)| a == b).count();
+        let suffix = before[prefix ..].iter().rev().zip(after[prefix ..].iter().rev()).take_while(|(a, b)| a == b).count();
+        if prefix == before.len() && prefix == after.len() { return None }
+
+        return Some(Self {
+            start    : prefix,
+            removed  : before[prefix .. before.len() - suffix].to_vec(),
+            inserted : after[prefix .. after.len() - suffix].to_vec(),
+        });
+    }
+
+    pub fn apply(&self, list: &mut Vec<T>) {
+        list.splice(self.start .. self.start + self.removed.len(), self.inserted.iter().cloned());
+    }
+
+    pub fn revert(&self, list: &mut Vec<T>) {
+        list.splice(self.start .. self.start + self.inserted.len(), self.removed.iter().cloned());
+    }
+}
+
+/// Everything about a beatmap which isn't a list of objects or points
+#[derive(Clone, PartialEq, Debug)]
+pub struct Properties {
+    pub general             : BeatmapGeneral,
+    pub metadata            : BeatmapMetadata,
+    pub difficulty          : BeatmapDifficulty,
+    pub velocity_multiplier : f32,
+}
+
+impl Properties {
+    pub fn of(beatmap: &Beatmap) -> Self {
+        return Self {
+            general             : beatmap.general.clone(),
+            metadata            : beatmap.metadata.clone(),
+            difficulty          : beatmap.difficulty.clone(),
+            velocity_multiplier : beatmap.velocity_multiplier,
+        };
+    }
+
+    pub fn set(&self, beatmap: &mut Beatmap) {
+        beatmap.general = self.general.clone();
+        beatmap.metadata = self.metadata.clone();
+        beatmap.difficulty = self.difficulty.clone();
+        beatmap.velocity_multiplier = self.velocity_multiplier;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Change {
+    Objects(Splice<TaikoCircle>),
+    Timing(Splice<TimingPoint>),
+    Velocity(Splice<VelocityPoint>),
+    Properties { before: Box<Properties>, after: Box<Properties> },
+}
+
+impl Change {
+    fn apply(&self, beatmap: &mut Beatmap) {
+        match self {
+            Change::Objects(splice)  => splice.apply(&mut beatmap.objects),
+            Change::Timing(splice)   => splice.apply(&mut beatmap.timing),
+            Change::Velocity(splice) => splice.apply(&mut beatmap.velocity),
+            Change::Properties { after, .. } => after.set(beatmap),
+        }
+    }
+
+    fn revert(&self, beatmap: &mut Beatmap) {
+        match self {
+            Change::Objects(splice)  => splice.revert(&mut beatmap.objects),
+            Change::Timing(splice)   => splice.revert(&mut beatmap.timing),
+            Change::Velocity(splice) => splice.revert(&mut beatmap.velocity),
+            Change::Properties { before, .. } => before.set(beatmap),
+        }
+    }
+}
+
+/// Copy of the parts of a beatmap an edit is about to touch, taken before making it
+#[derive(Default)]
+pub struct Snapshot {
+    objects    : Option<Vec<TaikoCircle>>,
+    timing     : Option<Vec<TimingPoint>>,
+    velocity   : Option<Vec<VelocityPoint>>,
+    properties : Option<Properties>,
+}
+
+impl Snapshot {
+    pub fn objects(beatmap: &Beatmap) -> Self {
+        return Self { objects: Some(beatmap.objects.clone()), ..Default::default() };
+    }
+
+    pub fn timing(beatmap: &Beatmap) -> Self {
+        return Self { timing: Some(beatmap.timing.clone()), ..Default::default() };
+    }
+
+    pub fn velocity(beatmap: &Beatmap) -> Self {
+        return Self { velocity: Some(beatmap.velocity.clone()), ..Default::default() };
+    }
+
+    pub fn properties(beatmap: &Beatmap) -> Self {
+        return Self { properties: Some(Properties::of(beatmap)), ..Default::default() };
+    }
+
+    /// Changes from the snapshot to the current state of the beatmap
+    fn changes(&self, beatmap: &Beatmap) -> Vec<Change> {
+        let mut changes = vec![];
+        if let Some(objects) = &self.objects && let Some(splice) = Splice::between(objects, &beatmap.objects) {
+            changes.push(Change::Objects(splice));
+        }
+
+        if let Some(timing) = &self.timing && let Some(splice) = Splice::between(timing, &beatmap.timing) {
+            changes.push(Change::Timing(splice));
+        }
+
+        if let Some(velocity) = &self.velocity && let Some(splice) = Splice::between(velocity, &beatmap.velocity) {
+            changes.push(Change::Velocity(splice));
+        }
+
+        if let Some(before) = &self.properties {
+            let after = Properties::of(beatmap);
+            if *before != after {
+                changes.push(Change::Properties { before: Box::new(before.clone()), after: Box::new(after) });
+            }
+        }
+
+        return changes;
+    }
+
+    /// Takes the parts this snapshot doesn't have yet from `other`
+    fn fill(&mut self, other: Snapshot) {
+        if self.objects.is_none() { self.objects = other.objects; }
+        if self.timing.is_none() { self.timing = other.timing; }
+        if self.velocity.is_none() { self.velocity = other.velocity; }
+        if self.properties.is_none() { self.properties = other.properties; }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Command {
+    pub name    : String,
+    pub changes : Vec<Change>,
+}
+
+/// Undo stack of a single beatmap, commands past `position` have been undone and can be redone
+#[derive(Default)]
+pub struct History {
+    commands : Vec<Command>,
+    position : usize,
+
+    /// The latest command keeps absorbing edits with the same name until sealed, used for drags.
+    /// Holds the beatmap from before its first edit, merged edits are compared against it.
+    open     : Option<Snapshot>,
+
+    /// Commands dropped because of [`MAX_COMMANDS`], the oldest state left is after them
+    dropped  : usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        return &self.commands;
+    }
+
+    /// Amount of commands currently applied
+    pub fn position(&self) -> usize {
+        return self.position;
+    }
+
+    /// Amount of commands which were too old to keep
+    pub fn dropped(&self) -> usize {
+        return self.dropped;
+    }
+
+    pub fn can_undo(&self) -> bool { return self.position > 0; }
+    pub fn can_redo(&self) -> bool { return self.position < self.commands.len(); }
+
+    /// Records what changed since `before` was taken, does nothing if nothing did.
+    /// With `merge` set, consecutive edits of the same name become one command until [`History::seal`] is called.
+    pub fn record(&mut self, name: &str, merge: bool, before: Snapshot, beatmap: &Beatmap) {
+        if merge && self.merges(name) && let Some(open) = &mut self.open && let Some(last) = self.commands.last_mut() {
+            // Rebuilt against the state before the first merged edit
+            open.fill(before);
+            last.changes = open.changes(beatmap);
+            if last.changes.is_empty() {
+                self.commands.pop();
+                self.position = self.commands.len();
+                self.open = None;
+            }
+
+            return;
+        }
+
+        let changes = before.changes(beatmap);
+        if changes.is_empty() { return }
+
+        self.commands.truncate(self.position);
+        self.commands.push(Command { name: name.to_owned(), changes });
+        if self.commands.len() > MAX_COMMANDS {
+            self.commands.remove(0);
+            self.dropped += 1;
+        }
+
+        self.position = self.commands.len();
+        self.open = if merge { Some(before) } else { None };
+    }
+
+    /// Snapshot of the objects to record an edit with, empty if the edit merges into an open command which already has them
+    pub fn objects_snapshot(&self, name: &str, merge: bool, beatmap: &Beatmap) -> Snapshot {
+        if merge && self.merges(name) && self.open.as_ref().is_some_and(|x| x.objects.is_some()) {
+            return Snapshot::default();
+        }
+
+        return Snapshot::objects(beatmap);
+    }
+
+    /// Whether a merged edit named `name` would go into the latest command
+    fn merges(&self, name: &str) -> bool {
+        return self.open.is_some() && self.position == self.commands.len() && self.commands.last().is_some_and(|x| x.name == name);
+    }
+
+    /// Ends merging, the next edit starts a new command
+    pub fn seal(&mut self) {
+        self.open = None;
+    }
+
+    /// Returns whether anything was undone
+    pub fn undo(&mut self, beatmap: &mut Beatmap) -> bool {
+        if !self.can_undo() { return false }
+        self.open = None;
+        self.position -= 1;
+        for change in self.commands[self.position].changes.iter().rev() {
+            change.revert(beatmap);
+        }
+
+        return true;
+    }
+
+    /// Returns whether anything was redone
+    pub fn redo(&mut self, beatmap: &mut Beatmap) -> bool {
+        if !self.can_redo() { return false }
+        self.open = None;
+        for change in &self.commands[self.position].changes {
+            change.apply(beatmap);
+        }
+
+        self.position += 1;
+        return true;
+    }
+
+    /// Undoes or redoes until `position` commands are applied
+    pub fn jump_to(&mut self, position: usize, beatmap: &mut Beatmap) {
+        while self.position > position && self.undo(beatmap) { }
+        while self.position < position && self.redo(beatmap) { }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wcore::time::Time;
+    use crate::taiko::{parser, editor, taiko_circle::TaikoColor};
+
+    fn stream() -> Beatmap {
+        return parser::try_parse(include_str!("../../tests/fixtures/stream.osu")).unwrap();
+    }
+
+    fn times(beatmap: &Beatmap) -> Vec<u64> {
+        return beatmap.objects.iter().map(|x| x.time.to_ms()).collect();
+    }
+
+    /// Places a circle the way the editor does
+    fn place(history: &mut History, beatmap: &mut Beatmap, time_ms: f64) {
+        let before = history.objects_snapshot("Place object", false, beatmap);
+        editor::insert(beatmap, editor::circle(Time::from_ms(time_ms), TaikoColor::DON, false));
+        history.record("Place object", false, before, beatmap);
+    }
+
+    /// One step of dragging the object at `idx`
+    fn drag(history: &mut History, beatmap: &mut Beatmap, idx: usize, time_ms: f64) -> usize {
+        let before = history.objects_snapshot("Move objects", true, beatmap);
+        let moved = editor::move_all(beatmap, &[idx], &[Time::from_ms(time_ms)]).unwrap();
+        history.record("Move objects", true, before, beatmap);
+        return moved[0];
+    }
+
+    #[test]
+    fn splice_keeps_changed_part() {
+        let splice = Splice::between(&[1, 2, 3, 4, 5], &[1, 2, 9, 9, 4, 5]).unwrap();
+        assert_eq!((splice.start, splice.removed.as_slice(), splice.inserted.as_slice()), (2, [3].as_slice(), [9, 9].as_slice()));
+        assert!(Splice::between(&[1, 2], &[1, 2]).is_none());
+
+        let mut list = vec![1, 2, 3, 4, 5];
+        splice.apply(&mut list);
+        assert_eq!(list, [1, 2, 9, 9, 4, 5]);
+        splice.revert(&mut list);
+        assert_eq!(list, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut beatmap = stream();
+        let original = times(&beatmap);
+        let mut history = History::new();
+
+        place(&mut history, &mut beatmap, 100_000.0);
+        let placed = times(&beatmap);
+        assert_eq!(placed.len(), original.len() + 1);
+        assert!(history.can_undo() && !history.can_redo());
+
+        assert!(history.undo(&mut beatmap));
+        assert_eq!(times(&beatmap), original);
+        assert!(!history.undo(&mut beatmap));
+
+        assert!(history.redo(&mut beatmap));
+        assert_eq!(times(&beatmap), placed);
+        assert!(!history.redo(&mut beatmap));
+
+        // Edits which don't change anything aren't recorded
+        let before = history.objects_snapshot("Nothing", false, &beatmap);
+        history.record("Nothing", false, before, &beatmap);
+        assert_eq!(history.commands().len(), 1);
+    }
+
+    #[test]
+    fn merges_until_sealed() {
+        let mut beatmap = stream();
+        let original = times(&beatmap);
+        let mut history = History::new();
+
+        let mut idx = drag(&mut history, &mut beatmap, 0, 50_000.0);
+        idx = drag(&mut history, &mut beatmap, idx, 60_000.0);
+
+        // Merged edits don't copy the objects again
+        assert!(history.objects_snapshot("Move objects", true, &beatmap).objects.is_none());
+        assert!(history.objects_snapshot("Place object", false, &beatmap).objects.is_some());
+
+        idx = drag(&mut history, &mut beatmap, idx, 70_000.0);
+        assert_eq!(history.commands().len(), 1);
+        let dragged = times(&beatmap);
+
+        history.undo(&mut beatmap);
+        assert_eq!(times(&beatmap), original);
+        history.redo(&mut beatmap);
+        assert_eq!(times(&beatmap), dragged);
+
+        // Sealing starts a new command, even with the same name
+        history.seal();
+        drag(&mut history, &mut beatmap, idx, 80_000.0);
+        assert_eq!(history.commands().len(), 2);
+
+        // So does an edit of another name
+        place(&mut history, &mut beatmap, 100_000.0);
+        drag(&mut history, &mut beatmap, 0, 90_000.0);
+        assert_eq!(history.commands().iter().map(|x| x.name.as_str()).collect::<Vec<_>>(), ["Move objects", "Move objects", "Place object", "Move objects"]);
+    }
+
+    #[test]
+    fn merge_back_to_start_drops_command() {
+        let mut beatmap = stream();
+        let original = times(&beatmap);
+        let mut history = History::new();
+
+        let idx = drag(&mut history, &mut beatmap, 0, 50_000.0);
+        drag(&mut history, &mut beatmap, idx, original[0] as f64);
+        assert_eq!(times(&beatmap), original);
+        assert!(history.commands().is_empty() && !history.can_undo());
+    }
+
+    #[test]
+    fn edit_after_undo_truncates_redo() {
+        let mut beatmap = stream();
+        let mut history = History::new();
+
+        place(&mut history, &mut beatmap, 100_000.0);
+        place(&mut history, &mut beatmap, 101_000.0);
+        place(&mut history, &mut beatmap, 102_000.0);
+        history.undo(&mut beatmap);
+        history.undo(&mut beatmap);
+        assert_eq!((history.position(), history.commands().len()), (1, 3));
+
+        place(&mut history, &mut beatmap, 103_000.0);
+        assert_eq!((history.position(), history.commands().len()), (2, 2));
+        assert!(!history.can_redo());
+
+        let times = times(&beatmap);
+        assert!(times.contains(&100_000) && times.contains(&103_000));
+        assert!(!times.contains(&101_000) && !times.contains(&102_000));
+    }
+
+    #[test]
+    fn undo_ends_merging() {
+        let mut beatmap = stream();
+        let mut history = History::new();
+
+        place(&mut history, &mut beatmap, 100_000.0);
+        let idx = drag(&mut history, &mut beatmap, 0, 50_000.0);
+        history.undo(&mut beatmap);
+        history.redo(&mut beatmap);
+        drag(&mut history, &mut beatmap, idx, 60_000.0);
+        assert_eq!(history.commands().len(), 3);
+    }
+
+    #[test]
+    fn drops_oldest_commands() {
+        let mut beatmap = stream();
+        let original = times(&beatmap);
+        let mut history = History::new();
+
+        for i in 0 .. MAX_COMMANDS + 3 {
+            place(&mut history, &mut beatmap, 100_000.0 + i as f64 * 10.0);
+        }
+
+        assert_eq!(history.commands().len(), MAX_COMMANDS);
+        assert_eq!((history.position(), history.dropped()), (MAX_COMMANDS, 3));
+
+        // Undoing everything only goes back to after the dropped commands
+        history.jump_to(0, &mut beatmap);
+        assert_eq!(times(&beatmap).len(), original.len() + 3);
+        assert_eq!(times(&beatmap)[original.len() ..], [100_000, 100_010, 100_020]);
+    }
+
+    #[test]
+    fn jumps_both_ways() {
+        let mut beatmap = stream();
+        let mut history = History::new();
+        let mut states = vec![times(&beatmap)];
+
+        for i in 0 .. 5 {
+            place(&mut history, &mut beatmap, 100_000.0 + i as f64 * 10.0);
+            states.push(times(&beatmap));
+        }
+
+        history.jump_to(1, &mut beatmap);
+        assert_eq!((history.position(), &times(&beatmap)), (1, &states[1]));
+
+        history.jump_to(4, &mut beatmap);
+        assert_eq!((history.position(), &times(&beatmap)), (4, &states[4]));
+
+        history.jump_to(4, &mut beatmap);
+        assert_eq!(history.position(), 4);
+
+        // Past the end stops at the newest command
+        history.jump_to(10, &mut beatmap);
+        assert_eq!((history.position(), &times(&beatmap)), (5, &states[5]));
+
+        history.jump_to(0, &mut beatmap);
+        assert_eq!((history.position(), &times(&beatmap)), (0, &states[0]));
+    }
+
+    #[test]
+    fn properties_and_points() {
+        let mut beatmap = stream();
+        let mut history = History::new();
+        let (velocity, timing) = (beatmap.velocity.len(), beatmap.timing[0].bpm);
+
+        let before = Snapshot::properties(&beatmap);
+        beatmap.metadata.version = "Edited".to_owned();
+        history.record("Edit properties", false, before, &beatmap);
+
+        let before = Snapshot::timing(&beatmap);
+        beatmap.timing[0].bpm *= 2.0;
+        history.record("Edit timing", false, before, &beatmap);
+
+        let before = Snapshot::velocity(&beatmap);
+        beatmap.velocity.clear();
+        history.record("Edit velocity", false, before, &beatmap);
+
+        history.jump_to(0, &mut beatmap);
+        assert_ne!(beatmap.metadata.version, "Edited");
+        assert_eq!((beatmap.velocity.len(), beatmap.timing[0].bpm), (velocity, timing));
+
+        history.jump_to(3, &mut beatmap);
+        assert_eq!(beatmap.metadata.version, "Edited");
+        assert_eq!((beatmap.velocity.len(), beatmap.timing[0].bpm), (0, timing * 2.0));
+    }
+}
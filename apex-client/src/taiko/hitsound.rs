@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use super::{parser::Beatmap, taiko_circle::{TaikoCircle, TaikoColor}};
+
+// Bit flags of the `hitSound` column
+pub const NORMAL  : u8 = 0b0001;
+pub const WHISTLE : u8 = 0b0010;
+pub const FINISH  : u8 = 0b0100;
+pub const CLAP    : u8 = 0b1000;
+
+/// Extensions osu! looks for when loading beatmap samples, in order
+const SAMPLE_EXTENSIONS: [&str; 3] = ["wav", "ogg", "mp3"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SampleSet {
+    /// Inherited from the timing point, or from the beatmap for timing points
+    #[default]
+    Auto,
+    Normal,
+    Soft,
+    Drum,
+}
+
+impl SampleSet {
+    pub fn from_index(value: u8) -> Option<Self> {
+        return match value {
+            0 => Some(SampleSet::Auto),
+            1 => Some(SampleSet::Normal),
+            2 => Some(SampleSet::Soft),
+            3 => Some(SampleSet::Drum),
+            _ => None,
+        };
+    }
+
+    pub fn index(&self) -> u8 {
+        return *self as u8;
+    }
+
+    /// Name used by the `SampleSet` key in [General]
+    pub fn from_name(value: &str) -> Option<Self> {
+        return match value {
+            "All" | "None" => Some(SampleSet::Auto),
+            "Normal" => Some(SampleSet::Normal),
+            "Soft"   => Some(SampleSet::Soft),
+            "Drum"   => Some(SampleSet::Drum),
+            _ => None,
+        };
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            SampleSet::Auto   => "None",
+            SampleSet::Normal => "Normal",
+            SampleSet::Soft   => "Soft",
+            SampleSet::Drum   => "Drum",
+        };
+    }
+
+    /// Prefix of sample file names
+    fn prefix(&self) -> &'static str {
+        return match self {
+            SampleSet::Auto | SampleSet::Normal => "normal",
+            SampleSet::Soft => "soft",
+            SampleSet::Drum => "drum",
+        };
+    }
+}
+
+impl FromStr for SampleSet {
+    type Err = ();
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        return SampleSet::from_name(value).ok_or(());
+    }
+}
+
+/// The `hitSample` column, zero values are inherited from the active timing point
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct HitSample {
+    pub normal_set   : SampleSet,
+    pub addition_set : SampleSet,
+    pub index        : u32,
+    pub volume       : u8,
+
+    /// Replaces every other sample of the object if set
+    pub filename     : String,
+}
+
+/// Hit sound flags osu! uses for the given taiko object
+pub fn hit_sound(color: TaikoColor, big: bool) -> u8 {
+    let mut value = 0;
+    if color == TaikoColor::KAT { value |= CLAP; }
+    if big { value |= FINISH; }
+    return value;
+}
+
+/// Sample file an object triggers
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ResolvedSample {
+    pub filename : String,
+    pub volume   : u8,
+
+    /// Whether the file comes from the beatmap, otherwise it's one of the default skin samples
+    pub custom   : bool,
+}
+
+/// Resolves every sample an object triggers, `has_file` tells whether the beatmap archive contains a file.
+/// Sample sets, the custom index and volume fall back to the active timing point, then to the beatmap.
+pub fn resolve(beatmap: &Beatmap, object: &TaikoCircle, has_file: impl Fn(&str) -> bool) -> Vec<ResolvedSample> {
+    let control = beatmap.control_at(object.time);
+    let sample = &object.sample;
+    let volume = if sample.volume != 0 { sample.volume } else { control.volume };
+
+    if !sample.filename.is_empty() && has_file(&sample.filename) {
+        return vec![ResolvedSample { filename: sample.filename.clone(), volume, custom: true }];
+    }
+
+    let inherited = [SampleSet::from_index(control.sample_set).unwrap_or_default(), beatmap.general.sample_set];
+    let normal_set = std::iter::once(sample.normal_set)
+        .chain(inherited)
+        .find(|x| *x != SampleSet::Auto)
+        .unwrap_or(SampleSet::Normal);
+
+    let addition_set = if sample.addition_set != SampleSet::Auto { sample.addition_set } else { normal_set };
+    let index = if sample.index != 0 { sample.index } else { control.sample_index };
+
+    // The normal sound is always played, additions only if their flag is set
+    let mut sounds = vec![(normal_set, "normal")];
+    for (flag, name) in [(WHISTLE, "whistle"), (FINISH, "finish"), (CLAP, "clap")] {
+        if object.hit_sound & flag != 0 { sounds.push((addition_set, name)); }
+    }
+
+    return sounds.into_iter().map(|(set, name)| {
+        let name = format!("{}-hit{name}", set.prefix());
+
+        // Index 0 always uses the skin, index 1 has no suffix
+        let custom = match index {
+            0 => None,
+            1 => find_sample(&name, &has_file),
+            _ => find_sample(&format!("{name}{index}"), &has_file),
+        };
+
+        match custom {
+            Some(filename) => ResolvedSample { filename, volume, custom: true },
+            None => ResolvedSample { filename: format!("{name}.wav"), volume, custom: false },
+        }
+    }).collect();
+}
+
+fn find_sample(name: &str, has_file: &impl Fn(&str) -> bool) -> Option<String> {
+    return SAMPLE_EXTENSIONS.iter()
+        .map(|extension| format!("{name}.{extension}"))
+        .find(|filename| has_file(filename));
+}
@@ -0,0 +1,184 @@
+use std::{fmt::Display, path::Path};
+
+use wcore::time::Time;
+
+use super::{parser::Beatmap, taiko_circle::TaikoKind};
+
+/// Beat subdivisions objects are expected to snap to
+pub const SNAP_DIVISORS: [u32; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+/// Consecutive slider velocities differing by more than this factor are reported
+const VELOCITY_JUMP: f64 = 3.0;
+
+/// Ranking criteria minimum for the drain time
+const MIN_LENGTH_MS: f64 = 30_000.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Warning,
+    Problem,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Check {
+    Unsnapped,
+    Overlap,
+    BeforeTiming,
+    VelocityJump,
+    MissingAudio,
+    Length,
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    /// `None` for issues with the beatmap as a whole
+    pub time     : Option<Time>,
+    pub check    : Check,
+    pub severity : Severity,
+    pub message  : String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self.time {
+            Some(time) => write!(f, "{}ms: {}", time.to_ms(), self.message),
+            None       => write!(f, "{}", self.message),
+        };
+    }
+}
+
+/// Runs every check, `has_file` tells whether a file referenced by the beatmap exists.
+/// Issues are sorted by time, the ones concerning the whole beatmap go first.
+pub fn check(beatmap: &Beatmap, has_file: impl Fn(&Path) -> bool) -> Vec<Issue> {
+    let mut issues = Vec::<Issue>::new();
+    check_audio(beatmap, &has_file, &mut issues);
+    check_length(beatmap, &mut issues);
+    check_timing(beatmap, &mut issues);
+    check_snapping(beatmap, &mut issues);
+    check_overlaps(beatmap, &mut issues);
+    check_velocity(beatmap, &mut issues);
+
+    issues.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    return issues;
+}
+
+fn issue(issues: &mut Vec<Issue>, time: Option<Time>, check: Check, severity: Severity, message: String) {
+    issues.push(Issue { time, check, severity, message });
+}
+
+fn check_audio(beatmap: &Beatmap, has_file: &impl Fn(&Path) -> bool, issues: &mut Vec<Issue>) {
+    if beatmap.audio.as_os_str().is_empty() {
+        issue(issues, None, Check::MissingAudio, Severity::Problem, String::from("No audio file is set"));
+    } else if !has_file(&beatmap.audio) {
+        issue(issues, None, Check::MissingAudio, Severity::Problem, format!("Audio file {} is missing", beatmap.audio.display()));
+    }
+}
+
+fn check_length(beatmap: &Beatmap, issues: &mut Vec<Issue>) {
+    let Some(first) = beatmap.objects.first() else {
+        issue(issues, None, Check::Length, Severity::Problem, String::from("Beatmap has no objects"));
+        return;
+    };
+
+    let last = beatmap.objects.iter().map(|x| x.end_time()).fold(first.time, |a, b| if b > a { b } else { a });
+    let length = (last - first.time).to_seconds() * 1000.0;
+    if length < MIN_LENGTH_MS {
+        issue(issues, None, Check::Length, Severity::Problem, format!("Beatmap is only {:.1}s long, at least 30s are required", length / 1000.0));
+    }
+
+    for object in &beatmap.objects {
+        let (name, duration) = match object.kind {
+            TaikoKind::Circle => continue,
+            TaikoKind::Drumroll { duration, .. } => ("Drumroll", duration),
+            TaikoKind::Swell { duration, .. } => ("Swell", duration),
+        };
+
+        if duration.to_seconds() <= 0.0 {
+            issue(issues, Some(object.time), Check::Length, Severity::Problem, format!("{name} has no length"));
+        }
+    }
+}
+
+fn check_timing(beatmap: &Beatmap, issues: &mut Vec<Issue>) {
+    let Some(first) = beatmap.timing.first() else {
+        issue(issues, None, Check::BeforeTiming, Severity::Problem, String::from("Beatmap has no uninherited timing points"));
+        return;
+    };
+
+    for object in beatmap.objects.iter().take_while(|x| x.time < first.time) {
+        issue(issues, Some(object.time), Check::BeforeTiming, Severity::Problem, String::from("Object is placed before the first timing point"));
+    }
+}
+
+fn check_snapping(beatmap: &Beatmap, issues: &mut Vec<Issue>) {
+    for object in &beatmap.objects {
+        let ends = match object.kind {
+            TaikoKind::Circle => vec![(object.time, "Object")],
+            _ => vec![(object.time, "Object"), (object.end_time(), "Object end")],
+        };
+
+        for (time, name) in ends {
+            let Some((offset, divisor)) = snap_offset(beatmap, time) else { continue };
+
+            // Editors round snapped times to whole milliseconds
+            let severity = match offset.abs() {
+                x if x < 1.0 => continue,
+                x if x < 2.0 => Severity::Warning,
+                _ => Severity::Problem,
+            };
+
+            issue(issues, Some(time), Check::Unsnapped, severity, format!("{name} is unsnapped by {offset:.1}ms from 1/{divisor}"));
+        }
+    }
+}
+
+/// Distance in ms from the closest snapped position of any divisor, and that divisor
+pub fn snap_offset(beatmap: &Beatmap, time: Time) -> Option<(f64, u32)> {
+    let point = beatmap.timing_at(time)?;
+    let beat_length = point.beat_length();
+    if !beat_length.is_finite() || beat_length <= 0.0 { return None }
+
+    let time = time.to_seconds() * 1000.0;
+    let beats = (time - point.time.to_seconds() * 1000.0) / beat_length;
+    return SNAP_DIVISORS.iter()
+        .map(|&divisor| {
+            let snapped = (beats * divisor as f64).round() / divisor as f64;
+            (time - (point.time.to_seconds() * 1000.0 + snapped * beat_length), divisor)
+        })
+        .min_by(|a, b| a.0.abs().total_cmp(&b.0.abs()));
+}
+
+fn check_overlaps(beatmap: &Beatmap, issues: &mut Vec<Issue>) {
+    let mut previous_end = None::<Time>;
+    for object in &beatmap.objects {
+        if let Some(end) = previous_end && (object.time - end).to_seconds() * 1000.0 < 1.0 {
+            issue(issues, Some(object.time), Check::Overlap, Severity::Problem, String::from("Object overlaps the previous one"));
+        }
+
+        let end = object.end_time();
+        previous_end = Some(match previous_end { Some(x) if x > end => x, _ => end });
+    }
+}
+
+fn check_velocity(beatmap: &Beatmap, issues: &mut Vec<Issue>) {
+    let mut times = beatmap.timing.iter().map(|x| x.time)
+        .chain(beatmap.velocity.iter().map(|x| x.time))
+        .collect::<Vec<_>>();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    times.dedup();
+
+    let mut previous = 1.0;
+    for time in times {
+        let velocity = beatmap.velocity_at(time);
+        if velocity <= 0.0 || !velocity.is_finite() {
+            issue(issues, Some(time), Check::VelocityJump, Severity::Problem, format!("Invalid slider velocity {velocity}x"));
+            continue;
+        }
+
+        if velocity / previous > VELOCITY_JUMP || previous / velocity > VELOCITY_JUMP {
+            issue(issues, Some(time), Check::VelocityJump, Severity::Warning, format!("Slider velocity jumps from {previous:.2}x to {velocity:.2}x"));
+        }
+
+        previous = velocity;
+    }
+}
@@ -1,2 +1,14 @@
 pub mod taiko_circle;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod writer;
+pub mod tja;
+pub mod tja_writer;
+pub mod hitsound;
+pub mod difficulty;
+pub mod performance;
+pub mod lint;
+pub mod diff;
+pub mod velocity;
+pub mod editor;
+pub mod history;
+pub mod transform;
\ No newline at end of file
@@ -1,160 +1,1007 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, fmt::Display, str::FromStr, cmp::Ordering};
 
 use wcore::time::Time;
 
-use super::taiko_circle::{TaikoCircle, TaikoColor};
+use crate::mania::vsrg_note::VsrgNote;
 
+use super::{taiko_circle::{TaikoCircle, TaikoColor, TaikoKind}, hitsound::{self, HitSample, SampleSet}};
+
+// Bit flags of the `effects` column
+pub const KIAI               : u8 = 0b0001;
+pub const OMIT_FIRST_BARLINE : u8 = 0b1000;
+
+// Bit flags of the object `type` column which don't affect the kind of object
+pub const NEW_COMBO   : u8 = 0b0000_0100;
+pub const COMBO_FLAGS : u8 = 0b0111_0100;
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct TimingPoint {
     pub time : Time,
     pub bpm  : f64,
+
+    pub meter        : u32,
+    pub sample_set   : u8,
+    pub sample_index : u32,
+    pub volume       : u8,
+    pub effects      : u8,
 }
 
+impl TimingPoint {
+    pub fn beat_length(&self) -> f64 {
+        return 60_000.0 / self.bpm;
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct VelocityPoint {
     pub time     : Time,
     pub velocity : f64,
+
+    pub meter        : u32,
+    pub sample_set   : u8,
+    pub sample_index : u32,
+    pub volume       : u8,
+    pub effects      : u8,
+}
+
+/// Section which is not (fully) modeled by [`Beatmap`], but has to be preserved when writing it back.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RawSection {
+    pub name  : String,
+    pub lines : Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BeatmapGeneral {
+    pub audio_lead_in : Time,
+    pub preview_time  : Option<Time>,
+
+    /// Used by timing points which don't specify a sample set
+    pub sample_set    : SampleSet,
+
+    /// Converted from an osu!standard beatmap
+    pub converted     : bool,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BeatmapMetadata {
+    pub title          : String,
+    pub title_unicode  : String,
+    pub artist         : String,
+    pub artist_unicode : String,
+    pub creator        : String,
+    pub version        : String,
+    pub source         : String,
+    pub tags           : Vec<String>,
+    pub beatmap_id     : Option<i32>,
+    pub beatmap_set_id : Option<i32>,
 }
 
-pub struct Beatmap {
-    pub objects  : Vec<TaikoCircle>,
+#[derive(Clone, PartialEq, Debug)]
+pub struct BeatmapDifficulty {
+    pub hp_drain_rate      : f32,
+    pub circle_size        : f32,
+    pub overall_difficulty : f32,
+    pub approach_rate      : f32,
+    pub slider_multiplier  : f64,
+    pub slider_tick_rate   : f64,
+}
+
+impl Default for BeatmapDifficulty {
+    fn default() -> Self {
+        return Self {
+            hp_drain_rate      : 5.0,
+            circle_size        : 5.0,
+            overall_difficulty : 5.0,
+            approach_rate      : 5.0,
+            slider_multiplier  : 1.4,
+            slider_tick_rate   : 1.0,
+        };
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Beatmap<T = TaikoCircle> {
+    pub objects  : Vec<T>,
     pub timing   : Vec<TimingPoint>,
     pub velocity : Vec<VelocityPoint>,
 
+    pub general    : BeatmapGeneral,
+    pub metadata   : BeatmapMetadata,
+    pub difficulty : BeatmapDifficulty,
+
     pub velocity_multiplier : f32,
 
     pub audio: PathBuf,
+
+    /// Every section in file order, [TimingPoints] and [HitObjects] are left empty
+    pub sections: Vec<RawSection>,
+}
+
+impl<T> Beatmap<T> {
+    /// Uninherited timing point active at the given time
+    pub fn timing_at(&self, time: Time) -> Option<&TimingPoint> {
+        return timing_at(&self.timing, time);
+    }
+
+    /// Slider velocity multiplier active at the given time
+    pub fn velocity_at(&self, time: Time) -> f64 {
+        return velocity_at(&self.timing, &self.velocity, time);
+    }
+
+    /// Closest 1/`divisor` beat of the active timing point, rounded to whole milliseconds
+    pub fn snap(&self, time: Time, divisor: u32) -> Time {
+        let Some(point) = self.timing_at(time) else { return time };
+        let step = point.beat_length() / divisor.max(1) as f64;
+        if !step.is_finite() || step <= 0.0 { return time }
+
+        let origin = point.time.to_seconds() * 1000.0;
+        let steps = ((time.to_seconds() * 1000.0 - origin) / step).round();
+        return Time::from_ms((origin + steps * step).round());
+    }
+
+    /// Sample and effect settings active at the given time
+    pub fn control_at(&self, time: Time) -> ControlPoint {
+        return control_at(&self.timing, &self.velocity, time);
+    }
+
+    /// Whether kiai time is active at the given time
+    pub fn is_kiai(&self, time: Time) -> bool {
+        return self.control_at(time).effects & KIAI != 0;
+    }
+}
+
+/// Settings shared by both kinds of timing points, the latest point of either kind is the active one
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ControlPoint {
+    pub sample_set   : u8,
+    pub sample_index : u32,
+    pub volume       : u8,
+    pub effects      : u8,
+}
+
+impl Default for ControlPoint {
+    fn default() -> Self {
+        return Self { sample_set: 0, sample_index: 0, volume: 100, effects: 0 };
+    }
+}
+
+/// Expects points to be sorted, the first timing point is used if `time` precedes all points.
+pub fn control_at(timing: &[TimingPoint], velocity: &[VelocityPoint], time: Time) -> ControlPoint {
+    let idx = velocity.partition_point(|x| x.time <= time);
+    let velocity = idx.checked_sub(1).and_then(|idx| velocity.get(idx));
+    let timing = timing_at(timing, time);
+
+    let from_timing = |x: &TimingPoint| ControlPoint { sample_set: x.sample_set, sample_index: x.sample_index, volume: x.volume, effects: x.effects };
+    let from_velocity = |x: &VelocityPoint| ControlPoint { sample_set: x.sample_set, sample_index: x.sample_index, volume: x.volume, effects: x.effects };
+    return match (timing, velocity) {
+        (Some(t), Some(v)) => if v.time >= t.time { from_velocity(v) } else { from_timing(t) },
+        (Some(t), None)    => from_timing(t),
+        (None, Some(v))    => from_velocity(v),
+        (None, None)       => ControlPoint::default(),
+    };
+}
+
+/// Expects points to be sorted, returns the first point if `time` precedes all of them.
+pub fn timing_at(timing: &[TimingPoint], time: Time) -> Option<&TimingPoint> {
+    let idx = timing.partition_point(|x| x.time <= time);
+    return timing.get(idx.saturating_sub(1));
+}
+
+/// Expects points to be sorted, uninherited timing points reset velocity back to 1.0.
+pub fn velocity_at(timing: &[TimingPoint], velocity: &[VelocityPoint], time: Time) -> f64 {
+    let idx = velocity.partition_point(|x| x.time <= time);
+    let Some(point) = idx.checked_sub(1).and_then(|idx| velocity.get(idx)) else { return 1.0 };
+
+    if let Some(timing) = timing_at(timing, time)
+    && timing.time > point.time && timing.time <= time {
+        return 1.0;
+    }
+
+    return point.velocity;
+}
+
+/// Expects points to be sorted, returns the start of every measure up to `end_time`.
+/// Measures restart at every timing point, points with [`OMIT_FIRST_BARLINE`] skip their own barline.
+pub fn barlines(timing: &[TimingPoint], end_time: Time) -> Vec<Time> {
+    let end_time = end_time.to_seconds() * 1000.0;
+    let mut out = vec![];
+    for (idx, point) in timing.iter().enumerate() {
+        let start = point.time.to_seconds() * 1000.0;
+        let measure = point.beat_length() * point.meter.max(1) as f64;
+        if !measure.is_finite() || measure <= 0.0 { continue }
+
+        // Barlines right before the next timing point are dropped, like in osu!
+        let limit = timing.get(idx + 1).map(|x| x.time.to_seconds() * 1000.0 - 1.0).unwrap_or(end_time);
+        let mut measure_idx = if point.effects & OMIT_FIRST_BARLINE != 0 { 1 } else { 0 };
+        loop {
+            let time = start + measure_idx as f64 * measure;
+            if time > limit { break }
+
+            out.push(Time::from_ms(time));
+            measure_idx += 1;
+        }
+    }
+
+    return out;
+}
+
+impl Beatmap {
+    /// Barlines up to the end of the last object
+    pub fn barlines(&self) -> Vec<Time> {
+        let end_time = self.objects.iter().map(|x| x.end_time()).fold(Time::zero(), |a, b| if b > a { b } else { a });
+        return barlines(&self.timing, end_time);
+    }
+}
+
+impl Beatmap<VsrgNote> {
+    pub fn key_count(&self) -> usize {
+        return key_count(&self.difficulty);
+    }
+}
+
+/// Beatmap of any supported game mode
+pub enum OsuBeatmap {
+    Taiko(Beatmap<TaikoCircle>),
+    Mania(Beatmap<VsrgNote>),
+}
+
+impl OsuBeatmap {
+    pub fn metadata(&self) -> &BeatmapMetadata {
+        return match self {
+            OsuBeatmap::Taiko(beatmap) => &beatmap.metadata,
+            OsuBeatmap::Mania(beatmap) => &beatmap.metadata,
+        };
+    }
+
+    pub fn audio(&self) -> &PathBuf {
+        return match self {
+            OsuBeatmap::Taiko(beatmap) => &beatmap.audio,
+            OsuBeatmap::Mania(beatmap) => &beatmap.audio,
+        };
+    }
+
+    pub fn object_count(&self) -> usize {
+        return match self {
+            OsuBeatmap::Taiko(beatmap) => beatmap.objects.len(),
+            OsuBeatmap::Mania(beatmap) => beatmap.objects.len(),
+        };
+    }
+
+    /// End time of the last object
+    pub fn length(&self) -> Time {
+        let end_time = match self {
+            OsuBeatmap::Taiko(beatmap) => beatmap.objects.iter().map(|x| x.end_time()).reduce(|a, b| if b > a { b } else { a }),
+            OsuBeatmap::Mania(beatmap) => beatmap.objects.iter().map(|x| x.end_time()).reduce(|a, b| if b > a { b } else { a }),
+        };
+
+        return end_time.unwrap_or_else(Time::zero);
+    }
 }
 
-type ParseError = ();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// Required section is not present in the file
+    MissingSection { section: &'static str },
+
+    /// Required key is not present in its section
+    MissingKey { section: &'static str, key: &'static str },
 
-#[allow(clippy::bind_instead_of_map, clippy::match_like_matches_macro)]
-#[allow(unused_variables, unused_assignments, clippy::result_unit_err)]
+    /// Line could not be parsed, `line` is 1-based
+    InvalidLine { line: usize, section: String, text: String, reason: String },
+
+    /// Game mode which is not supported (yet)
+    UnsupportedMode { mode: String },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ParseError::MissingSection { section } => write!(f, "missing section {section}"),
+            ParseError::MissingKey { section, key } => write!(f, "missing key `{key}` in section {section}"),
+            ParseError::InvalidLine { line, section, text, reason } => write!(f, "line {line} in section {section}: {reason}: `{text}`"),
+            ParseError::UnsupportedMode { mode } => write!(f, "unsupported game mode `{mode}`"),
+        };
+    }
+}
+
+impl std::error::Error for ParseError { }
+
+struct Entry<'a> {
+    idx   : usize,
+    line  : &'a str,
+    value : &'a str,
+}
+
+type Table<'a> = HashMap<&'a str, HashMap<&'a str, Entry<'a>>>;
+
+/// Sections which consist of `key: value` pairs
+const KEY_VALUE_SECTIONS: [&str; 5] = ["[General]", "[Editor]", "[Metadata]", "[Difficulty]", "[Colours]"];
+
+/// Parses a taiko beatmap, failing on the first line which could not be parsed.
 pub fn try_parse(data: &str) -> Result<Beatmap, ParseError> {
-    let mut version_string = None;
-    let mut objects_taiko = Vec::<TaikoCircle>::new();
+    return taiko_only(parse(data, None)?);
+}
+
+/// Parses a taiko beatmap, skipping lines which could not be parsed. Skipped lines are returned as warnings.
+pub fn try_parse_lenient(data: &str) -> Result<(Beatmap, Vec<ParseError>), ParseError> {
+    let (beatmap, warnings) = try_parse_osu_lenient(data)?;
+    return Ok((taiko_only(beatmap)?, warnings));
+}
+
+/// Parses a beatmap of any supported game mode, failing on the first line which could not be parsed.
+pub fn try_parse_osu(data: &str) -> Result<OsuBeatmap, ParseError> {
+    return parse(data, None);
+}
+
+/// Parses a beatmap of any supported game mode, skipping lines which could not be parsed.
+pub fn try_parse_osu_lenient(data: &str) -> Result<(OsuBeatmap, Vec<ParseError>), ParseError> {
+    let mut warnings = Vec::new();
+    let beatmap = parse(data, Some(&mut warnings))?;
+    return Ok((beatmap, warnings));
+}
+
+/// Parses loose lines of a [HitObjects] section against the timing of `beatmap`, anything else is skipped.
+pub fn parse_hit_objects(data: &str, beatmap: &Beatmap) -> Vec<TaikoCircle> {
+    return data.lines()
+        .filter_map(|line| parse_taiko_object(line.trim(), &beatmap.timing, &beatmap.velocity, &beatmap.difficulty).ok())
+        .collect();
+}
+
+fn taiko_only(beatmap: OsuBeatmap) -> Result<Beatmap, ParseError> {
+    return match beatmap {
+        OsuBeatmap::Taiko(beatmap) => Ok(beatmap),
+        OsuBeatmap::Mania(_)       => Err(ParseError::UnsupportedMode { mode: String::from("3") }),
+    };
+}
+
+fn parse(data: &str, mut warnings: Option<&mut Vec<ParseError>>) -> Result<OsuBeatmap, ParseError> {
     let mut timing_points = Vec::<TimingPoint>::new();
     let mut velocity_points = Vec::<VelocityPoint>::new();
 
-    
-    let mut table = HashMap::<&str, HashMap<&str, &str>>::new();
-    let mut category: Option<&str> = None;
-    for line in data.lines() {
-        if let Some(char) = line.chars().nth(0)
-        && char == '[' {
-            category = Some(line);
+    // Hit objects are parsed after everything else, because their format depends on the game mode
+    let mut object_lines = Vec::<(usize, &str)>::new();
+
+    // Only matters for converts, assume the latest version if the header is missing
+    let mut version = 14u32;
+
+    let mut table = Table::new();
+    let mut sections = Vec::<RawSection>::new();
+    let mut section: Option<&str> = None;
+    for (idx, line) in data.lines().enumerate() {
+        let line = if idx == 0 { line.trim_start_matches('\u{feff}') } else { line };
+        if line.starts_with('[') {
+            section = Some(line.trim_end());
+            sections.push(RawSection { name: line.trim_end().to_owned(), lines: vec![] });
             continue;
         }
 
-        match category {
-            Some("[TimingPoints]") => {
-                if line.trim().is_empty() { continue }
-                
-                let mut parts = line.split(',');
-                let Some(time_ms)     = parts.next().and_then(|x| x.parse::<i32>().ok()) else { continue };
-                let Some(beat_length) = parts.next().and_then(|x| x.parse::<f64>().ok()) else { continue };
-                let Some(uninherited) = parts.nth(4).and_then(|x| Some(x == "1"))        else { continue };
-                
-                if uninherited {
-                    let bpm = 60.0 / (beat_length / 1000.0);
-                    timing_points.push(TimingPoint {
-                        time : Time::from_ms(time_ms),
-                        bpm  : bpm,
-                    });
-                } else {
-                    let velocity = -100.0 / beat_length;
-                    velocity_points.push(VelocityPoint {
-                        time     : Time::from_ms(time_ms),
-                        velocity : velocity,
-                    });
+        if line.trim().is_empty() { continue }
+        if let Some(raw) = sections.last_mut()
+        && !matches!(section, Some("[TimingPoints]" | "[HitObjects]")) {
+            raw.lines.push(line.to_owned());
+        }
+
+        if line.starts_with("//") { continue }
+
+        let result = match section {
+            Some("[TimingPoints]") => parse_timing_point(line).map(|point| {
+                match point {
+                    ParsedPoint::Timing(point)   => timing_points.push(point),
+                    ParsedPoint::Velocity(point) => velocity_points.push(point),
                 }
-            }
+            }),
 
             Some("[HitObjects]") => {
-                let mut parts = line.split(',');
-
-                match table["[General]"]["Mode"] {
-                    // Taiko
-                    "1" => {
-                        let Some(time_in_ms)  = parts.nth(2).and_then(|x| x.parse::<f64>().ok()) else { continue };
-                        let Some(object_type) = parts.nth(1).and_then(|x| x.parse::<u8> ().ok()) else { continue };
-
-                        objects_taiko.push(
-                            TaikoCircle {
-                                time  : Time::from_ms(time_in_ms),
-                                big   : if let 4 | 6 = object_type { true            } else { false           },
-                                color : if let 0 | 4 = object_type { TaikoColor::DON } else { TaikoColor::KAT },
-                            }
-                        );
-                    }
-
-                    // Mania
-                    "3" => {
-                        let Ok(key_count) = table["[Difficulty]"]["CircleSize"].parse::<f64>() else { continue };
-
-                        // x,y,time,type,hitSound,...
-                        let Some(x_position)  = parts.next().and_then(|x| x.parse::<isize>().ok()) else { continue };
-                        let Some(time_ms)     = parts.nth(1).and_then(|x| x.parse::<f64>  ().ok()) else { continue };
-                        let Some(object_type) = parts.nth(1).and_then(|x| x.parse::<u8>   ().ok()) else { continue };
-
-                        // objects_mania.push(
-                        //     VsrgNote {
-                        //         time     : Time::from_ms(time_ms),
-                        //         key      : (x_position as f64 * key_count / 512.0).floor() as usize,
-                        //         duration : if (0b10000000 & object_type) != 0 { // is a hold note
-                        //             // ...,endTime:hitSample
-                        //             let Some(end_time_ms) = parts.nth(1).and_then(|x| {
-                        //                 let mut parts = x.split(':');
-                        //                 parts.next().and_then(|x| x.parse::<f64>().ok())
-                        //             }) else { continue };
-                                    
-                        //             Time::from_seconds(end_time_ms - time_ms)
-                        //         } else { Time::zero() },
-                        //     }
-                        // );
-                    }
-
-                    _ => {}
+                object_lines.push((idx, line));
+                Ok(())
+            }
+
+            Some(section) if KEY_VALUE_SECTIONS.contains(&section) => {
+                parse_key_value(line).map(|(key, value)| {
+                    table
+                        .entry(section)
+                        .or_default()
+                        .insert(key, Entry { idx, line, value });
+                })
+            }
+
+            // Sections like [Events] are not used yet
+            Some(_) => Ok(()),
+
+            None => {
+                match line.strip_prefix("osu file format v").map(|x| x.trim().parse::<u32>()) {
+                    Some(Ok(value)) => { version = value; Ok(()) }
+                    Some(Err(_)) => Err("invalid file format version"),
+                    None => Err("expected file format version"),
                 }
             }
+        };
+
+        if let Err(reason) = result {
+            report(&mut warnings, idx, section.unwrap_or("(header)"), line, reason)?;
+        }
+    }
+
+    let general    = parse_general   (&table, &mut warnings)?;
+    let metadata   = parse_metadata  (&table, &mut warnings)?;
+    let difficulty = parse_difficulty(&table, &mut warnings)?;
+
+    let audio = PathBuf::from(lookup(&table, "[General]", "AudioFilename")?);
+
+    timing_points.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+    velocity_points.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
 
-            Some(category) => {
-                if line.trim().is_empty() { continue }
+    let mode = lookup(&table, "[General]", "Mode")?;
+    return match mode {
+        // Taiko, standard beatmaps are converted
+        "0" | "1" => {
+            let converted = mode == "0";
+            let mut difficulty = difficulty;
+            if converted { difficulty.slider_multiplier *= LEGACY_VELOCITY_MULTIPLIER; }
 
-                let mut parts = line.split(':');
-                let Some(key)   = parts.next() else { continue };
-                let Some(value) = parts.next() else { continue };
+            let mut general = general;
+            general.converted = converted;
 
-                table
-                    .entry(category)
-                    .or_default()
-                    .insert(key.trim(), value.trim());
+            let mut objects = Vec::<TaikoCircle>::with_capacity(object_lines.len());
+            for (idx, line) in object_lines {
+                let result = match converted {
+                    true  => convert_standard_object(line, &timing_points, &velocity_points, &difficulty, version, &mut objects),
+                    false => parse_taiko_object(line, &timing_points, &velocity_points, &difficulty).map(|object| objects.push(object)),
+                };
+
+                if let Err(reason) = result {
+                    report(&mut warnings, idx, "[HitObjects]", line, reason)?;
+                }
             }
 
-            None => {
-                if line.starts_with("osu file format v") {
-                    version_string = Some(line.to_owned());
+            // Files aren't guaranteed to be in order, and sliders split into circles may run past the next object
+            objects.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+            Ok(OsuBeatmap::Taiko(Beatmap {
+                objects  : objects,
+                timing   : timing_points,
+                velocity : velocity_points,
+
+                velocity_multiplier : difficulty.slider_multiplier as f32,
+
+                general    : general,
+                metadata   : metadata,
+                difficulty : difficulty,
+
+                audio : audio,
+
+                sections : sections,
+            }))
+        }
+
+        // Mania
+        "3" => {
+            let key_count = key_count(&difficulty);
+            let mut objects = Vec::<VsrgNote>::with_capacity(object_lines.len());
+            for (idx, line) in object_lines {
+                match parse_mania_object(line, key_count) {
+                    Ok(object)  => objects.push(object),
+                    Err(reason) => report(&mut warnings, idx, "[HitObjects]", line, reason)?,
                 }
             }
+
+            objects.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+            Ok(OsuBeatmap::Mania(Beatmap {
+                objects  : objects,
+                timing   : timing_points,
+                velocity : velocity_points,
+
+                velocity_multiplier : difficulty.slider_multiplier as f32,
+
+                general    : general,
+                metadata   : metadata,
+                difficulty : difficulty,
+
+                audio : audio,
+
+                sections : sections,
+            }))
         }
+
+        mode => Err(ParseError::UnsupportedMode { mode: mode.to_owned() }),
+    };
+}
+
+/// Either returns the error or stores it as a warning, depending on the parsing mode.
+pub fn report(warnings: &mut Option<&mut Vec<ParseError>>, idx: usize, section: &str, text: &str, reason: &str) -> Result<(), ParseError> {
+    let error = ParseError::InvalidLine {
+        line    : idx + 1,
+        section : section.to_owned(),
+        text    : text.to_owned(),
+        reason  : reason.to_owned(),
+    };
+
+    return match warnings {
+        Some(warnings) => { warnings.push(error); Ok(()) }
+        None => Err(error),
+    };
+}
+
+fn lookup<'a>(table: &Table<'a>, section: &'static str, key: &'static str) -> Result<&'a str, ParseError> {
+    let Some(entries) = table.get(section) else { return Err(ParseError::MissingSection { section }) };
+    let Some(entry)   = entries.get(key)   else { return Err(ParseError::MissingKey { section, key }) };
+    return Ok(entry.value);
+}
+
+/// Parses an optional value, `Ok(None)` is returned if the key is missing or its value was skipped.
+fn parse_value<T: FromStr>(table: &Table, warnings: &mut Option<&mut Vec<ParseError>>, section: &'static str, key: &'static str) -> Result<Option<T>, ParseError> {
+    let Some(entry) = table.get(section).and_then(|x| x.get(key)) else { return Ok(None) };
+    return match entry.value.parse::<T>() {
+        Ok(value) => Ok(Some(value)),
+        Err(_) => {
+            report(warnings, entry.idx, section, entry.line, &format!("invalid value for `{key}`"))?;
+            Ok(None)
+        }
+    };
+}
+
+fn parse_string(table: &Table, section: &'static str, key: &'static str) -> String {
+    return table.get(section)
+        .and_then(|x| x.get(key))
+        .map(|x| x.value.to_owned())
+        .unwrap_or_default();
+}
+
+fn parse_general(table: &Table, warnings: &mut Option<&mut Vec<ParseError>>) -> Result<BeatmapGeneral, ParseError> {
+    let audio_lead_in = parse_value::<f64>(table, warnings, "[General]", "AudioLeadIn")?.unwrap_or(0.0);
+    let preview_time  = parse_value::<f64>(table, warnings, "[General]", "PreviewTime")?;
+    let sample_set    = parse_value::<SampleSet>(table, warnings, "[General]", "SampleSet")?.unwrap_or(SampleSet::Normal);
+
+    return Ok(BeatmapGeneral {
+        audio_lead_in : Time::from_ms(audio_lead_in),
+        preview_time  : preview_time.filter(|x| *x >= 0.0).map(Time::from_ms), // -1 stands for "not set"
+        sample_set    : sample_set,
+        converted     : false,
+    });
+}
+
+fn parse_metadata(table: &Table, warnings: &mut Option<&mut Vec<ParseError>>) -> Result<BeatmapMetadata, ParseError> {
+    return Ok(BeatmapMetadata {
+        title          : parse_string(table, "[Metadata]", "Title"),
+        title_unicode  : parse_string(table, "[Metadata]", "TitleUnicode"),
+        artist         : parse_string(table, "[Metadata]", "Artist"),
+        artist_unicode : parse_string(table, "[Metadata]", "ArtistUnicode"),
+        creator        : parse_string(table, "[Metadata]", "Creator"),
+        version        : parse_string(table, "[Metadata]", "Version"),
+        source         : parse_string(table, "[Metadata]", "Source"),
+        tags           : parse_string(table, "[Metadata]", "Tags").split_whitespace().map(str::to_owned).collect(),
+        beatmap_id     : parse_value(table, warnings, "[Metadata]", "BeatmapID")?,
+        beatmap_set_id : parse_value(table, warnings, "[Metadata]", "BeatmapSetID")?,
+    });
+}
+
+fn parse_difficulty(table: &Table, warnings: &mut Option<&mut Vec<ParseError>>) -> Result<BeatmapDifficulty, ParseError> {
+    let default = BeatmapDifficulty::default();
+    let overall_difficulty = parse_value(table, warnings, "[Difficulty]", "OverallDifficulty")?.unwrap_or(default.overall_difficulty);
+
+    return Ok(BeatmapDifficulty {
+        hp_drain_rate      : parse_value(table, warnings, "[Difficulty]", "HPDrainRate"     )?.unwrap_or(default.hp_drain_rate),
+        circle_size        : parse_value(table, warnings, "[Difficulty]", "CircleSize"      )?.unwrap_or(default.circle_size),
+        overall_difficulty : overall_difficulty,
+        approach_rate      : parse_value(table, warnings, "[Difficulty]", "ApproachRate"    )?.unwrap_or(overall_difficulty), // Old beatmaps have no AR
+        slider_multiplier  : parse_value(table, warnings, "[Difficulty]", "SliderMultiplier")?.unwrap_or(default.slider_multiplier),
+        slider_tick_rate   : parse_value(table, warnings, "[Difficulty]", "SliderTickRate"  )?.unwrap_or(default.slider_tick_rate),
+    });
+}
+
+fn parse_key_value(line: &str) -> Result<(&str, &str), &'static str> {
+    let Some((key, value)) = line.split_once(':') else { return Err("expected `key: value` pair") };
+    return Ok((key.trim(), value.trim()));
+}
+
+enum ParsedPoint {
+    Timing(TimingPoint),
+    Velocity(VelocityPoint),
+}
+
+// time,beatLength,meter,sampleSet,sampleIndex,volume,uninherited,effects
+fn parse_timing_point(line: &str) -> Result<ParsedPoint, &'static str> {
+    let mut parts = line.split(',');
+    let Some(time_ms)     = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid time")        };
+    let Some(beat_length) = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid beat length") };
+
+    // Old beatmaps may omit everything after the beat length
+    let meter        = parse_column(parts.next(), 4,   "invalid meter")?;
+    let sample_set   = parse_column(parts.next(), 0,   "invalid sample set")?;
+    let sample_index = parse_column(parts.next(), 0,   "invalid sample index")?;
+    let volume       = parse_column(parts.next(), 100, "invalid volume")?;
+    let uninherited  = parse_column(parts.next(), 1u8, "invalid uninherited flag")?;
+    let effects      = parse_column(parts.next(), 0,   "invalid effects")?;
+
+    if uninherited == 1 {
+        if beat_length <= 0.0 { return Err("beat length of an uninherited point must be positive") }
+
+        let bpm = 60.0 / (beat_length / 1000.0);
+        return Ok(ParsedPoint::Timing(TimingPoint {
+            time : Time::from_ms(time_ms),
+            bpm  : bpm,
+
+            meter        : meter,
+            sample_set   : sample_set,
+            sample_index : sample_index,
+            volume       : volume,
+            effects      : effects,
+        }));
+    } else if uninherited == 0 {
+        if beat_length >= 0.0 { return Err("beat length of an inherited point must be negative") }
+
+        let velocity = -100.0 / beat_length;
+        return Ok(ParsedPoint::Velocity(VelocityPoint {
+            time     : Time::from_ms(time_ms),
+            velocity : velocity,
+
+            meter        : meter,
+            sample_set   : sample_set,
+            sample_index : sample_index,
+            volume       : volume,
+            effects      : effects,
+        }));
+    } else { return Err("invalid uninherited flag") }
+}
+
+/// Parses an optional column, a missing column results in the default value.
+fn parse_column<T: FromStr>(part: Option<&str>, default: T, reason: &'static str) -> Result<T, &'static str> {
+    let Some(part) = part.map(str::trim) else { return Ok(default) };
+    return part.parse::<T>().map_err(|_| reason);
+}
+
+// x,y,time,type,hitSound,objectParams,hitSample
+fn parse_taiko_object(line: &str, timing: &[TimingPoint], velocity: &[VelocityPoint], difficulty: &BeatmapDifficulty) -> Result<TaikoCircle, &'static str> {
+    let mut parts = line.split(',');
+    let Some(time_ms)     = parts.nth(2).and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid time")        };
+    let Some(object_type) = parts.next().and_then(|x| x.trim().parse::<u8> ().ok()) else { return Err("invalid object type") };
+    let Some(hit_sound)   = parts.next().and_then(|x| x.trim().parse::<u8> ().ok()) else { return Err("invalid hit sound")   };
+
+    let time = Time::from_ms(time_ms);
+    let kind = if object_type & 0b0000_0010 != 0 {
+        // ...,curveType|curvePoints,slides,length,...
+        let Some(slides) = parts.nth(1).and_then(|x| x.trim().parse::<u32>().ok()) else { return Err("invalid slide count")    };
+        let Some(length) = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid slider length") };
+        let Some(timing_point) = timing_at(timing, time) else { return Err("drumroll is not covered by any timing point") };
+
+        // ...,edgeSounds,edgeSets,hitSample
+        parts.nth(1);
+
+        let beat_length = timing_point.beat_length();
+        let velocity = velocity_at(timing, velocity, time);
+        let duration_ms = length * slides as f64 / (difficulty.slider_multiplier * 100.0 * velocity) * beat_length;
+        drumroll(duration_ms, beat_length, difficulty)
+    } else if object_type & 0b0000_1000 != 0 {
+        // ...,endTime,...
+        let Some(end_time_ms) = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid swell end time") };
+        if end_time_ms < time_ms { return Err("swell ends before it starts") }
+        swell(end_time_ms - time_ms, difficulty)
+    } else { TaikoKind::Circle };
+
+    return Ok(TaikoCircle {
+        time  : time,
+        big   : hit_sound & hitsound::FINISH != 0,
+        color : if hit_sound & (hitsound::WHISTLE | hitsound::CLAP) != 0 { TaikoColor::KAT } else { TaikoColor::DON },
+        kind  : kind,
+
+        hit_sound : hit_sound,
+        sample    : parse_hit_sample(parts.next())?,
+        combo     : object_type & COMBO_FLAGS,
+    });
+}
+
+/// Drumroll with the tick count osu! would give it
+pub fn drumroll(duration_ms: f64, beat_length: f64, difficulty: &BeatmapDifficulty) -> TaikoKind {
+    // Ticks are placed every 1/4 beat, or 1/3 beat for triplet based maps
+    let tick_rate = if difficulty.slider_tick_rate == 3.0 { 3.0 } else { 4.0 };
+    let tick_spacing = beat_length / tick_rate;
+
+    return TaikoKind::Drumroll {
+        duration : Time::from_ms(duration_ms),
+        ticks    : (duration_ms / tick_spacing + 0.5).ceil().max(1.0) as u32,
+    };
+}
+
+/// Swell with the hit count osu! would require, based on overall difficulty
+pub fn swell(duration_ms: f64, difficulty: &BeatmapDifficulty) -> TaikoKind {
+    let hit_multiplier = difficulty_range(difficulty.overall_difficulty as f64, 3.0, 5.0, 7.5) * 1.65;
+    return TaikoKind::Swell {
+        duration : Time::from_ms(duration_ms),
+        hits     : (duration_ms / 1000.0 * hit_multiplier).max(1.0) as u32,
+    };
+}
+
+/// Converts scroll faster than taiko beatmaps, osu! scales both the slider length and the slider multiplier by this
+pub const LEGACY_VELOCITY_MULTIPLIER: f64 = 1.4;
+
+/// Slider multiplier as the file states it, converts keep theirs multiplied by [`LEGACY_VELOCITY_MULTIPLIER`]
+pub fn file_slider_multiplier(general: &BeatmapGeneral, difficulty: &BeatmapDifficulty) -> f64 {
+    return if general.converted { difficulty.slider_multiplier / LEGACY_VELOCITY_MULTIPLIER } else { difficulty.slider_multiplier };
+}
+
+/// Same algorithm as osu!, expects `difficulty` to be already adjusted for converts.
+/// Sliders become drumrolls, unless they are short enough to be split into circles.
+// x,y,time,type,hitSound,objectParams,hitSample
+fn convert_standard_object(line: &str, timing: &[TimingPoint], velocity: &[VelocityPoint], difficulty: &BeatmapDifficulty, version: u32, out: &mut Vec<TaikoCircle>) -> Result<(), &'static str> {
+    let mut parts = line.split(',');
+    let Some(time_ms)     = parts.nth(2).and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid time")        };
+    let Some(object_type) = parts.next().and_then(|x| x.trim().parse::<u8> ().ok()) else { return Err("invalid object type") };
+    let Some(hit_sound)   = parts.next().and_then(|x| x.trim().parse::<u8> ().ok()) else { return Err("invalid hit sound")   };
+
+    let circle = |time_ms: f64, hit_sound: u8, sample: &HitSample| TaikoCircle {
+        time  : Time::from_ms(time_ms),
+        big   : hit_sound & hitsound::FINISH != 0,
+        color : if hit_sound & (hitsound::WHISTLE | hitsound::CLAP) != 0 { TaikoColor::KAT } else { TaikoColor::DON },
+        kind  : TaikoKind::Circle,
+
+        hit_sound : hit_sound,
+        sample    : sample.clone(),
+        combo     : 0,
+    };
+
+    let time = Time::from_ms(time_ms);
+    if object_type & 0b0000_0010 != 0 {
+        // ...,curveType|curvePoints,slides,length,edgeSounds,...
+        let Some(slides) = parts.nth(1).and_then(|x| x.trim().parse::<u32>().ok()) else { return Err("invalid slide count")    };
+        let Some(length) = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid slider length") };
+        let Some(timing_point) = timing_at(timing, time) else { return Err("slider is not covered by any timing point") };
+
+        // Every slider node has its own hit sound and sample sets, falling back to the ones of the whole object
+        let edge_sounds = match parts.next() {
+            Some(part) => part.split('|').map(|x| x.trim().parse::<u8>()).collect::<Result<Vec<_>, _>>().map_err(|_| "invalid edge sounds")?,
+            None => vec![],
+        };
+        let edge_sounds = if edge_sounds.is_empty() { vec![hit_sound] } else { edge_sounds };
+        let edge_sets = parse_edge_sets(parts.next())?;
+        let sample = parse_hit_sample(parts.next())?;
+
+        let slides = slides.max(1);
+        let distance = length * slides as f64 * LEGACY_VELOCITY_MULTIPLIER;
+
+        let mut beat_length = timing_point.beat_length() / velocity_at(timing, velocity, time);
+        let taiko_velocity = difficulty.slider_multiplier * 100.0;
+        let taiko_duration = (distance / taiko_velocity * beat_length).trunc();
+        let osu_velocity = taiko_velocity * (1000.0 / beat_length);
+
+        // osu!stable only uses the slider velocity adjusted beat length below for old beatmaps
+        if version >= 8 { beat_length = timing_point.beat_length(); }
+
+        // Assume the circles are spaced evenly within a beat, like slider ticks
+        let tick_spacing = (beat_length / difficulty.slider_tick_rate).min(taiko_duration / slides as f64);
+        if tick_spacing > 0.0 && distance / osu_velocity * 1000.0 < 2.0 * beat_length {
+            let end_ms = time_ms + taiko_duration + tick_spacing / 8.0;
+            let mut tick_ms = time_ms;
+            let mut node = 0;
+            while tick_ms <= end_ms {
+                let (normal_set, addition_set) = edge_sets.get(node).copied().unwrap_or_default();
+                let node_sample = HitSample {
+                    normal_set   : if normal_set   != SampleSet::Auto { normal_set }   else { sample.normal_set },
+                    addition_set : if addition_set != SampleSet::Auto { addition_set } else { sample.addition_set },
+                    ..sample.clone()
+                };
+
+                out.push(circle(tick_ms, edge_sounds[node], &node_sample));
+                node = (node + 1) % edge_sounds.len();
+                tick_ms += tick_spacing;
+            }
+
+            return Ok(());
+        }
+
+        out.push(TaikoCircle {
+            kind : drumroll(taiko_duration, timing_point.beat_length(), difficulty),
+            ..circle(time_ms, hit_sound, &sample)
+        });
+    } else if object_type & 0b0000_1000 != 0 {
+        // ...,endTime,...
+        let Some(end_time_ms) = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid spinner end time") };
+        if end_time_ms < time_ms { return Err("spinner ends before it starts") }
+
+        // Swells are never big
+        let hit_sound = hit_sound & !hitsound::FINISH;
+        out.push(TaikoCircle {
+            kind : swell(end_time_ms - time_ms, difficulty),
+            ..circle(time_ms, hit_sound, &parse_hit_sample(parts.next())?)
+        });
+    } else {
+        out.push(circle(time_ms, hit_sound, &parse_hit_sample(parts.next())?));
     }
 
-    return match table["[General]"]["Mode"] {
-        // Taiko
-        "1" => Ok(Beatmap {
-            objects  : objects_taiko,
-            timing   : timing_points,
-            velocity : velocity_points,
+    return Ok(());
+}
 
-            velocity_multiplier : 1.0, //table["[Difficulty]"]["SliderMultiplier"].parse().unwrap(),
-            
-            audio : PathBuf::from(table["[General]"]["AudioFilename"]),
-        }),
+/// Parses the optional `normalSet:additionSet:index:volume:filename` column, trailing values may be omitted.
+fn parse_hit_sample(part: Option<&str>) -> Result<HitSample, &'static str> {
+    let Some(part) = part.map(str::trim).filter(|x| !x.is_empty()) else { return Ok(HitSample::default()) };
+    let mut values = part.splitn(5, ':');
 
-        // Mania
-        // "3" => Ok(OsuBeatmap::ManiaBeatmap(Beatmap::<VsrgNote> {
-        //     objects : objects_mania,
-        //     timing  : timing_points,
-        // })),
+    let normal_set   = parse_column(values.next(), 0u8, "invalid normal sample set")?;
+    let addition_set = parse_column(values.next(), 0u8, "invalid addition sample set")?;
+    return Ok(HitSample {
+        normal_set   : SampleSet::from_index(normal_set)  .ok_or("invalid normal sample set")?,
+        addition_set : SampleSet::from_index(addition_set).ok_or("invalid addition sample set")?,
+        index        : parse_column(values.next(), 0, "invalid sample index")?,
+        volume       : parse_column(values.next(), 0, "invalid sample volume")?,
+        filename     : values.next().unwrap_or_default().to_owned(),
+    });
+}
+
+/// Parses the optional `normalSet:additionSet|...` column of sliders
+fn parse_edge_sets(part: Option<&str>) -> Result<Vec<(SampleSet, SampleSet)>, &'static str> {
+    let Some(part) = part.map(str::trim).filter(|x| !x.is_empty()) else { return Ok(vec![]) };
+    return part.split('|').map(|x| {
+        let (normal_set, addition_set) = x.split_once(':').unwrap_or((x, "0"));
+        let normal_set   = normal_set  .trim().parse::<u8>().ok().and_then(SampleSet::from_index);
+        let addition_set = addition_set.trim().parse::<u8>().ok().and_then(SampleSet::from_index);
+        normal_set.zip(addition_set).ok_or("invalid edge sets")
+    }).collect();
+}
+
+/// Circle size is the key count in mania
+fn key_count(difficulty: &BeatmapDifficulty) -> usize {
+    return (difficulty.circle_size.round() as usize).clamp(1, 18);
+}
+
+// x,y,time,type,hitSound,endTime:hitSample
+fn parse_mania_object(line: &str, key_count: usize) -> Result<VsrgNote, &'static str> {
+    let mut parts = line.split(',');
+    let Some(x_position)  = parts.next().and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid x position")  };
+    let Some(time_ms)     = parts.nth(1).and_then(|x| x.trim().parse::<f64>().ok()) else { return Err("invalid time")        };
+    let Some(object_type) = parts.next().and_then(|x| x.trim().parse::<u8> ().ok()) else { return Err("invalid object type") };
+
+    let duration = if object_type & 0b1000_0000 != 0 { // is a hold note
+        let Some(end_time_ms) = parts.nth(1).and_then(|x| {
+            let mut parts = x.split(':');
+            parts.next().and_then(|x| x.trim().parse::<f64>().ok())
+        }) else { return Err("invalid hold note end time") };
+
+        if end_time_ms < time_ms { return Err("hold note ends before it starts") }
+        Time::from_ms(end_time_ms - time_ms)
+    } else { Time::zero() };
+
+    let key = (x_position * key_count as f64 / 512.0).floor().max(0.0) as usize;
+    return Ok(VsrgNote {
+        time     : Time::from_ms(time_ms),
+        key      : key.min(key_count - 1),
+        duration : duration,
+    });
+}
+
+/// Maps a difficulty value from 0..10 onto min..max, with 5 mapping to mid
+pub(crate) fn difficulty_range(difficulty: f64, min: f64, mid: f64, max: f64) -> f64 {
+    if difficulty > 5.0 { return mid + (max - mid) * (difficulty - 5.0) / 5.0; }
+    if difficulty < 5.0 { return mid - (mid - min) * (5.0 - difficulty) / 5.0; }
+    return mid;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "osu file format v14\n\n[General]\nAudioFilename: audio.mp3\nMode: 1\n\n[Difficulty]\nOverallDifficulty:5\n\n";
+
+    fn beatmap(timing: &str, objects: &str) -> String {
+        return format!("{HEADER}[TimingPoints]\n{timing}\n\n[HitObjects]\n{objects}\n");
+    }
+
+    #[test]
+    fn invalid_line_has_position() {
+        let data = beatmap("0,500,4,1,0,100,1,0", "256,192,0,1,0,0:0:0:0:\n256,192,abc,1,0,0:0:0:0:");
+        let Err(ParseError::InvalidLine { line, section, text, reason }) = try_parse(&data) else { panic!("expected an invalid line") };
 
-        _ => Err(())
+        assert_eq!(line, 15);
+        assert_eq!(section, "[HitObjects]");
+        assert_eq!(text, "256,192,abc,1,0,0:0:0:0:");
+        assert_eq!(reason, "invalid time");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn invalid_timing_point() {
+        let data = beatmap("0,500,4,1,0,100,1,0\n1000,-50,4,1,0,100,1,0", "");
+        let Err(ParseError::InvalidLine { line, section, reason, .. }) = try_parse(&data) else { panic!("expected an invalid line") };
+
+        assert_eq!((line, section.as_str()), (12, "[TimingPoints]"));
+        assert_eq!(reason, "beat length of an uninherited point must be positive");
+    }
+
+    #[test]
+    fn invalid_value() {
+        let data = HEADER.replace("OverallDifficulty:5", "OverallDifficulty:hard");
+        let Err(ParseError::InvalidLine { line, section, reason, .. }) = try_parse(&data) else { panic!("expected an invalid line") };
+
+        assert_eq!((line, section.as_str()), (8, "[Difficulty]"));
+        assert_eq!(reason, "invalid value for `OverallDifficulty`");
+    }
+
+    #[test]
+    fn missing_section() {
+        let data = "osu file format v14\n\n[Difficulty]\nOverallDifficulty:5\n";
+        assert_eq!(try_parse(data).err(), Some(ParseError::MissingSection { section: "[General]" }));
+    }
+
+    #[test]
+    fn missing_key() {
+        let data = "osu file format v14\n\n[General]\nMode: 1\n";
+        assert_eq!(try_parse(data).err(), Some(ParseError::MissingKey { section: "[General]", key: "AudioFilename" }));
+    }
+
+    #[test]
+    fn unsupported_mode() {
+        let data = HEADER.replace("Mode: 1", "Mode: 2");
+        assert_eq!(try_parse(&data).err(), Some(ParseError::UnsupportedMode { mode: String::from("2") }));
+
+        // Mania is supported, just not by the taiko parser
+        let data = HEADER.replace("Mode: 1", "Mode: 3");
+        assert_eq!(try_parse(&data).err(), Some(ParseError::UnsupportedMode { mode: String::from("3") }));
+        assert!(matches!(try_parse_osu(&data), Ok(OsuBeatmap::Mania(_))));
+    }
+
+    #[test]
+    fn lenient_skips_bad_lines() {
+        let data = beatmap(
+            "0,500,4,1,0,100,1,0\nnot a timing point\n1000,-50,4,1,0,100,0,0",
+            "256,192,0,1,0,0:0:0:0:\n256,192,250,1,x,0:0:0:0:\n256,192,500,1,2,0:0:0:0:",
+        ).replace("OverallDifficulty:5", "OverallDifficulty:hard");
+
+        assert!(try_parse(&data).is_err());
+        let (beatmap, warnings) = try_parse_lenient(&data).unwrap();
+
+        assert_eq!(beatmap.timing.len(), 1);
+        assert_eq!(beatmap.velocity.len(), 1);
+        assert_eq!(beatmap.objects.len(), 2);
+        assert_eq!(beatmap.objects[1].color, TaikoColor::KAT);
+
+        // Skipped values fall back to their default
+        assert_eq!(beatmap.difficulty.overall_difficulty, 5.0);
+
+        // Objects are parsed last, after the sections they depend on
+        let lines = warnings.iter().map(|x| match x {
+            ParseError::InvalidLine { line, reason, .. } => (*line, reason.as_str()),
+            error => panic!("unexpected warning {error}"),
+        }).collect::<Vec<_>>();
+
+        assert_eq!(lines, [
+            (12, "invalid time"),
+            (8,  "invalid value for `OverallDifficulty`"),
+            (17, "invalid hit sound"),
+        ]);
+    }
+
+    #[test]
+    fn objects_are_sorted() {
+        let data = beatmap("0,500,4,1,0,100,1,0", "256,192,500,1,0,0:0:0:0:\n256,192,0,1,2,0:0:0:0:\n256,192,250,1,0,0:0:0:0:\n256,192,250,1,8,0:0:0:0:");
+        let beatmap = try_parse(&data).unwrap();
+        let objects = beatmap.objects.iter().map(|x| (x.time.to_seconds() * 1000.0, x.hit_sound)).collect::<Vec<_>>();
+
+        // Objects at the same time keep their order in the file
+        assert_eq!(objects, [(0.0, 2), (250.0, 0), (250.0, 8), (500.0, 0)]);
+
+        let data = data.replace("Mode: 1", "Mode: 3").replace("OverallDifficulty:5", "CircleSize:4");
+        let Ok(OsuBeatmap::Mania(beatmap)) = try_parse_osu(&data) else { panic!("expected a mania beatmap") };
+        assert!(beatmap.objects.windows(2).all(|x| x[0].time <= x[1].time));
+    }
+
+    #[test]
+    fn lenient_still_fails_on_missing_data() {
+        let data = "osu file format v14\n\n[Difficulty]\nOverallDifficulty:5\n";
+        assert_eq!(try_parse_lenient(data).err(), Some(ParseError::MissingSection { section: "[General]" }));
+    }
+
+    #[test]
+    fn report_depends_on_mode() {
+        let mut strict = None;
+        assert!(report(&mut strict, 4, "[General]", "Mode", "expected `key: value` pair").is_err());
+
+        let mut warnings = vec![];
+        let mut lenient = Some(&mut warnings);
+        assert!(report(&mut lenient, 4, "[General]", "Mode", "expected `key: value` pair").is_ok());
+        assert_eq!(warnings, [ParseError::InvalidLine {
+            line    : 5,
+            section : String::from("[General]"),
+            text    : String::from("Mode"),
+            reason  : String::from("expected `key: value` pair"),
+        }]);
+    }
+}
@@ -0,0 +1,222 @@
+use super::{parser::{Beatmap, difficulty_range}, difficulty::{self, DifficultyAttributes}};
+
+/// Accuracies listed by [`table`]
+pub const TABLE_ACCURACIES: [f64; 4] = [0.95, 0.98, 0.99, 1.0];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Mods {
+    pub easy        : bool,
+    pub half_time   : bool,
+    pub hidden      : bool,
+    pub hard_rock   : bool,
+    pub double_time : bool,
+    pub flashlight  : bool,
+}
+
+impl Mods {
+    pub fn clock_rate(&self) -> f64 {
+        if self.double_time { return 1.5 }
+        if self.half_time { return 0.75 }
+        return 1.0;
+    }
+
+    pub fn overall_difficulty(&self, value: f32) -> f64 {
+        let value = value as f64;
+        if self.hard_rock { return (value * 1.4).min(10.0) }
+        if self.easy { return value * 0.5 }
+        return value;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Score {
+    /// Between 0 and 1, rounded to the closest possible amount of goods
+    pub accuracy : f64,
+    pub misses   : usize,
+
+    /// Highest combo, `None` for a full combo apart from misses
+    pub combo    : Option<usize>,
+    pub mods     : Mods,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PerformanceAttributes {
+    pub total      : f64,
+    pub difficulty : f64,
+    pub accuracy   : f64,
+
+    /// Hit counts the score was turned into
+    pub greats     : usize,
+    pub goods      : usize,
+    pub misses     : usize,
+}
+
+/// Half of the window for a great hit in ms, before rate changes
+pub fn great_hit_window(overall_difficulty: f64) -> f64 {
+    return difficulty_range(overall_difficulty, 50.0, 35.0, 20.0);
+}
+
+/// Expects `attributes` to be calculated with the clock rate of the score's mods
+pub fn calculate(attributes: &DifficultyAttributes, overall_difficulty: f32, score: &Score) -> PerformanceAttributes {
+    let total_hits = attributes.max_combo;
+
+    // Breaking combo without a miss isn't possible in taiko
+    let mut misses = score.misses.min(total_hits);
+    if let Some(combo) = score.combo && combo < total_hits && misses == 0 {
+        misses = 1;
+    }
+
+    // Accuracy is made of greats and goods, goods being worth half
+    let hits = total_hits - misses;
+    let goods = (2.0 * (hits as f64 - score.accuracy * total_hits as f64)).round().clamp(0.0, hits as f64) as usize;
+    let greats = hits - goods;
+    let accuracy = if total_hits > 0 { (greats as f64 + goods as f64 * 0.5) / total_hits as f64 } else { 0.0 };
+
+    // Misses weigh more on shorter maps
+    let effective_misses = if hits > 0 { (1000.0 / hits as f64).max(1.0) * misses as f64 } else { 0.0 };
+
+    let mods = score.mods;
+    let great_hit_window = great_hit_window(mods.overall_difficulty(overall_difficulty)) / mods.clock_rate();
+
+    // Difficulty
+    let mut difficulty_value = (5.0 * (attributes.star_rating / 0.115).max(1.0) - 4.0).powf(2.25) / 1150.0;
+    let length_bonus = 1.0 + 0.1 * (total_hits as f64 / 1500.0).min(1.0);
+    difficulty_value *= length_bonus;
+    difficulty_value *= 0.986_f64.powf(effective_misses);
+
+    if mods.easy { difficulty_value *= 0.985; }
+    if mods.hidden { difficulty_value *= 1.025; }
+    if mods.hard_rock { difficulty_value *= 1.05; }
+    if mods.flashlight { difficulty_value *= 1.05 * length_bonus; }
+    difficulty_value *= accuracy.powi(2);
+
+    // Accuracy
+    let mut accuracy_value = 0.0;
+    if great_hit_window > 0.0 {
+        let length_bonus = (total_hits as f64 / 1500.0).powf(0.3).min(1.15);
+        accuracy_value = (60.0 / great_hit_window).powf(1.1) * accuracy.powi(8) * attributes.star_rating.powf(0.4) * 27.0 * length_bonus;
+
+        if mods.hidden && mods.flashlight {
+            accuracy_value *= (1.075 * length_bonus).max(1.05);
+        }
+    }
+
+    let mut multiplier = 1.13;
+    if mods.hidden { multiplier *= 1.075; }
+    if mods.easy { multiplier *= 0.975; }
+
+    let total = (difficulty_value.powf(1.1) + accuracy_value.powf(1.1)).powf(1.0 / 1.1) * multiplier;
+    return PerformanceAttributes {
+        total      : total,
+        difficulty : difficulty_value,
+        accuracy   : accuracy_value,
+        greats     : greats,
+        goods      : goods,
+        misses     : misses,
+    };
+}
+
+/// Performance for every accuracy in [`TABLE_ACCURACIES`]
+pub fn table(beatmap: &Beatmap, mods: Mods, misses: usize) -> Vec<(f64, PerformanceAttributes)> {
+    let attributes = difficulty::calculate_with_rate(beatmap, mods.clock_rate());
+    return TABLE_ACCURACIES.iter().map(|&accuracy| {
+        let score = Score { accuracy, misses, combo: None, mods };
+        return (accuracy, calculate(&attributes, beatmap.difficulty.overall_difficulty, &score));
+    }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::parser;
+
+    const ATTRIBUTES: DifficultyAttributes = DifficultyAttributes {
+        star_rating : 5.0,
+        colour      : 0.0,
+        rhythm      : 0.0,
+        stamina     : 0.0,
+        peak        : 0.0,
+        max_combo   : 1000,
+    };
+
+    fn score(accuracy: f64, misses: usize) -> Score {
+        return Score { accuracy, misses, combo: None, mods: Mods::default() };
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-6, "{value} != {expected}");
+    }
+
+    /// Expected values are worked out by hand from the formula of osu!lazer's taiko performance calculator
+    #[test]
+    fn matches_formula() {
+        let result = calculate(&ATTRIBUTES, 5.0, &score(1.0, 0));
+        assert_close(result.difficulty, 161.427863);
+        assert_close(result.accuracy, 82.340958);
+        assert_close(result.total, 260.018829);
+    }
+
+    #[test]
+    fn splits_accuracy_into_greats_and_goods() {
+        let result = calculate(&ATTRIBUTES, 5.0, &score(0.977, 3));
+        assert_eq!((result.greats, result.goods, result.misses), (957, 40, 3));
+        assert_close(result.total, 230.795644);
+
+        // Accuracies which can't be reached with the given misses are clamped
+        let result = calculate(&ATTRIBUTES, 5.0, &score(1.0, 3));
+        assert_eq!((result.greats, result.goods), (997, 0));
+
+        let result = calculate(&ATTRIBUTES, 5.0, &score(0.0, 5000));
+        assert_eq!((result.greats, result.goods, result.misses), (0, 0, 1000));
+    }
+
+    #[test]
+    fn broken_combo_is_a_miss() {
+        let broken = Score { combo: Some(500), ..score(1.0, 0) };
+        assert_eq!(calculate(&ATTRIBUTES, 5.0, &broken).misses, 1);
+
+        let full = Score { combo: Some(1000), ..score(1.0, 0) };
+        assert_eq!(calculate(&ATTRIBUTES, 5.0, &full).misses, 0);
+
+        let missed = Score { combo: Some(500), ..score(1.0, 2) };
+        assert_eq!(calculate(&ATTRIBUTES, 5.0, &missed).misses, 2);
+    }
+
+    #[test]
+    fn rate_changes_hit_window() {
+        assert_eq!(great_hit_window(5.0), 35.0);
+        assert_eq!(great_hit_window(10.0), 20.0);
+        assert_eq!(great_hit_window(0.0), 50.0);
+
+        // A 35ms window at 1.5x is a 23.3ms one in real time
+        let double_time = Score { mods: Mods { double_time: true, ..Default::default() }, ..score(1.0, 0) };
+        let result = calculate(&ATTRIBUTES, 5.0, &double_time);
+        assert_close(result.accuracy, 128.622308);
+        assert_close(result.total, 307.938081);
+
+        let half_time = Score { mods: Mods { half_time: true, hard_rock: true, ..Default::default() }, ..score(1.0, 0) };
+        assert_eq!(half_time.mods.overall_difficulty(5.0), 7.0);
+        assert!(calculate(&ATTRIBUTES, 5.0, &half_time).accuracy < 82.340958);
+    }
+
+    #[test]
+    fn table_lists_every_accuracy() {
+        let beatmap = parser::try_parse(include_str!("../../tests/fixtures/stream.osu")).unwrap();
+        let rows = table(&beatmap, Mods::default(), 2);
+        let attributes = difficulty::calculate(&beatmap);
+
+        assert_eq!(rows.iter().map(|x| x.0).collect::<Vec<_>>(), TABLE_ACCURACIES);
+        for (accuracy, row) in &rows {
+            let expected = calculate(&attributes, beatmap.difficulty.overall_difficulty, &score(*accuracy, 2));
+            assert_eq!((row.greats, row.goods, row.misses), (expected.greats, expected.goods, 2));
+            assert_close(row.total, expected.total);
+        }
+
+        assert!(rows.windows(2).all(|x| x[0].1.total < x[1].1.total));
+        assert_eq!(rows[3].1.goods, 0);
+
+        // Rate changes go into the star rating as well
+        let double_time = table(&beatmap, Mods { double_time: true, ..Default::default() }, 0);
+        assert!(double_time[3].1.difficulty > table(&beatmap, Mods::default(), 0)[3].1.difficulty);
+    }
+}
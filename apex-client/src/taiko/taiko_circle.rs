@@ -1,11 +1,35 @@
 use wcore::time::Time;
 
-#[derive(Clone, Default)]
+use super::hitsound::HitSample;
+
+#[derive(Clone, PartialEq, Default, Debug)]
 pub struct TaikoCircle {
     pub time  : Time,
-    
+
     pub big   : bool,
     pub color : TaikoColor,
+    pub kind  : TaikoKind,
+
+    /// Flags from [`super::hitsound`], `big` and `color` are derived from them when parsing
+    pub hit_sound : u8,
+    pub sample    : HitSample,
+
+    /// [`super::parser::COMBO_FLAGS`] of the object type, unused by taiko but kept for writing
+    pub combo     : u8,
+}
+
+impl TaikoCircle {
+    pub fn duration(&self) -> Time {
+        return match self.kind {
+            TaikoKind::Circle => Time::zero(),
+            TaikoKind::Drumroll { duration, .. } => duration,
+            TaikoKind::Swell    { duration, .. } => duration,
+        };
+    }
+
+    pub fn end_time(&self) -> Time {
+        return self.time + self.duration();
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
@@ -22,4 +46,16 @@ impl TaikoColor {
             TaikoColor::DON => *self = TaikoColor::KAT,
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum TaikoKind {
+    #[default]
+    Circle,
+
+    /// Slider in the .osu format, `ticks` includes both ends
+    Drumroll { duration: Time, ticks: u32 },
+
+    /// Spinner in the .osu format, `hits` is the amount of hits required to clear it
+    Swell { duration: Time, hits: u32 },
+}
@@ -0,0 +1,561 @@
+use std::path::PathBuf;
+
+use wcore::time::Time;
+
+use super::{parser::{self, Beatmap, BeatmapGeneral, BeatmapMetadata, BeatmapDifficulty, TimingPoint, VelocityPoint, ParseError, KIAI}, taiko_circle::{TaikoCircle, TaikoColor, TaikoKind}, hitsound::{self, HitSample, SampleSet}};
+
+/// Parses every course of a TJA chart in file order, failing on the first line which could not be parsed.
+pub fn try_parse(data: &str) -> Result<Vec<Beatmap>, ParseError> {
+    return parse(data, None);
+}
+
+/// Parses every course of a TJA chart in file order, skipping lines which could not be parsed.
+pub fn try_parse_lenient(data: &str) -> Result<(Vec<Beatmap>, Vec<ParseError>), ParseError> {
+    let mut warnings = Vec::new();
+    let courses = parse(data, Some(&mut warnings))?;
+    return Ok((courses, warnings));
+}
+
+/// Charts are either UTF-8 or Shift-JIS, which is what most simulators expect
+pub fn decode(data: &[u8]) -> String {
+    if let Ok(text) = std::str::from_utf8(data) {
+        return text.trim_start_matches('\u{feff}').to_owned();
+    }
+
+    let (text, _, _) = encoding_rs::SHIFT_JIS.decode(data);
+    return text.into_owned();
+}
+
+/// Header values, course specific ones are overwritten by each `COURSE:` block
+struct Header {
+    title     : String,
+    subtitle  : String,
+    maker     : String,
+    wave      : Option<String>,
+    bpm       : f64,
+    offset    : f64, // s
+    demostart : Option<f64>, // s
+    course    : String,
+    level     : Option<u32>,
+    balloons  : Vec<u32>,
+}
+
+fn parse(data: &str, mut warnings: Option<&mut Vec<ParseError>>) -> Result<Vec<Beatmap>, ParseError> {
+    let mut header = Header {
+        title     : String::new(),
+        subtitle  : String::new(),
+        maker     : String::new(),
+        wave      : None,
+        bpm       : 120.0,
+        offset    : 0.0,
+        demostart : None,
+        course    : String::from("Oni"),
+        level     : None,
+        balloons  : vec![],
+    };
+
+    let mut courses = Vec::<Beatmap>::new();
+    let mut chart: Option<Chart> = None;
+    for (idx, line) in data.lines().enumerate() {
+        let line = if idx == 0 { line.trim_start_matches('\u{feff}') } else { line };
+        let line = line.split_once("//").map(|(line, _)| line).unwrap_or(line).trim();
+        if line.is_empty() { continue }
+
+        // Chart body
+        if line.starts_with("#END") && let Some(current) = chart.take() {
+            courses.push(current.finish(&header));
+            header.level = None;
+            header.balloons.clear();
+            continue;
+        }
+
+        if let Some(current) = &mut chart {
+            let result = match line.strip_prefix('#') {
+                Some(command) => { current.push_command(command); Ok(()) }
+                // Invalid notes are skipped, the rest of the line still counts
+                None => line.chars().map(|x| current.push_char(x)).fold(Ok(()), Result::and),
+            };
+
+            if let Err(reason) = result {
+                report(&mut warnings, &header, idx, line, reason)?;
+            }
+
+            continue;
+        }
+
+        if line.starts_with("#START") {
+            chart = Some(Chart::new(&header));
+            continue;
+        }
+
+        // Header
+        let Some((key, value)) = line.split_once(':') else {
+            report(&mut warnings, &header, idx, line, "expected a `key:value` pair")?;
+            continue;
+        };
+
+        let value = value.trim();
+        let result = match key.trim() {
+            "TITLE"     => { header.title = value.to_owned(); Ok(()) }
+            "SUBTITLE"  => { header.subtitle = value.trim_start_matches("--").trim_start_matches("++").to_owned(); Ok(()) }
+            "MAKER"     => { header.maker = value.to_owned(); Ok(()) }
+            "WAVE"      => { header.wave = Some(value.to_owned()); Ok(()) }
+            "BPM"       => value.parse::<f64>().ok().filter(|x| *x > 0.0).map(|x| header.bpm = x).ok_or("invalid BPM"),
+            "OFFSET"    => value.parse::<f64>().map(|x| header.offset = x).map_err(|_| "invalid offset"),
+            "DEMOSTART" => value.parse::<f64>().map(|x| header.demostart = Some(x)).map_err(|_| "invalid demo start"),
+            "COURSE"    => { header.course = course_name(value); Ok(()) }
+            "LEVEL"     => value.parse::<u32>().map(|x| header.level = Some(x)).map_err(|_| "invalid level"),
+            "BALLOON"   => value.split(',')
+                .filter(|x| !x.trim().is_empty())
+                .map(|x| x.trim().parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map(|x| header.balloons = x)
+                .map_err(|_| "invalid balloon hit counts"),
+
+            // Scoring and display related keys are not used
+            _ => Ok(()),
+        };
+
+        if let Err(reason) = result {
+            report(&mut warnings, &header, idx, line, reason)?;
+        }
+    }
+
+    if chart.is_some() {
+        let error = ParseError::MissingKey { section: "#START", key: "#END" };
+        match &mut warnings {
+            Some(warnings) => warnings.push(error),
+            None => return Err(error),
+        }
+
+        if let Some(current) = chart.take() {
+            courses.push(current.finish(&header));
+        }
+    }
+
+    if courses.is_empty() { return Err(ParseError::MissingSection { section: "#START" }) }
+    if header.wave.is_none() { return Err(ParseError::MissingKey { section: "(header)", key: "WAVE" }) }
+
+    return Ok(courses);
+}
+
+fn report(warnings: &mut Option<&mut Vec<ParseError>>, header: &Header, idx: usize, text: &str, reason: &str) -> Result<(), ParseError> {
+    return parser::report(warnings, idx, &format!("COURSE:{}", header.course), text, reason);
+}
+
+/// Course names can also be given as numbers
+fn course_name(value: &str) -> String {
+    return match value.to_ascii_lowercase().as_str() {
+        "0" | "easy"          => String::from("Easy"),
+        "1" | "normal"        => String::from("Normal"),
+        "2" | "hard"          => String::from("Hard"),
+        "3" | "oni"           => String::from("Oni"),
+        "4" | "edit" | "ura"  => String::from("Edit"),
+        _ => value.to_owned(),
+    };
+}
+
+enum Event<'a> {
+    Note(u8),
+    Command(&'a str),
+}
+
+/// Chart state between `#START` and `#END`
+struct Chart<'a> {
+    time_ms  : f64,
+    bpm      : f64,
+    scroll   : f64,
+    measure  : (f64, f64),
+    effects  : u8,
+
+    /// Events of the current measure, notes are spread evenly across it once it ends
+    events   : Vec<Event<'a>>,
+
+    /// Start time and kind of the drumroll or balloon which is waiting for its end
+    roll     : Option<(f64, u8)>,
+
+    /// Only the master branch is imported
+    skip_branch : bool,
+
+    timing   : Vec<TimingPoint>,
+    velocity : Vec<VelocityPoint>,
+    objects  : Vec<TaikoCircle>,
+}
+
+impl<'a> Chart<'a> {
+    fn new(header: &Header) -> Self {
+        let mut chart = Self {
+            time_ms  : -header.offset * 1000.0,
+            bpm      : header.bpm,
+            scroll   : 1.0,
+            measure  : (4.0, 4.0),
+            effects  : 0,
+
+            events   : vec![],
+
+            roll     : None,
+
+            skip_branch : false,
+
+            timing   : vec![],
+            velocity : vec![],
+            objects  : vec![],
+        };
+
+        chart.push_timing_point();
+        return chart;
+    }
+
+    /// Branch commands apply right away, everything else once the measure ends
+    fn push_command(&mut self, command: &'a str) {
+        match command.split_whitespace().next().unwrap_or_default() {
+            "BRANCHSTART" | "BRANCHEND" | "M" => self.skip_branch = false,
+            "N" | "E" => self.skip_branch = true,
+
+            _ if self.skip_branch => { }
+            _ => self.events.push(Event::Command(command)),
+        }
+    }
+
+    fn push_char(&mut self, value: char) -> Result<(), &'static str> {
+        if self.skip_branch { return Ok(()) }
+        match value {
+            ','       => { self.end_measure(); Ok(()) }
+            '0'..='9' => { self.events.push(Event::Note(value as u8 - b'0')); Ok(()) }
+            'A' | 'B' => { self.events.push(Event::Note(if value == 'A' { 3 } else { 4 })); Ok(()) } // hand-held big notes
+            _ if value.is_whitespace() => Ok(()),
+            _ => Err("invalid note"),
+        }
+    }
+
+    fn end_measure(&mut self) {
+        let events = std::mem::take(&mut self.events);
+        let notes = events.iter().filter(|x| matches!(x, Event::Note(_))).count();
+
+        // Empty measures still take up time
+        if notes == 0 {
+            for event in &events {
+                if let Event::Command(command) = event { self.apply_command(command); }
+            }
+
+            self.time_ms += self.measure_length();
+            return;
+        }
+
+        for event in events {
+            match event {
+                Event::Command(command) => self.apply_command(command),
+                Event::Note(note) => {
+                    self.push_note(note);
+                    self.time_ms += self.measure_length() / notes as f64;
+                }
+            }
+        }
+    }
+
+    fn measure_length(&self) -> f64 {
+        let (numerator, denominator) = self.measure;
+        return 60000.0 / self.bpm * 4.0 * numerator / denominator;
+    }
+
+    /// Unknown commands are ignored, many simulators have their own
+    fn apply_command(&mut self, command: &str) {
+        let (name, value) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let value = value.trim();
+        match name {
+            "BPMCHANGE" => if let Ok(bpm) = value.parse::<f64>() && bpm > 0.0 {
+                self.bpm = bpm;
+                self.push_timing_point();
+            }
+
+            "SCROLL" => if let Ok(scroll) = value.parse::<f64>() && scroll > 0.0 {
+                self.scroll = scroll;
+                self.push_velocity_point();
+            }
+
+            "MEASURE" => if let Some((numerator, denominator)) = value.split_once('/')
+            && let (Ok(numerator), Ok(denominator)) = (numerator.trim().parse::<f64>(), denominator.trim().parse::<f64>())
+            && numerator > 0.0 && denominator > 0.0 {
+                self.measure = (numerator, denominator);
+                self.push_timing_point();
+            }
+
+            "DELAY" => if let Ok(delay) = value.parse::<f64>() {
+                self.time_ms += delay * 1000.0;
+            }
+
+            "GOGOSTART" => { self.effects |=  KIAI; self.push_velocity_point(); }
+            "GOGOEND"   => { self.effects &= !KIAI; self.push_velocity_point(); }
+
+            _ => { }
+        }
+    }
+
+    /// Measures are only approximated by osu!, the meter is rounded to whole beats
+    fn push_timing_point(&mut self) {
+        let (numerator, denominator) = self.measure;
+        let time = Time::from_ms(self.time_ms);
+        self.timing.retain(|x| x.time != time);
+        self.timing.push(TimingPoint {
+            time : time,
+            bpm  : self.bpm,
+
+            meter        : (4.0 * numerator / denominator).round().max(1.0) as u32,
+            sample_set   : 0,
+            sample_index : 0,
+            volume       : 100,
+            effects      : self.effects,
+        });
+
+        // Uninherited points reset scroll speed
+        if self.scroll != 1.0 { self.push_velocity_point(); }
+    }
+
+    fn push_velocity_point(&mut self) {
+        let time = Time::from_ms(self.time_ms);
+        self.velocity.retain(|x| x.time != time);
+        self.velocity.push(VelocityPoint {
+            time     : time,
+            velocity : self.scroll,
+
+            meter        : self.timing.last().map(|x| x.meter).unwrap_or(4),
+            sample_set   : 0,
+            sample_index : 0,
+            volume       : 100,
+            effects      : self.effects,
+        });
+    }
+
+    fn push_note(&mut self, note: u8) {
+        let (color, big) = match note {
+            1 => (TaikoColor::DON, false),
+            2 => (TaikoColor::KAT, false),
+            3 => (TaikoColor::DON, true),
+            4 => (TaikoColor::KAT, true),
+
+            // Drumroll, big drumroll, balloon, kusudama
+            5 | 6 | 7 | 9 => {
+                if self.roll.is_none() { self.roll = Some((self.time_ms, note)); }
+                return;
+            }
+
+            // End of a drumroll or balloon
+            8 => {
+                let Some((start_ms, kind)) = self.roll.take() else { return };
+                self.objects.push(self.roll_object(start_ms, kind));
+                return;
+            }
+
+            _ => return,
+        };
+
+        self.objects.push(TaikoCircle {
+            time  : Time::from_ms(self.time_ms),
+            big   : big,
+            color : color,
+            kind  : TaikoKind::Circle,
+
+            hit_sound : hitsound::hit_sound(color, big),
+            sample    : HitSample::default(),
+            combo     : 0,
+        });
+    }
+
+    /// Balloon hit counts are filled in once the chart is finished
+    fn roll_object(&self, start_ms: f64, note: u8) -> TaikoCircle {
+        let duration_ms = self.time_ms - start_ms;
+        return TaikoCircle {
+            time  : Time::from_ms(start_ms),
+            big   : note == 6,
+            color : TaikoColor::DON,
+            kind  : match note {
+                5 | 6 => parser::drumroll(duration_ms, 60000.0 / self.bpm, &BeatmapDifficulty::default()),
+                _     => TaikoKind::Swell { duration: Time::from_ms(duration_ms), hits: 1 },
+            },
+
+            hit_sound : hitsound::hit_sound(TaikoColor::DON, note == 6),
+            sample    : HitSample::default(),
+            combo     : 0,
+        };
+    }
+
+    fn finish(mut self, header: &Header) -> Beatmap {
+        // Balloon hit counts are given in the header, in the order balloons appear in.
+        // Missing ones fall back to 5, which is what most simulators do.
+        for (object, hits) in self.objects.iter_mut()
+            .filter(|x| matches!(x.kind, TaikoKind::Swell { .. }))
+            .zip(header.balloons.iter().copied().chain(std::iter::repeat(5))) {
+            if let TaikoKind::Swell { hits: value, .. } = &mut object.kind { *value = hits.max(1); }
+        }
+
+        let difficulty = BeatmapDifficulty::default();
+        return Beatmap {
+            objects  : self.objects,
+            timing   : self.timing,
+            velocity : self.velocity,
+
+            velocity_multiplier : difficulty.slider_multiplier as f32,
+
+            general : BeatmapGeneral {
+                audio_lead_in : Time::zero(),
+                preview_time  : header.demostart.map(|x| Time::from_ms(x * 1000.0)),
+                sample_set    : SampleSet::Normal,
+                converted     : false,
+            },
+
+            metadata : BeatmapMetadata {
+                title          : header.title.clone(),
+                title_unicode  : header.title.clone(),
+                artist         : header.subtitle.clone(),
+                artist_unicode : header.subtitle.clone(),
+                creator        : header.maker.clone(),
+                version        : match header.level {
+                    Some(level) => format!("{} Lv.{level}", header.course),
+                    None => header.course.clone(),
+                },
+                ..Default::default()
+            },
+
+            difficulty : difficulty,
+
+            audio : PathBuf::from(header.wave.clone().unwrap_or_default()),
+
+            sections : vec![],
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "TITLE:Test\nSUBTITLE:--Artist\nWAVE:song.ogg\nBPM:120\nOFFSET:0\n";
+
+    /// Single Oni course, each measure of 4/4 at 120 BPM takes 2 seconds
+    fn chart(body: &str) -> Beatmap {
+        let mut courses = try_parse(&format!("{HEADER}COURSE:Oni\n#START\n{body}\n#END\n")).unwrap();
+        assert_eq!(courses.len(), 1);
+        return courses.remove(0);
+    }
+
+    fn times(beatmap: &Beatmap) -> Vec<f64> {
+        return beatmap.objects.iter().map(|x| (x.time.to_seconds() * 1000.0).round()).collect();
+    }
+
+    #[test]
+    fn notes_are_spread_across_measures() {
+        let beatmap = chart("1020,\n3400,\n,\n1,");
+        assert_eq!(times(&beatmap), [0.0, 1000.0, 2000.0, 2500.0, 6000.0]);
+
+        let kinds = beatmap.objects.iter().map(|x| (x.color, x.big)).collect::<Vec<_>>();
+        assert_eq!(kinds, [(TaikoColor::DON, false), (TaikoColor::KAT, false), (TaikoColor::DON, true), (TaikoColor::KAT, true), (TaikoColor::DON, false)]);
+        assert_eq!(beatmap.metadata.artist, "Artist");
+    }
+
+    #[test]
+    fn bpm_change() {
+        let beatmap = chart("1,\n#BPMCHANGE 240\n11,\n1,");
+        assert_eq!(times(&beatmap), [0.0, 2000.0, 2500.0, 3000.0]);
+        assert_eq!(beatmap.timing.len(), 2);
+        assert_eq!(beatmap.timing[1].time, Time::from_ms(2000.0));
+        assert_eq!(beatmap.timing[1].bpm, 240.0);
+    }
+
+    #[test]
+    fn scroll() {
+        let beatmap = chart("1,\n#SCROLL 2\n1,\n#BPMCHANGE 60\n1,");
+        assert_eq!(beatmap.velocity_at(Time::from_ms(1000.0)), 1.0);
+        assert_eq!(beatmap.velocity_at(Time::from_ms(2000.0)), 2.0);
+
+        // Timing points would reset the scroll speed otherwise
+        assert_eq!(beatmap.velocity_at(Time::from_ms(4000.0)), 2.0);
+    }
+
+    #[test]
+    fn measure() {
+        let beatmap = chart("#MEASURE 3/4\n111,\n1,\n#MEASURE 7/8\n1,\n1,");
+        assert_eq!(times(&beatmap), [0.0, 500.0, 1000.0, 1500.0, 3000.0, 4750.0]);
+        assert_eq!(beatmap.timing.iter().map(|x| x.meter).collect::<Vec<_>>(), [3, 4]);
+    }
+
+    #[test]
+    fn delay() {
+        let beatmap = chart("1,\n#DELAY 0.5\n1,\n1,");
+        assert_eq!(times(&beatmap), [0.0, 2500.0, 4500.0]);
+        assert!(beatmap.timing.len() == 1, "delays don't need a timing point");
+    }
+
+    #[test]
+    fn offset() {
+        let courses = try_parse(&format!("{}#START\n1,\n#END\n", HEADER.replace("OFFSET:0", "OFFSET:-1.5"))).unwrap();
+        assert_eq!(times(&courses[0]), [1500.0]);
+    }
+
+    #[test]
+    fn gogo() {
+        let beatmap = chart("1,\n#GOGOSTART\n1,\n#GOGOEND\n1,");
+        assert!(!beatmap.is_kiai(Time::from_ms(0.0)));
+        assert!(beatmap.is_kiai(Time::from_ms(2000.0)));
+        assert!(!beatmap.is_kiai(Time::from_ms(4000.0)));
+    }
+
+    #[test]
+    fn rolls_and_balloons() {
+        let data = format!("{HEADER}BALLOON:10,20\n#START\n5008,6000,8000,7000,8000,7008,9008,\n#END\n");
+        let beatmap = &try_parse(&data).unwrap()[0];
+
+        let kinds = beatmap.objects.iter().map(|x| match x.kind {
+            TaikoKind::Circle                    => (0, 0.0, 0),
+            TaikoKind::Drumroll { duration, .. } => (5, (duration.to_seconds() * 1000.0).round(), 0),
+            TaikoKind::Swell { duration, hits }  => (7, (duration.to_seconds() * 1000.0).round(), hits),
+        }).collect::<Vec<_>>();
+
+        assert_eq!(kinds, [(5, 1500.0, 0), (5, 2000.0, 0), (7, 2000.0, 10), (7, 1500.0, 20), (7, 1500.0, 5)]);
+        assert!(beatmap.objects[1].big);
+    }
+
+    #[test]
+    fn only_master_branch() {
+        let beatmap = chart("1,\n#BRANCHSTART p,50,80\n#N\n2,\n#E\n22,\n#M\n3333,\n#BRANCHEND\n1,");
+        assert_eq!(times(&beatmap), [0.0, 2000.0, 2500.0, 3000.0, 3500.0, 4000.0]);
+        assert!(beatmap.objects[1 .. 5].iter().all(|x| x.big && x.color == TaikoColor::DON));
+    }
+
+    #[test]
+    fn courses() {
+        let data = format!("{HEADER}COURSE:Easy\nLEVEL:3\n#START\n1,\n#END\nCOURSE:4\nLEVEL:10\n#START\n11,\n#END\n");
+        let courses = try_parse(&data).unwrap();
+        let versions = courses.iter().map(|x| x.metadata.version.as_str()).collect::<Vec<_>>();
+        assert_eq!(versions, ["Easy Lv.3", "Edit Lv.10"]);
+        assert_eq!(courses[1].objects.len(), 2);
+    }
+
+    #[test]
+    fn lenient_skips_bad_notes() {
+        let data = format!("{HEADER}#START\n1x,\n1,\n#END\n");
+        assert!(matches!(try_parse(&data), Err(ParseError::InvalidLine { line: 7, reason, .. }) if reason == "invalid note"));
+
+        let (courses, warnings) = try_parse_lenient(&data).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(times(&courses[0]), [0.0, 2000.0]);
+    }
+
+    #[test]
+    fn missing_wave() {
+        let data = format!("{}#START\n1,\n#END\n", HEADER.replace("WAVE:song.ogg\n", ""));
+        assert_eq!(try_parse(&data).err(), Some(ParseError::MissingKey { section: "(header)", key: "WAVE" }));
+    }
+
+    #[test]
+    fn decodes_shift_jis() {
+        let text = "TITLE:さくらさくら\nSUBTITLE:--日本古謡\n";
+        let (encoded, _, unmappable) = encoding_rs::SHIFT_JIS.encode(text);
+        assert!(!unmappable);
+        assert!(std::str::from_utf8(&encoded).is_err());
+        assert_eq!(decode(&encoded), text);
+
+        let mut bom = vec![0xef, 0xbb, 0xbf];
+        bom.extend_from_slice(text.as_bytes());
+        assert_eq!(decode(&bom), text);
+    }
+}
@@ -0,0 +1,410 @@
+use std::fmt::{Display, Write};
+
+use wcore::time::Time;
+
+use super::{parser::{Beatmap, TimingPoint}, taiko_circle::{TaikoColor, TaikoKind}};
+
+/// Subdivisions per beat the save dialog starts with, covers both 1/4 and 1/6 snaps
+pub const DEFAULT_DIVISOR: u32 = 12;
+
+/// Objects further than this from their quantized position are reported
+const TOLERANCE_MS: f64 = 1.0;
+
+/// Something which could not be represented exactly in the TJA chart
+#[derive(Debug, Clone)]
+pub struct ExportWarning {
+    pub time   : Time,
+    pub reason : String,
+}
+
+impl Display for ExportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}ms: {}", self.time.to_ms(), self.reason);
+    }
+}
+
+struct Measure {
+    start       : f64, // ms
+    bpm         : f64,
+    beat_length : f64,
+
+    /// Notes per slot, slots are `divisor` per beat
+    notes       : Vec<char>,
+
+    /// Commands which go before the note in the given slot
+    commands    : Vec<(usize, String)>,
+}
+
+impl Measure {
+    fn slot_time(&self, slot: usize, divisor: u32) -> f64 {
+        return self.start + slot as f64 * self.beat_length / divisor as f64;
+    }
+}
+
+/// Serializes a beatmap into a single `Oni` course, quantizing objects onto `divisor` subdivisions per beat.
+/// Objects which don't land exactly on a subdivision are moved to the closest one and reported.
+pub fn write(beatmap: &Beatmap, divisor: u32) -> (String, Vec<ExportWarning>) {
+    let divisor = divisor.max(1);
+    let mut warnings = Vec::<ExportWarning>::new();
+    let mut measures = build_measures(beatmap, divisor, &mut warnings);
+
+    // Slot start times, for finding the closest slot of an object
+    let slots = measures.iter().enumerate()
+        .flat_map(|(idx, measure)| (0 .. measure.notes.len()).map(move |slot| (idx, slot)))
+        .map(|(idx, slot)| (idx, slot, measures[idx].slot_time(slot, divisor)))
+        .collect::<Vec<_>>();
+
+    // Roll ends pass the slot of their roll as `after`, they go on the first free slot past it and drop the roll if there is none
+    let mut place = |time: f64, note: char, after: Option<usize>, warnings: &mut Vec<ExportWarning>| -> Option<usize> {
+        let idx = slots.partition_point(|x| x.2 < time);
+        let closest = [idx.wrapping_sub(1), idx].into_iter()
+            .filter(|idx| *idx < slots.len())
+            .min_by(|a, b| (slots[*a].2 - time).abs().total_cmp(&(slots[*b].2 - time).abs()))?;
+
+        let target = match after {
+            Some(start) => (closest.max(start + 1) .. slots.len()).find(|idx| measures[slots[*idx].0].notes[slots[*idx].1] == '0'),
+            None => Some(closest),
+        };
+
+        let Some(target) = target else {
+            if let Some(start) = after { measures[slots[start].0].notes[slots[start].1] = '0'; }
+            warnings.push(ExportWarning { time: Time::from_ms(time), reason: String::from("roll has no room for its end and was dropped") });
+            return None;
+        };
+
+        let (measure, slot, slot_time) = slots[target];
+        if target != closest {
+            warnings.push(ExportWarning {
+                time   : Time::from_ms(time),
+                reason : format!("roll end overlaps another object and was moved by {:.1}ms", slot_time - time),
+            });
+        } else if (slot_time - time).abs() > TOLERANCE_MS {
+            warnings.push(ExportWarning {
+                time   : Time::from_ms(time),
+                reason : format!("moved by {:.1}ms to fit 1/{divisor} of a beat", slot_time - time),
+            });
+        }
+
+        let value = &mut measures[measure].notes[slot];
+        if *value != '0' {
+            warnings.push(ExportWarning { time: Time::from_ms(time), reason: String::from("overlaps another object and was dropped") });
+            return None;
+        }
+
+        *value = note;
+        return Some(target);
+    };
+
+    // Circles and roll heads go first, so roll ends can't take the slot of an object which comes later
+    let mut rolls = Vec::<(usize, f64, Option<u32>)>::new();
+    for object in &beatmap.objects {
+        let time = object.time.to_seconds() * 1000.0;
+        let end_time = object.end_time().to_seconds() * 1000.0;
+        match object.kind {
+            TaikoKind::Circle => {
+                let note = match (object.color, object.big) {
+                    (TaikoColor::DON, false) => '1',
+                    (TaikoColor::KAT, false) => '2',
+                    (TaikoColor::DON, true)  => '3',
+                    (TaikoColor::KAT, true)  => '4',
+                };
+
+                place(time, note, None, &mut warnings);
+            }
+
+            TaikoKind::Drumroll { .. } => {
+                let Some(start) = place(time, if object.big { '6' } else { '5' }, None, &mut warnings) else { continue };
+                rolls.push((start, end_time, None));
+            }
+
+            TaikoKind::Swell { hits, .. } => {
+                let Some(start) = place(time, '7', None, &mut warnings) else { continue };
+                rolls.push((start, end_time, Some(hits)));
+            }
+        }
+    }
+
+    // A roll without its end would last until the end of the chart
+    let mut balloons = Vec::<u32>::new();
+    for (start, end_time, hits) in rolls {
+        if place(end_time, '8', Some(start), &mut warnings).is_some() && let Some(hits) = hits {
+            balloons.push(hits);
+        }
+    }
+
+    // Scroll speed and kiai changes
+    let mut velocity = 1.0;
+    let mut kiai = false;
+    for measure in measures.iter_mut() {
+        for slot in 0 .. measure.notes.len() {
+            let time = Time::from_ms(measure.slot_time(slot, divisor));
+            let slot_velocity = beatmap.velocity_at(time);
+            if slot_velocity != velocity {
+                velocity = slot_velocity;
+                measure.commands.push((slot, format!("#SCROLL {}", format_float(velocity))));
+            }
+
+            let slot_kiai = beatmap.is_kiai(time);
+            if slot_kiai != kiai {
+                kiai = slot_kiai;
+                measure.commands.push((slot, String::from(if kiai { "#GOGOSTART" } else { "#GOGOEND" })));
+            }
+        }
+    }
+
+    return (write_chart(beatmap, &measures, &balloons, divisor), warnings);
+}
+
+/// Measures restart at every timing point, like in osu!
+fn build_measures(beatmap: &Beatmap, divisor: u32, warnings: &mut Vec<ExportWarning>) -> Vec<Measure> {
+    let default_point = TimingPoint { time: Time::zero(), bpm: 120.0, meter: 4, sample_set: 0, sample_index: 0, volume: 100, effects: 0 };
+    let first_point = beatmap.timing.first().unwrap_or(&default_point);
+
+    let end_time = beatmap.objects.iter()
+        .map(|x| x.end_time().to_seconds() * 1000.0)
+        .fold(0.0, f64::max);
+
+    // Objects before the first timing point are covered by extending its measures backwards
+    let first_object = beatmap.objects.first().map(|x| x.time.to_seconds() * 1000.0).unwrap_or(0.0);
+    let mut chart_start = first_point.time.to_seconds() * 1000.0;
+    let first_measure = first_point.beat_length() * first_point.meter.max(1) as f64;
+    while chart_start > first_object + TOLERANCE_MS {
+        chart_start -= first_measure;
+    }
+
+    let points = if beatmap.timing.is_empty() { std::slice::from_ref(&default_point) } else { beatmap.timing.as_slice() };
+    let mut measures = Vec::<Measure>::new();
+    for (idx, point) in points.iter().enumerate() {
+        let next = points.get(idx + 1).map(|x| x.time.to_seconds() * 1000.0).unwrap_or(f64::INFINITY);
+        let beat_length = point.beat_length();
+        let slot_length = beat_length / divisor as f64;
+        let full = (point.meter.max(1) * divisor) as usize;
+
+        let mut time = if idx == 0 { chart_start } else { point.time.to_seconds() * 1000.0 };
+        while time < next - TOLERANCE_MS && time <= end_time + TOLERANCE_MS {
+            let remaining = (next - time) / slot_length;
+            let slots = if remaining.is_finite() { (remaining.round() as usize).min(full) } else { full };
+            if slots == 0 { break }
+
+            if slots < full && (remaining - remaining.round()).abs() * slot_length > TOLERANCE_MS {
+                warnings.push(ExportWarning {
+                    time   : Time::from_ms(next),
+                    reason : format!("timing point is not on 1/{divisor} of a beat of the previous one"),
+                });
+            }
+
+            measures.push(Measure {
+                start       : time,
+                bpm         : point.bpm,
+                beat_length : beat_length,
+                notes       : vec!['0'; slots],
+                commands    : vec![],
+            });
+
+            time += slots as f64 * slot_length;
+        }
+    }
+
+    return measures;
+}
+
+fn write_chart(beatmap: &Beatmap, measures: &[Measure], balloons: &[u32], divisor: u32) -> String {
+    let metadata = &beatmap.metadata;
+    let title = if metadata.title_unicode.is_empty() { &metadata.title } else { &metadata.title_unicode };
+    let artist = if metadata.artist_unicode.is_empty() { &metadata.artist } else { &metadata.artist_unicode };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "TITLE:{title}");
+    let _ = writeln!(out, "SUBTITLE:--{artist}");
+    let _ = writeln!(out, "MAKER:{}", metadata.creator);
+    let _ = writeln!(out, "BPM:{}", format_float(measures.first().map(|x| x.bpm).unwrap_or(120.0)));
+    let _ = writeln!(out, "WAVE:{}", beatmap.audio.file_name().map(|x| x.to_string_lossy()).unwrap_or_default());
+    let _ = writeln!(out, "OFFSET:{}", format_float(-measures.first().map(|x| x.start).unwrap_or(0.0) / 1000.0));
+    if let Some(preview_time) = beatmap.general.preview_time {
+        let _ = writeln!(out, "DEMOSTART:{}", format_float(preview_time.to_seconds()));
+    }
+
+    out.push('\n');
+    let _ = writeln!(out, "COURSE:Oni");
+    if !balloons.is_empty() {
+        let _ = writeln!(out, "BALLOON:{}", balloons.iter().map(u32::to_string).collect::<Vec<_>>().join(","));
+    }
+
+    out.push('\n');
+    out.push_str("#START\n");
+
+    let mut bpm = measures.first().map(|x| x.bpm).unwrap_or(120.0);
+    let mut length = (4 * divisor) as usize;
+    for measure in measures {
+        if measure.bpm != bpm {
+            bpm = measure.bpm;
+            let _ = writeln!(out, "#BPMCHANGE {}", format_float(bpm));
+        }
+
+        // Measure length as a time signature, 4/4 being the default
+        if measure.notes.len() != length {
+            length = measure.notes.len();
+            let beat = divisor as usize;
+            let gcd = gcd(length, 4 * beat);
+            let _ = match length % beat {
+                0 => writeln!(out, "#MEASURE {}/4", length / beat),
+                _ => writeln!(out, "#MEASURE {}/{}", length / gcd, 4 * beat / gcd),
+            };
+        }
+
+        // Slots are merged as long as nothing gets lost
+        let step = measure.notes.iter().enumerate()
+            .filter(|(_, note)| **note != '0')
+            .map(|(slot, _)| slot)
+            .chain(measure.commands.iter().map(|(slot, _)| *slot))
+            .fold(measure.notes.len(), gcd);
+
+        let mut line = String::new();
+        for slot in (0 .. measure.notes.len()).step_by(step.max(1)) {
+            for (_, command) in measure.commands.iter().filter(|(x, _)| *x == slot) {
+                if !line.is_empty() { out.push_str(&line); out.push('\n'); line.clear(); }
+                out.push_str(command);
+                out.push('\n');
+            }
+
+            line.push(measure.notes[slot]);
+        }
+
+        out.push_str(&line);
+        out.push_str(",\n");
+    }
+
+    out.push_str("#END\n");
+    return out;
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+fn format_float(value: f64) -> String {
+    let value = format!("{value:.6}");
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    return if value == "-0" { String::from("0") } else { value.to_owned() };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::{parser, tja};
+
+    /// 120 BPM unless `timing` says otherwise, each beat is 500ms
+    fn beatmap(timing: &str, objects: &str) -> Beatmap {
+        let data = format!("osu file format v14\n\n[General]\nAudioFilename: song.ogg\nMode: 1\n\n[Metadata]\nTitle:Test\nArtist:Artist\n\n[TimingPoints]\n{timing}\n\n[HitObjects]\n{objects}\n");
+        return parser::try_parse(&data).unwrap();
+    }
+
+    fn reasons(warnings: &[ExportWarning]) -> Vec<String> {
+        return warnings.iter().map(ToString::to_string).collect();
+    }
+
+    /// Lines between `#START` and `#END`
+    fn body(chart: &str) -> Vec<&str> {
+        return chart.lines().skip_while(|x| *x != "#START").skip(1).take_while(|x| *x != "#END").collect();
+    }
+
+    #[test]
+    fn quantizes_onto_divisor() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0", "256,192,0,1,0\n256,192,130,1,2\n256,192,250,1,4\n256,192,501,1,6");
+        let (chart, warnings) = write(&beatmap, 4);
+
+        // Off by less than the tolerance isn't worth a warning
+        assert_eq!(reasons(&warnings), ["130ms: moved by -5.0ms to fit 1/4 of a beat"]);
+        assert_eq!(body(&chart), ["1230400000000000,"]);
+    }
+
+    #[test]
+    fn overlapping_objects_are_dropped() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0", "256,192,0,1,0\n256,192,10,1,2");
+        let (chart, warnings) = write(&beatmap, 4);
+        assert_eq!(reasons(&warnings), ["10ms: moved by -10.0ms to fit 1/4 of a beat", "10ms: overlaps another object and was dropped"]);
+        assert_eq!(body(&chart), ["1,"]);
+    }
+
+    #[test]
+    fn bpm_and_measure_changes() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0\n2000,250,3,1,0,100,1,0\n2750,250,4,1,0,100,1,0", "256,192,0,1,0\n256,192,2000,1,0\n256,192,2750,1,0");
+        let (chart, warnings) = write(&beatmap, 4);
+        assert!(warnings.is_empty());
+        assert!(chart.contains("\nBPM:120\n"));
+        assert_eq!(body(&chart), ["1,", "#BPMCHANGE 240", "#MEASURE 3/4", "1,", "#MEASURE 4/4", "1,"]);
+    }
+
+    #[test]
+    fn timing_point_off_the_grid() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0\n1100,500,4,1,0,100,1,0", "256,192,0,1,0\n256,192,1100,1,0");
+        let (_, warnings) = write(&beatmap, 4);
+        assert_eq!(reasons(&warnings), ["1100ms: timing point is not on 1/4 of a beat of the previous one"]);
+    }
+
+    #[test]
+    fn scroll_and_gogo() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0\n1000,-200,4,1,0,100,0,1\n1500,-100,4,1,0,100,0,0", "256,192,0,1,0\n256,192,1750,1,0");
+        let (chart, _) = write(&beatmap, 2);
+        assert_eq!(body(&chart), ["1000", "#SCROLL 0.5", "#GOGOSTART", "00", "#SCROLL 1", "#GOGOEND", "01,"]);
+    }
+
+    #[test]
+    fn roll_end_moves_past_objects() {
+        // The roll ends on a circle, the slot after it is taken by a later one
+        let beatmap = beatmap("0,500,4,1,0,100,1,0", "256,192,0,2,0,L|300:192,1,70\n256,192,250,1,0\n256,192,375,1,2");
+        let (chart, warnings) = write(&beatmap, 4);
+        assert_eq!(reasons(&warnings), ["250ms: roll end overlaps another object and was moved by 250.0ms"]);
+        assert_eq!(body(&chart), ["5012800000000000,"]);
+    }
+
+    #[test]
+    fn roll_without_room_is_dropped() {
+        let beatmap = beatmap("0,500,4,1,0,100,1,0", "256,192,0,12,0,1750\n256,192,1750,1,0");
+        let (chart, warnings) = write(&beatmap, 2);
+        assert_eq!(reasons(&warnings), ["1750ms: roll has no room for its end and was dropped"]);
+        assert_eq!(body(&chart), ["00000001,"]);
+        assert!(!chart.contains("BALLOON"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let objects = [
+            "256,192,0,1,0",
+            "256,192,250,1,2",
+            "256,192,500,1,4",
+            "256,192,750,1,6",
+            "256,192,1000,2,4,L|300:192,1,140",
+            "256,192,2000,12,0,3000",
+            "256,192,3375,1,8",
+            "256,192,3750,1,0",
+            "256,192,4125,12,0,4875",
+        ];
+
+        let original = beatmap("0,500,4,1,0,100,1,0\n2000,-50,4,1,0,100,0,1\n3000,375,3,1,0,100,1,0\n4000,-100,3,1,0,100,0,0", &objects.join("\n"));
+        let (chart, warnings) = write(&original, 4);
+        assert!(warnings.is_empty());
+
+        let parsed = tja::try_parse(&chart).unwrap().remove(0);
+        assert_eq!(parsed.metadata.title, "Test");
+        assert_eq!(parsed.objects.len(), original.objects.len());
+        for (a, b) in original.objects.iter().zip(&parsed.objects) {
+            assert!((a.time.to_seconds() - b.time.to_seconds()).abs() < 1e-6);
+            assert!((a.end_time().to_seconds() - b.end_time().to_seconds()).abs() < 1e-6);
+            assert_eq!((a.color, a.big), (b.color, b.big));
+            assert_eq!(std::mem::discriminant(&a.kind), std::mem::discriminant(&b.kind));
+        }
+
+        let hits = |beatmap: &Beatmap| beatmap.objects.iter().filter_map(|x| if let TaikoKind::Swell { hits, .. } = x.kind { Some(hits) } else { None }).collect::<Vec<_>>();
+        assert_eq!(hits(&parsed), hits(&original));
+
+        // The shortened measure before the BPM change gets a timing point of its own
+        for time in [0.0, 2500.0, 3200.0, 4500.0] {
+            let time = Time::from_ms(time);
+            let (a, b) = (original.timing_at(time).unwrap(), parsed.timing_at(time).unwrap());
+            assert_eq!(a.bpm, b.bpm);
+            assert_eq!(parsed.velocity_at(time), original.velocity_at(time));
+            assert_eq!(parsed.is_kiai(time), original.is_kiai(time));
+        }
+    }
+}
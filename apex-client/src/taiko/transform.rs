@@ -0,0 +1,233 @@
+use wcore::time::Time;
+
+use super::{parser::{self, Beatmap}, taiko_circle::{TaikoCircle, TaikoKind}, editor, writer};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transform {
+    SwapColors,
+    Reverse,
+
+    /// Ratio of the new distances to the old ones
+    Scale(f64),
+    Big(bool),
+
+    /// Around the given time
+    Mirror(Time),
+}
+
+impl Transform {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Transform::SwapColors  => "Swap colors",
+            Transform::Reverse     => "Reverse objects",
+            Transform::Scale(_)    => "Scale objects",
+            Transform::Big(true)   => "Make objects big",
+            Transform::Big(false)  => "Make objects small",
+            Transform::Mirror(_)   => "Mirror objects",
+        };
+    }
+
+    /// Returns the new indices of the objects, `None` if they would overlap other objects and nothing changed
+    pub fn apply(&self, beatmap: &mut Beatmap, indices: &[usize], divisor: u32) -> Option<Vec<usize>> {
+        match *self {
+            Transform::SwapColors    => swap_colors(beatmap, indices),
+            Transform::Big(big)      => set_big(beatmap, indices, big),
+            Transform::Reverse       => return reverse(beatmap, indices),
+            Transform::Scale(ratio)  => return scale(beatmap, indices, ratio, divisor),
+            Transform::Mirror(pivot) => return mirror(beatmap, indices, pivot),
+        }
+
+        return Some(indices.to_vec());
+    }
+}
+
+/// Swaps don and kat, only circles have a color
+pub fn swap_colors(beatmap: &mut Beatmap, indices: &[usize]) {
+    for idx in indices {
+        editor::toggle_color(beatmap, *idx);
+    }
+}
+
+/// Swells stay big
+pub fn set_big(beatmap: &mut Beatmap, indices: &[usize], big: bool) {
+    for idx in indices {
+        let Some(object) = beatmap.objects.get_mut(*idx) else { continue };
+        if matches!(object.kind, TaikoKind::Swell { .. }) { continue }
+
+        object.big = big;
+        object.hit_sound = writer::object_hit_sound(object);
+    }
+}
+
+/// Plays the objects back to front within the time they already take up
+pub fn reverse(beatmap: &mut Beatmap, indices: &[usize]) -> Option<Vec<usize>> {
+    let objects = indices.iter().filter_map(|x| beatmap.objects.get(*x)).collect::<Vec<_>>();
+    let start = objects.iter().map(|x| x.time.to_seconds()).reduce(f64::min)?;
+    let end = objects.iter().map(|x| x.end_time().to_seconds()).reduce(f64::max)?;
+    return mirror(beatmap, indices, Time::from_seconds((start + end) / 2.0));
+}
+
+/// Flips the objects around `pivot`, long objects keep their length. Fails if anything would end up before the song starts.
+pub fn mirror(beatmap: &mut Beatmap, indices: &[usize], pivot: Time) -> Option<Vec<usize>> {
+    let pivot = pivot.to_seconds() * 1000.0;
+    let mut objects = indices.iter().filter_map(|x| beatmap.objects.get(*x).cloned()).collect::<Vec<_>>();
+    for object in &mut objects {
+        let time = (2.0 * pivot - object.end_time().to_seconds() * 1000.0).round();
+        if time < 0.0 { return None }
+        object.time = Time::from_ms(time);
+    }
+
+    return editor::replace_all(beatmap, indices, objects);
+}
+
+/// Stretches the distances from the first object by `ratio` and snaps the result to 1/`divisor` beats.
+/// Drumrolls and swells are stretched as well, with ticks and hits recalculated, and never get shorter than one tick.
+pub fn scale(beatmap: &mut Beatmap, indices: &[usize], ratio: f64, divisor: u32) -> Option<Vec<usize>> {
+    if !ratio.is_finite() || ratio <= 0.0 { return None }
+
+    let origin = indices.first().and_then(|x| beatmap.objects.get(*x))?.time.to_seconds() * 1000.0;
+    let scaled = |time: Time| beatmap.snap(Time::from_ms(origin + (time.to_seconds() * 1000.0 - origin) * ratio), divisor);
+
+    let objects = indices.iter().filter_map(|x| beatmap.objects.get(*x)).map(|object| {
+        let time = scaled(object.time);
+        let beat_length = beatmap.timing_at(time).map(|x| x.beat_length()).unwrap_or(500.0);
+
+        // Both ends snap to the grid, long objects keep at least one tick instead of shrinking to nothing
+        let step = (beat_length / divisor.max(1) as f64).round();
+        let duration_ms = ((scaled(object.end_time()).to_seconds() - time.to_seconds()) * 1000.0).max(step);
+        let kind = match object.kind {
+            TaikoKind::Circle => TaikoKind::Circle,
+            TaikoKind::Drumroll { .. } => parser::drumroll(duration_ms, beat_length, &beatmap.difficulty),
+            TaikoKind::Swell { .. } => parser::swell(duration_ms, &beatmap.difficulty),
+        };
+
+        TaikoCircle { time, kind, ..object.clone() }
+    }).collect::<Vec<_>>();
+
+    return editor::replace_all(beatmap, indices, objects);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::{hitsound, taiko_circle::TaikoColor};
+
+    fn rolls() -> Beatmap {
+        return parser::try_parse(include_str!("../../tests/fixtures/rolls.osu")).unwrap();
+    }
+
+    fn times(beatmap: &Beatmap) -> Vec<u64> {
+        return beatmap.objects.iter().map(|x| x.time.to_ms()).collect();
+    }
+
+    #[test]
+    fn swaps_only_circles() {
+        let mut beatmap = rolls();
+        let roll = beatmap.objects[1].clone();
+        assert_eq!(Transform::SwapColors.apply(&mut beatmap, &[0, 1], 4), Some(vec![0, 1]));
+
+        assert_eq!(beatmap.objects[0].color, TaikoColor::KAT);
+        assert_ne!(beatmap.objects[0].hit_sound & (hitsound::WHISTLE | hitsound::CLAP), 0);
+        assert_eq!(beatmap.objects[1], roll);
+    }
+
+    #[test]
+    fn swells_stay_big() {
+        let mut beatmap = rolls();
+        set_big(&mut beatmap, &[0, 2, 4], true);
+        assert!(beatmap.objects[0].big && beatmap.objects[0].hit_sound & hitsound::FINISH != 0);
+
+        set_big(&mut beatmap, &[0, 2, 4], false);
+        assert!(!beatmap.objects[0].big && beatmap.objects[0].hit_sound & hitsound::FINISH == 0);
+        assert!(!beatmap.objects[2].big && beatmap.objects[2].hit_sound & hitsound::FINISH == 0);
+        assert_eq!(beatmap.objects[4].big, rolls().objects[4].big);
+    }
+
+    #[test]
+    fn mirror_keeps_roll_lengths() {
+        let mut beatmap = rolls();
+        let roll = rolls().objects[1].clone();
+
+        // The circle at 500ms and the roll from 900ms flip around 1200ms, the roll now ends where it started
+        let moved = mirror(&mut beatmap, &[0, 1], Time::from_ms(1200)).unwrap();
+        assert_eq!(moved, [0, 1]);
+        assert_eq!(beatmap.objects[1].time.to_ms(), 1900);
+        assert_eq!(beatmap.objects[1].kind, TaikoKind::Circle);
+
+        let mirrored = &beatmap.objects[0];
+        assert_eq!(mirrored.time.to_ms(), 2400 - roll.end_time().to_ms());
+        assert_eq!(mirrored.kind, roll.kind);
+        assert_eq!(mirrored.end_time().to_ms(), 2400 - roll.time.to_ms());
+    }
+
+    #[test]
+    fn refuses_overlaps() {
+        let mut beatmap = rolls();
+
+        // Would land on the drumroll at 2500ms
+        assert_eq!(mirror(&mut beatmap, &[0], Time::from_ms(1500)), None);
+
+        // Would start before the song
+        assert_eq!(mirror(&mut beatmap, &[0, 1], Time::from_ms(200)), None);
+
+        // Squashed onto the same tick
+        assert_eq!(scale(&mut beatmap, &[0, 1], 0.01, 4), None);
+        assert_eq!(scale(&mut beatmap, &[0, 1], 0.0, 4), None);
+        assert_eq!(scale(&mut beatmap, &[0, 1], f64::NAN, 4), None);
+
+        assert_eq!(beatmap.objects, rolls().objects);
+    }
+
+    #[test]
+    fn reverse_stays_in_place() {
+        let mut beatmap = rolls();
+        let original = rolls();
+        let end = original.objects[1].end_time().to_ms();
+
+        let moved = reverse(&mut beatmap, &[0, 1]).unwrap();
+        assert_eq!(moved, [0, 1]);
+        assert_eq!(beatmap.objects[0].time.to_ms(), 500);
+        assert_eq!(beatmap.objects[1].time.to_ms(), end);
+        assert_eq!(beatmap.objects[0].duration(), original.objects[1].duration());
+
+        // Twice is the original
+        reverse(&mut beatmap, &moved).unwrap();
+        assert_eq!(times(&beatmap), times(&original));
+    }
+
+    #[test]
+    fn scale_snaps_both_ends() {
+        let mut beatmap = rolls();
+        let roll = rolls().objects[1].clone();
+
+        // 150bpm, a 1/4 beat is 100ms
+        let moved = scale(&mut beatmap, &[0, 1], 2.0, 4).unwrap();
+        assert_eq!(moved, [0, 1]);
+        assert_eq!(times(&beatmap)[.. 2], [500, 1300]);
+
+        let expected = ((roll.end_time().to_ms() as f64 - 500.0) * 2.0 / 100.0).round() * 100.0 + 500.0 - 1300.0;
+        assert_eq!(beatmap.objects[1].duration().to_ms(), expected as u64);
+        let (TaikoKind::Drumroll { ticks: before, .. }, TaikoKind::Drumroll { ticks: after, .. }) = (roll.kind, beatmap.objects[1].kind) else { panic!("expected drumrolls") };
+        assert!(after > before);
+    }
+
+    #[test]
+    fn scale_keeps_a_tick_of_short_rolls() {
+        let mut beatmap = rolls();
+        let roll = rolls().objects[5].clone();
+        let swell = rolls().objects[6].clone();
+        assert!(roll.duration().to_ms() > 0);
+
+        // The end of the roll would snap onto its start
+        let moved = scale(&mut beatmap, &[5, 6], 0.1, 4).unwrap();
+        assert_eq!(moved, [5, 6]);
+        assert_eq!(beatmap.objects[5].time, roll.time);
+        assert_eq!(beatmap.objects[5].duration().to_ms(), 100);
+        assert!(matches!(beatmap.objects[5].kind, TaikoKind::Drumroll { ticks, .. } if ticks >= 1));
+
+        // The swell is far enough from the origin to only shrink
+        assert_eq!(beatmap.objects[6].time.to_ms(), 8700);
+        assert_eq!(beatmap.objects[6].end_time().to_ms(), 8900);
+        assert!(beatmap.objects[6].duration() < swell.duration());
+    }
+}
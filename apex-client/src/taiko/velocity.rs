@@ -0,0 +1,76 @@
+use wcore::time::Time;
+
+use super::parser::{Beatmap, VelocityPoint};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interpolation {
+    Linear,
+    Exponential,
+}
+
+impl Interpolation {
+    /// Velocity at `t` in 0..=1 of the way from `from` to `to`
+    pub fn value(self, from: f64, to: f64, t: f64) -> f64 {
+        return match self {
+            Interpolation::Linear => from + (to - from) * t,
+
+            // Constant ratio between steps, velocities are always positive
+            Interpolation::Exponential => from * (to / from).powf(t),
+        };
+    }
+}
+
+/// Points on every 1/`divisor` beat from `start` to `end`, both included.
+/// Sample and effect settings are copied from whatever is active at each point.
+pub fn interpolate(beatmap: &Beatmap, start: Time, end: Time, from: f64, to: f64, divisor: u32, interpolation: Interpolation) -> Vec<VelocityPoint> {
+    let (start_ms, end_ms) = (start.to_seconds() * 1000.0, end.to_seconds() * 1000.0);
+    if end_ms <= start_ms || from <= 0.0 || to <= 0.0 { return vec![] }
+
+    let mut times = vec![];
+    let mut time = start_ms;
+    while time < end_ms {
+        times.push(time);
+
+        // Step size follows the timing point, rounded like editors do
+        let beat_length = beatmap.timing_at(Time::from_ms(time)).map(|x| x.beat_length()).unwrap_or(500.0);
+        let step = (beat_length / divisor.max(1) as f64).max(1.0);
+        time = (time + step).round();
+    }
+
+    times.push(end_ms);
+    return times.into_iter().map(|time| {
+        let t = (time - start_ms) / (end_ms - start_ms);
+        point_at(beatmap, Time::from_ms(time), interpolation.value(from, to, t))
+    }).collect();
+}
+
+/// New point, sample and effect settings are copied from whatever is active at `time`
+pub fn point_at(beatmap: &Beatmap, time: Time, velocity: f64) -> VelocityPoint {
+    let control = beatmap.control_at(time);
+    return VelocityPoint {
+        time,
+        velocity,
+        meter        : beatmap.timing_at(time).map(|x| x.meter).unwrap_or(4),
+        sample_set   : control.sample_set,
+        sample_index : control.sample_index,
+        volume       : control.volume,
+        effects      : control.effects,
+    };
+}
+
+/// Keeps points sorted, returns where the point ended up
+pub fn insert(beatmap: &mut Beatmap, point: VelocityPoint) -> usize {
+    let idx = beatmap.velocity.partition_point(|x| x.time <= point.time);
+    beatmap.velocity.insert(idx, point);
+    return idx;
+}
+
+/// Replaces every velocity point between the first and last of `points`, keeping them sorted
+pub fn replace_range(beatmap: &mut Beatmap, points: Vec<VelocityPoint>) {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else { return };
+    let (start, end) = (first.time, last.time);
+
+    let from = beatmap.velocity.partition_point(|x| x.time < start);
+    let to = beatmap.velocity.partition_point(|x| x.time <= end);
+    beatmap.velocity.splice(from .. to, points);
+}
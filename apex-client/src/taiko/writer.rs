@@ -0,0 +1,360 @@
+use std::fmt::Write;
+
+use wcore::time::Time;
+
+use super::{parser::{self, Beatmap, RawSection}, taiko_circle::{TaikoCircle, TaikoColor, TaikoKind}, hitsound::{self, HitSample}};
+
+/// Sections which are always written, in the order used by osu!
+const REQUIRED_SECTIONS: [&str; 5] = ["[General]", "[Metadata]", "[Difficulty]", "[TimingPoints]", "[HitObjects]"];
+
+/// Serializes a beatmap into the `osu file format v14`.
+/// Sections and keys which are not modeled by [`Beatmap`] are written back as they were read.
+pub fn write(beatmap: &Beatmap) -> String {
+    let mut sections = beatmap.sections.iter().collect::<Vec<_>>();
+    let missing = REQUIRED_SECTIONS.iter()
+        .filter(|name| !beatmap.sections.iter().any(|x| x.name == **name))
+        .map(|name| RawSection { name: name.to_string(), lines: vec![] })
+        .collect::<Vec<_>>();
+
+    sections.extend(missing.iter());
+
+    let mut out = String::from("osu file format v14\n");
+    for section in sections {
+        out.push('\n');
+        out.push_str(&section.name);
+        out.push('\n');
+
+        match section.name.as_str() {
+            "[General]"      => write_key_values(&mut out, &section.lines, ": ", &general(beatmap)),
+            "[Metadata]"     => write_key_values(&mut out, &section.lines, ":",  &metadata(beatmap)),
+            "[Difficulty]"   => write_key_values(&mut out, &section.lines, ":",  &difficulty(beatmap)),
+            "[TimingPoints]" => write_timing_points(&mut out, beatmap),
+            "[HitObjects]"   => write_hit_objects(&mut out, beatmap),
+
+            _ => for line in &section.lines {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+
+    return out;
+}
+
+type KeyValues = Vec<(&'static str, Option<String>)>;
+
+fn general(beatmap: &Beatmap) -> KeyValues {
+    return vec![
+        ("AudioFilename", Some(beatmap.audio.to_string_lossy().into_owned())),
+        ("AudioLeadIn",   Some(format_ms(beatmap.general.audio_lead_in))),
+        ("PreviewTime",   Some(beatmap.general.preview_time.map(format_ms).unwrap_or_else(|| String::from("-1")))),
+        ("SampleSet",     Some(beatmap.general.sample_set.name().to_owned())),
+        ("Mode",          Some(String::from(if beatmap.general.converted { "0" } else { "1" }))),
+    ];
+}
+
+fn metadata(beatmap: &Beatmap) -> KeyValues {
+    let metadata = &beatmap.metadata;
+    return vec![
+        ("Title",         Some(metadata.title.clone())),
+        ("TitleUnicode",  Some(metadata.title_unicode.clone())),
+        ("Artist",        Some(metadata.artist.clone())),
+        ("ArtistUnicode", Some(metadata.artist_unicode.clone())),
+        ("Creator",       Some(metadata.creator.clone())),
+        ("Version",       Some(metadata.version.clone())),
+        ("Source",        Some(metadata.source.clone())),
+        ("Tags",          Some(metadata.tags.join(" "))),
+        ("BeatmapID",     metadata.beatmap_id.map(|x| x.to_string())),
+        ("BeatmapSetID",  metadata.beatmap_set_id.map(|x| x.to_string())),
+    ];
+}
+
+fn difficulty(beatmap: &Beatmap) -> KeyValues {
+    let difficulty = &beatmap.difficulty;
+    return vec![
+        ("HPDrainRate",       Some(difficulty.hp_drain_rate.to_string())),
+        ("CircleSize",        Some(difficulty.circle_size.to_string())),
+        ("OverallDifficulty", Some(difficulty.overall_difficulty.to_string())),
+        ("ApproachRate",      Some(difficulty.approach_rate.to_string())),
+        ("SliderMultiplier",  Some(slider_multiplier(beatmap))),
+        ("SliderTickRate",    Some(difficulty.slider_tick_rate.to_string())),
+    ];
+}
+
+/// Converts are written back as osu!standard beatmaps, with the slider multiplier they were loaded with
+fn slider_multiplier(beatmap: &Beatmap) -> String {
+    if !beatmap.general.converted { return beatmap.difficulty.slider_multiplier.to_string() }
+    return format_float(parser::file_slider_multiplier(&beatmap.general, &beatmap.difficulty), 12);
+}
+
+/// Writes `key: value` lines, replacing values of known keys and keeping everything else intact.
+/// Known keys without a value are left as they were, known keys missing from the file are appended.
+fn write_key_values(out: &mut String, lines: &[String], separator: &str, values: &KeyValues) {
+    let mut written = vec![false; values.len()];
+    for line in lines {
+        let known = line.split_once(':').and_then(|(key, value)| {
+            let idx = values.iter().position(|(name, _)| *name == key.trim())?;
+            Some((idx, key.trim(), value.trim()))
+        });
+
+        match known {
+            Some((idx, key, value)) if !written[idx] => {
+                written[idx] = true;
+                match &values[idx].1 {
+                    Some(new_value) if new_value != value => { let _ = writeln!(out, "{key}{separator}{new_value}"); }
+                    _ => { out.push_str(line); out.push('\n'); }
+                }
+            }
+
+            // Duplicate keys are dropped, osu! uses the last one anyways
+            Some(_) => { }
+
+            None => { out.push_str(line); out.push('\n'); }
+        }
+    }
+
+    for (idx, (key, value)) in values.iter().enumerate() {
+        if let Some(value) = value && !written[idx] {
+            let _ = writeln!(out, "{key}{separator}{value}");
+        }
+    }
+}
+
+fn write_timing_points(out: &mut String, beatmap: &Beatmap) {
+    let mut timing   = beatmap.timing.iter().peekable();
+    let mut velocity = beatmap.velocity.iter().peekable();
+
+    // Uninherited points go first if both are at the same time
+    loop {
+        let next_timing = match (timing.peek(), velocity.peek()) {
+            (Some(t), Some(v)) => t.time <= v.time,
+            (Some(_), None)    => true,
+            (None, Some(_))    => false,
+            (None, None)       => break,
+        };
+
+        if next_timing {
+            let Some(point) = timing.next() else { break };
+            let _ = writeln!(out, "{},{},{},{},{},{},1,{}",
+                format_ms(point.time), format_beat_length(point.beat_length()),
+                point.meter, point.sample_set, point.sample_index, point.volume, point.effects);
+        } else {
+            let Some(point) = velocity.next() else { break };
+            let _ = writeln!(out, "{},{},{},{},{},{},0,{}",
+                format_ms(point.time), format_beat_length(-100.0 / point.velocity),
+                point.meter, point.sample_set, point.sample_index, point.volume, point.effects);
+        }
+    }
+}
+
+fn write_hit_objects(out: &mut String, beatmap: &Beatmap) {
+    for object in &beatmap.objects {
+        write_hit_object(out, beatmap, object);
+    }
+}
+
+/// Taiko doesn't care about positions, so every object is placed at the center of the playfield.
+/// Swells always start a new combo, like spinners written by osu!.
+pub fn write_hit_object(out: &mut String, beatmap: &Beatmap, object: &TaikoCircle) {
+    let hit_sound = object_hit_sound(object);
+    let sample = format_hit_sample(&object.sample);
+    let combo = object.combo & parser::COMBO_FLAGS;
+
+    let time = format_ms(object.time);
+    match object.kind {
+        TaikoKind::Circle => {
+            let _ = writeln!(out, "256,192,{time},{},{hit_sound},{sample}", 1 | combo);
+        }
+
+        // Both ends use the hit sound and sample sets of the whole object
+        TaikoKind::Drumroll { duration, .. } => {
+            let length = drumroll_length(beatmap, object.time, duration);
+            let _ = writeln!(out, "256,192,{time},{},{hit_sound},L|{}:192,1,{},{hit_sound}|{hit_sound},0:0|0:0,{sample}",
+                2 | combo, 256 + length.round() as i64, format_float(length, 6));
+        }
+
+        TaikoKind::Swell { .. } => {
+            let _ = writeln!(out, "256,192,{time},{},{hit_sound},{},{sample}", 8 | parser::NEW_COMBO | combo, format_ms(object.end_time()));
+        }
+    }
+}
+
+/// Keeps the hit sound flags of the object, except for the ones `big` and `color` have to override
+pub fn object_hit_sound(object: &TaikoCircle) -> u8 {
+    let mut hit_sound = object.hit_sound;
+    if object.big { hit_sound |= hitsound::FINISH } else { hit_sound &= !hitsound::FINISH }
+
+    if object.kind == TaikoKind::Circle {
+        let is_kat = hit_sound & (hitsound::WHISTLE | hitsound::CLAP) != 0;
+        match object.color {
+            TaikoColor::KAT if !is_kat => hit_sound |= hitsound::CLAP,
+            TaikoColor::DON => hit_sound &= !(hitsound::WHISTLE | hitsound::CLAP),
+            _ => { }
+        }
+    }
+
+    return hit_sound;
+}
+
+fn format_hit_sample(sample: &HitSample) -> String {
+    return format!("{}:{}:{}:{}:{}",
+        sample.normal_set.index(), sample.addition_set.index(), sample.index, sample.volume, sample.filename);
+}
+
+/// Inverse of the drumroll duration formula used by the parser, converts are scaled again once parsed
+fn drumroll_length(beatmap: &Beatmap, time: Time, duration: Time) -> f64 {
+    let Some(timing_point) = beatmap.timing_at(time) else { return 0.0 };
+    let velocity = beatmap.velocity_at(time);
+    let duration_ms = duration.to_seconds() * 1000.0;
+
+    let slider_multiplier = parser::file_slider_multiplier(&beatmap.general, &beatmap.difficulty);
+    let length = duration_ms * slider_multiplier * 100.0 * velocity / timing_point.beat_length();
+
+    // Converted durations are truncated to whole milliseconds, rounding the length down would lose one
+    return if beatmap.general.converted { (length * 1e6).ceil() / 1e6 + 1e-6 } else { length };
+}
+
+/// Milliseconds without a fractional part unless there is one
+fn format_ms(time: Time) -> String {
+    return format_float(time.to_seconds() * 1000.0, 6);
+}
+
+/// 15 significant digits like osu! writes them, anything less changes the value once parsed again
+fn format_beat_length(value: f64) -> String {
+    let digits = value.abs().log10().floor().max(0.0) as usize + 1;
+    return format_float(value, 15usize.saturating_sub(digits));
+}
+
+/// Rounds off floating point noise, so values survive being parsed and written again
+fn format_float(value: f64, precision: usize) -> String {
+    let value = format!("{value:.precision$}");
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    return if value == "-0" { String::from("0") } else { value.to_owned() };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taiko::parser;
+
+    const FIXTURES: [(&str, &str); 3] = [
+        ("inherited.osu", include_str!("../../tests/fixtures/inherited.osu")),
+        ("rolls.osu",     include_str!("../../tests/fixtures/rolls.osu")),
+        ("convert.osu",   include_str!("../../tests/fixtures/convert.osu")),
+    ];
+
+    #[test]
+    fn round_trip_keeps_beatmap() {
+        for (name, data) in FIXTURES {
+            let beatmap = parser::try_parse(data).unwrap();
+            let written = parser::try_parse(&write(&beatmap)).unwrap();
+            assert_eq!(beatmap, written, "{name}");
+        }
+    }
+
+    #[test]
+    fn round_trip_is_stable() {
+        for (name, data) in FIXTURES {
+            let first = write(&parser::try_parse(data).unwrap());
+            let second = write(&parser::try_parse(&first).unwrap());
+            assert_eq!(first, second, "{name}");
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_sections_and_keys() {
+        let written = write(&parser::try_parse(FIXTURES[0].1).unwrap());
+        for line in ["EpilepsyWarning: 1", "SamplesMatchPlaybackRate: 1", "Bookmarks: 1218,30218", "2,15218,20218", "Combo2 : 80,160,240", "[Extra]", "// Not a section osu! knows about", "Key: value"] {
+            assert!(written.lines().any(|x| x == line), "missing `{line}`");
+        }
+    }
+
+    #[test]
+    fn keeps_timing_and_inherited_points() {
+        let beatmap = parser::try_parse(FIXTURES[0].1).unwrap();
+        let written = parser::try_parse(&write(&beatmap)).unwrap();
+        assert_eq!(written.timing.len(), 2);
+        assert_eq!(written.velocity.len(), 5);
+        assert_eq!(written.timing[1].meter, 7);
+        assert!(written.is_kiai(Time::from_ms(3218.0)));
+        assert!((written.velocity_at(Time::from_ms(6218.0)) - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn keeps_combo_flags() {
+        let beatmap = parser::try_parse(FIXTURES[0].1).unwrap();
+        let combos = beatmap.objects.iter().map(|x| x.combo).collect::<Vec<_>>();
+        assert_eq!(&combos[.. 6], &[4, 0, 0, 0, 0, 36]);
+
+        let written = write(&beatmap);
+        assert!(written.lines().any(|x| x == "256,192,3218,37,0,0:0:0:0:"));
+    }
+
+    #[test]
+    fn keeps_drumrolls_and_swells() {
+        let beatmap = parser::try_parse(FIXTURES[1].1).unwrap();
+        let written = parser::try_parse(&write(&beatmap)).unwrap();
+
+        let rolls = written.objects.iter().filter(|x| matches!(x.kind, TaikoKind::Drumroll { .. })).count();
+        let swells = written.objects.iter().filter(|x| matches!(x.kind, TaikoKind::Swell { .. })).count();
+        assert_eq!((rolls, swells), (5, 2));
+
+        for (a, b) in beatmap.objects.iter().zip(&written.objects) {
+            assert_eq!(a.end_time(), b.end_time());
+            assert_eq!(a.sample, b.sample);
+        }
+    }
+
+    #[test]
+    fn keeps_converts_as_standard() {
+        let beatmap = parser::try_parse(FIXTURES[2].1).unwrap();
+        assert!(beatmap.general.converted);
+
+        let written = write(&beatmap);
+        for line in ["Mode: 0", "SliderMultiplier:1.8", "StackLeniency: 0.5", "Combo1 : 255,128,0"] {
+            assert!(written.lines().any(|x| x == line), "missing `{line}`");
+        }
+
+        // Saving again must not speed the beatmap up
+        let written = parser::try_parse(&written).unwrap();
+        assert_eq!(written.difficulty.slider_multiplier, beatmap.difficulty.slider_multiplier);
+        assert_eq!(written.velocity_multiplier, beatmap.velocity_multiplier);
+    }
+
+    #[test]
+    fn keeps_converted_drumrolls() {
+        let beatmap = parser::try_parse(FIXTURES[2].1).unwrap();
+        let kinds = beatmap.objects.iter().map(|x| match x.kind {
+            TaikoKind::Circle         => 'c',
+            TaikoKind::Drumroll { .. } => 'd',
+            TaikoKind::Swell { .. }    => 's',
+        }).collect::<String>();
+
+        // The short slider is split into circles, the long ones become drumrolls
+        assert_eq!(kinds, "ccccccddscc");
+
+        let written = parser::try_parse(&write(&beatmap)).unwrap();
+        for (a, b) in beatmap.objects.iter().zip(&written.objects) {
+            assert_eq!((a.time, a.end_time()), (b.time, b.end_time()));
+        }
+    }
+
+    #[test]
+    fn explicit_convert_writes_taiko() {
+        let mut beatmap = parser::try_parse(FIXTURES[2].1).unwrap();
+        beatmap.general.converted = false;
+
+        let written = write(&beatmap);
+        assert!(written.lines().any(|x| x == "Mode: 1"));
+
+        // Objects keep their timing and scroll speed as a taiko beatmap
+        let written = parser::try_parse(&written).unwrap();
+        assert!(!written.general.converted);
+        assert_eq!(written.velocity_multiplier, beatmap.velocity_multiplier);
+        assert_eq!(written.objects.len(), beatmap.objects.len());
+        for (a, b) in beatmap.objects.iter().zip(&written.objects) {
+            assert_eq!((a.time, a.color, a.big), (b.time, b.color, b.big));
+            assert!((a.end_time().to_seconds() - b.end_time().to_seconds()).abs() < 1e-6);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use wcore::{graphics::{gui::{view::View, window::Window}, context::Graphics}};
 
 use crate::state::AppState;
 
-use super::window::file_dialog::FileDialogWindow;
+use super::window::{file_dialog::FileDialogWindow, save_dialog::SaveDialogWindow, performance::PerformanceWindow, checks::ChecksWindow, compare::CompareWindow, timing::TimingWindow, velocity::VelocityWindow, history::HistoryWindow, transform::TransformWindow, properties::PropertiesWindow};
 
 pub struct MenuView {}
 
@@ -13,9 +13,9 @@ impl MenuView {
     }
 }
 
-impl View<(&mut AppState, &mut FileDialogWindow)> for MenuView {
+impl View<(&mut AppState, &mut FileDialogWindow, &mut SaveDialogWindow, &mut PerformanceWindow, &mut ChecksWindow, &mut CompareWindow, &mut TimingWindow, &mut VelocityWindow, &mut HistoryWindow, &mut TransformWindow, &mut PropertiesWindow)> for MenuView {
     #[allow(unused_variables)]
-    fn show(&mut self, (state, file_dialog): (&mut AppState, &mut FileDialogWindow), view: &wgpu::TextureView, graphics: &mut Graphics, ctx: &egui::Context) {
+    fn show(&mut self, (state, file_dialog, save_dialog, performance, checks, compare, timing, velocity, history, transform, properties): (&mut AppState, &mut FileDialogWindow, &mut SaveDialogWindow, &mut PerformanceWindow, &mut ChecksWindow, &mut CompareWindow, &mut TimingWindow, &mut VelocityWindow, &mut HistoryWindow, &mut TransformWindow, &mut PropertiesWindow), view: &wgpu::TextureView, graphics: &mut Graphics, ctx: &egui::Context) {
         TopBottomPanel::top("menu").show(ctx, |ui| {
             menu::bar(ui, |ui| {
                 ui.menu_button("File", |ui| {
@@ -24,24 +24,131 @@ impl View<(&mut AppState, &mut FileDialogWindow)> for MenuView {
                         ui.close_menu();
                     }
 
+                    if ui.add_enabled(state.taiko_layer.beatmap.is_some(), egui::Button::new("Save as")).clicked() {
+                        save_dialog.set_visible(true);
+                        ui.close_menu();
+                    }
+
                     if ui.button("Close").clicked() {
                         state.taiko_layer.close_beatmap();
+                        state.mania_layer.close_beatmap();
+                        state.beatmap_set = None;
                         ui.close_menu();
                     }
                 });
                 
+                ui.menu_button("Edit", |ui| {
+                    if ui.add_enabled(state.taiko_layer.history.can_undo(), egui::Button::new("Undo")).clicked() {
+                        state.taiko_layer.undo(&mut state.taiko);
+                        ui.close_menu();
+                    }
+
+                    if ui.add_enabled(state.taiko_layer.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                        state.taiko_layer.redo(&mut state.taiko);
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    // Pasting needs the clipboard contents, which only Ctrl+V hands out
+                    let selected = !state.taiko_layer.editor.selection.is_empty();
+                    if ui.add_enabled(selected, egui::Button::new("Cut")).clicked() {
+                        if let Some(text) = state.taiko_layer.cut(&mut state.taiko) { ui.output_mut(|x| x.copied_text = text); }
+                        ui.close_menu();
+                    }
+
+                    if ui.add_enabled(selected, egui::Button::new("Copy")).clicked() {
+                        if let Some(text) = state.taiko_layer.copy() { ui.output_mut(|x| x.copied_text = text); }
+                        ui.close_menu();
+                    }
+
+                    if ui.add_enabled(selected, egui::Button::new("Delete")).clicked() {
+                        state.taiko_layer.delete_selection(&mut state.taiko);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Select all").clicked() {
+                        state.taiko_layer.select_all(&mut state.taiko);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Transform").clicked() {
+                        transform.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Beatmap properties").clicked() {
+                        properties.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui.button("History").clicked() {
+                        history.set_visible(true);
+                        ui.close_menu();
+                    }
+                });
+
+                let difficulties = state.beatmap_set.as_ref().map(|x| x.difficulties.len()).unwrap_or(0);
+                ui.add_enabled_ui(difficulties > 1, |ui| ui.menu_button("Difficulty", |ui| {
+                    let Some(set) = &state.beatmap_set else { return };
+                    let versions = set.difficulties.iter().map(|x| x.version.clone()).collect::<Vec<_>>();
+                    let active = set.active;
+
+                    for (idx, version) in versions.iter().enumerate() {
+                        if ui.radio(idx == active, version).clicked() {
+                            state.select_difficulty(idx);
+                            ui.close_menu();
+                        }
+                    }
+                }));
+
                 ui.menu_button("View", |ui| {
                     if ui.button(format!("{} Hit circles", if state.taiko.hit_circles { "✔" } else { "❌" })).clicked() {
                         state.taiko.hit_circles = !state.taiko.hit_circles;
                         ui.close_menu();
                     }
+
+                    if ui.button("Performance").clicked() {
+                        performance.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Checks").clicked() {
+                        checks.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Compare").clicked() {
+                        compare.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Timing").clicked() {
+                        timing.set_visible(true);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Scroll velocity").clicked() {
+                        velocity.set_visible(true);
+                        ui.close_menu();
+                    }
                 });
 
-                ui.with_layout(egui::Layout::default().with_cross_align(egui::Align::Max), |ui| {
+                let stars = state.beatmap_set.as_ref()
+                    .and_then(|x| x.difficulties.get(x.active))
+                    .and_then(|x| x.stars);
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     let value = ui.available_height();
                     if ui.add_sized(egui::vec2(value, value), egui::Button::new("⛭")).clicked() {
                         state.sidebar.shown = !state.sidebar.shown;
                     }
+
+                    if let Some(stars) = stars {
+                        ui.label(format!("{stars:.2}★"));
+                    }
                 });
             });
         });
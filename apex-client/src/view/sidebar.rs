@@ -32,6 +32,8 @@ impl View<&mut AppState> for SidebarView {
           .show_animated(ctx, state.sidebar.shown, |ui| {
             egui::ScrollArea::new([false, true]).show(ui, |ui| {
                 state.render_settings(ui);
+                ui.separator();
+                state.render_samples(ui);
             });
         });
     }
@@ -0,0 +1,85 @@
+use egui::Color32;
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::{state::AppState, taiko::lint::{self, Issue, Severity}};
+
+pub struct ChecksWindow {
+    open     : bool,
+
+    /// Rerun when the beatmap changes
+    issues   : Option<Vec<Issue>>,
+    revision : u64,
+}
+
+impl ChecksWindow {
+    pub fn new() -> Self {
+        return Self {
+            open     : false,
+            issues   : None,
+            revision : 0,
+        };
+    }
+}
+
+impl Window<&mut AppState> for ChecksWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Checks";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(true)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .default_size(egui::vec2(360.0, 240.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; self.issues = None; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let Some(beatmap) = &state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        if self.revision != state.taiko.revision {
+            self.revision = state.taiko.revision;
+            self.issues = None;
+        }
+
+        let issues = self.issues.get_or_insert_with(|| {
+            let set = state.beatmap_set.as_ref();
+            return lint::check(beatmap, |path| set.is_some_and(|x| x.has_file(path)));
+        });
+
+        if issues.is_empty() {
+            ui.label("No issues found");
+            return;
+        }
+
+        let mut seek = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for issue in issues.iter() {
+                let color = match issue.severity {
+                    Severity::Warning => Color32::from_rgb(230, 190, 80),
+                    Severity::Problem => Color32::from_rgb(230, 90, 80),
+                };
+
+                let text = egui::RichText::new(issue.to_string()).color(color);
+                let response = ui.selectable_label(false, text);
+                if response.clicked() && let Some(time) = issue.time {
+                    seek = Some(time);
+                }
+            }
+        });
+
+        // Clicking an issue jumps to it
+        if let Some(time) = seek {
+            state.taiko_layer.seek(&state.taiko, time);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use egui_file::FileDialog;
+use log::{info, error};
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::{state::AppState, taiko::{parser, diff::{self, Difference}}};
+
+pub struct CompareWindow {
+    open   : bool,
+    dialog : FileDialog,
+
+    /// File the loaded beatmap was compared against, and the changes towards it
+    result : Option<(String, Vec<Difference>)>,
+}
+
+impl CompareWindow {
+    pub fn new() -> Self {
+        return Self {
+            open   : false,
+            dialog : FileDialog::open_file(None),
+            result : None,
+        };
+    }
+}
+
+impl Window<&mut AppState> for CompareWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Compare";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(true)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .default_size(egui::vec2(360.0, 240.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let Some(beatmap) = &state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            if ui.button("Open .osu file").clicked() {
+                self.dialog.open();
+            }
+
+            if let Some((_, differences)) = &self.result && ui.button("Copy as text").clicked() {
+                ui.output_mut(|x| x.copied_text = diff::to_text(differences));
+            }
+        });
+
+        // Changes are listed from the loaded beatmap towards the chosen file
+        if self.dialog.show(ui.ctx()).selected() && let Some(path) = self.dialog.path() {
+            let result = std::fs::read_to_string(&path)
+                .map_err(|x| x.to_string())
+                .and_then(|data| parser::try_parse_lenient(&data).map_err(|x| x.to_string()));
+
+            match result {
+                Ok((other, _)) => {
+                    let differences = diff::diff(beatmap, &other);
+                    info!("Changes towards {}:\n{}", path.display(), diff::to_text(&differences));
+                    self.result = Some((path.display().to_string(), differences));
+                }
+
+                Err(error) => error!("Failed to load {}: {error}", path.display()),
+            }
+        }
+
+        let Some((filename, differences)) = &self.result else { return };
+        ui.label(format!("{} changes towards {filename}", differences.len()));
+        ui.separator();
+
+        let mut seek = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for difference in differences {
+                if ui.selectable_label(false, difference.to_string()).clicked() {
+                    seek = Some(difference.time);
+                }
+            }
+        });
+
+        if let Some(time) = seek {
+            state.taiko_layer.seek(&state.taiko, time);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::state::AppState;
+
+pub struct DifficultyPickerWindow {
+    open: bool,
+}
+
+impl DifficultyPickerWindow {
+    pub fn new() -> Self {
+        return Self {
+            open: false,
+        };
+    }
+}
+
+impl Window<&mut AppState> for DifficultyPickerWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Difficulties";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(false)
+            .resizable(false)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let Some(set) = &state.beatmap_set else {
+            ui.label("No beatmap loaded");
+            return;
+        };
+
+        let mut selected = None;
+        egui::Grid::new("difficulties")
+          .num_columns(4)
+          .spacing([24.0, 4.0])
+          .striped(true)
+          .show(ui, |ui| {
+            ui.strong("Version");
+            ui.strong("Objects");
+            ui.strong("Length");
+            ui.strong("Stars");
+            ui.end_row();
+
+            for (idx, difficulty) in set.difficulties.iter().enumerate() {
+                if ui.selectable_label(idx == set.active, &difficulty.version).clicked() {
+                    selected = Some(idx);
+                }
+
+                let length = difficulty.length.to_ms();
+                ui.label(difficulty.objects.to_string());
+                ui.label(format!("{:02}:{:02}", length / (60 * 1000), length / 1000 % 60));
+                ui.label(difficulty.stars.map(|x| format!("{x:.2}★")).unwrap_or_default());
+                ui.end_row();
+            }
+        });
+
+        if let Some(idx) = selected {
+            state.select_difficulty(idx);
+        }
+    }
+}
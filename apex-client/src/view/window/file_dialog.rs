@@ -1,10 +1,11 @@
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::{HashMap, HashSet}, io::Cursor};
 
 use async_zip::base::read::mem::ZipFileReader;
 use egui_file::FileDialog;
+use log::{warn, error};
 use wcore::{graphics::{gui::{view::View, window::Window}, context::Graphics}, audio::{AudioData, Hint}, clock::Clock};
 
-use crate::{state::AppState, taiko::parser};
+use crate::{state::AppState, taiko::{parser::{self, OsuBeatmap}, tja}};
 
 pub struct FileDialogWindow {
     open   : bool,
@@ -47,33 +48,116 @@ impl View<&mut AppState> for FileDialogWindow {
     fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ctx: &egui::Context) {
         if self.dialog.show(ctx).selected() {
             if let Some(path) = self.dialog.path() {
+                let data = match std::fs::read(&path) {
+                    Ok(data) => data,
+                    Err(error) => {
+                        error!("Failed to read {}: {error}", path.display());
+                        return;
+                    }
+                };
+
                 let mut files = HashMap::<String, Vec<u8>>::default();
-                pollster::block_on(async {
-                    let archive = std::fs::read(&path).unwrap();
-                    let archive = ZipFileReader::new(archive).await.unwrap();
-                    for i in 0 .. archive.file().entries().len() {
-                        let mut file = archive.entry(i).await.unwrap();
-                        let mut buffer = vec![];
-                        file.read_to_end_checked(&mut buffer, archive.file().entries()[i].entry()).await.unwrap();
-                        files.insert(archive.file().entries()[i].entry().filename().to_owned(), buffer);
+                let is_tja = path.extension().is_some_and(|x| x.eq_ignore_ascii_case("tja"));
+                if is_tja {
+                    let Some(filename) = path.file_name().map(|x| x.to_string_lossy().into_owned()) else {
+                        error!("Not a file: {}", path.display());
+                        return;
+                    };
+
+                    files.insert(filename, data);
+                } else {
+                    pollster::block_on(async {
+                        let archive = ZipFileReader::new(data).await.unwrap();
+                        for i in 0 .. archive.file().entries().len() {
+                            let mut file = archive.entry(i).await.unwrap();
+                            let mut buffer = vec![];
+                            file.read_to_end_checked(&mut buffer, archive.file().entries()[i].entry()).await.unwrap();
+                            files.insert(archive.file().entries()[i].entry().filename().to_owned(), buffer);
+                        }
+                    });
+                }
+
+                // Every difficulty is parsed, broken ones are skipped
+                let mut filenames = files.keys().filter(|x| x.ends_with(if is_tja { ".tja" } else { ".osu" })).cloned().collect::<Vec<_>>();
+                filenames.sort();
+
+                let mut beatmaps = Vec::<OsuBeatmap>::new();
+                for filename in filenames {
+                    let Some(data) = files.remove(&filename) else { continue };
+                    let data = match is_tja {
+                        true  => tja::decode(&data),
+                        false => match String::from_utf8(data) {
+                            Ok(data) => data,
+                            Err(_) => { error!("Failed to read {filename}: not valid UTF-8"); continue }
+                        },
+                    };
+
+                    let result = if is_tja {
+                        tja::try_parse_lenient(&data).map(|(courses, warnings)| (courses.into_iter().map(OsuBeatmap::Taiko).collect(), warnings))
+                    } else {
+                        parser::try_parse_osu_lenient(&data).map(|(beatmap, warnings)| (vec![beatmap], warnings))
+                    };
+
+                    match result {
+                        Ok((parsed, warnings)) => {
+                            for warning in warnings {
+                                warn!("{filename}: {warning}");
+                            }
+
+                            beatmaps.extend(parsed);
+                        }
+
+                        Err(error) => error!("Failed to parse {filename}: {error}"),
                     }
-                });
-
-                let filename = files.keys().find(|x| x.ends_with(".osu")).unwrap().clone();
-                let data = String::from_utf8(files.remove(&filename).unwrap()).unwrap();
-                
-                // Beatmap
-                let beatmap = parser::try_parse(&data).unwrap();
-                
-                let audio_filename = beatmap.audio.file_name().unwrap().to_str().unwrap();
-                let filename = files.keys().find(|x| *x == audio_filename).unwrap().clone();
-                let file = files.remove(&filename).unwrap();
+                }
+
+                if beatmaps.is_empty() {
+                    error!("No beatmap could be loaded from {}", path.display());
+                    return;
+                }
+
+                // Easiest first, osu! does the same using star rating
+                beatmaps.sort_by_key(|x| x.object_count());
+
+                let audio = beatmaps[0].audio().clone();
+                let Some(audio_filename) = audio.file_name().map(|x| x.to_string_lossy().into_owned()) else {
+                    error!("No audio file is set in {}", path.display());
+                    return;
+                };
+
+                if is_tja {
+                    // The audio file sits next to the chart
+                    match std::fs::read(path.with_file_name(&audio_filename)) {
+                        Ok(data) => { files.insert(audio_filename.clone(), data); }
+                        Err(error) => {
+                            error!("Failed to read {audio_filename}: {error}");
+                            return;
+                        }
+                    }
+                }
+
+                let archive_files = files.keys().map(|x| x.to_lowercase()).collect::<HashSet<_>>();
+                let Some(file) = files.remove(&audio_filename) else {
+                    error!("Audio file {audio_filename} is missing from {}", path.display());
+                    return;
+                };
+
                 let audio_file = Cursor::new(file);
-                state.taiko_layer.beatmap = Some(beatmap);
+
+                // Whatever audio is left are custom hit samples
+                let samples = files.into_iter()
+                    .filter(|(filename, _)| [".wav", ".ogg", ".mp3"].iter().any(|x| filename.to_lowercase().ends_with(x)))
+                    .map(|(filename, data)| (filename.to_lowercase(), data))
+                    .collect();
+
+                // Only one layer holds a beatmap at a time
+                state.taiko_layer.beatmap = None;
+                state.mania_layer.close_beatmap();
+                state.load_beatmap_set(beatmaps, samples, archive_files);
 
                 let audio_data = AudioData::new(
                     Box::new(audio_file),
-                    Hint::new().with_extension("mp3")
+                    Hint::new().with_extension(audio.extension().and_then(|x| x.to_str()).unwrap_or("mp3"))
                 ).unwrap();
 
                 state.taiko_layer.audio.play(&audio_data).unwrap();
@@ -82,9 +166,6 @@ impl View<&mut AppState> for FileDialogWindow {
                 state.taiko_layer.clock.set_time(0);
                 state.taiko_layer.clock.set_paused(true, 0);
                 state.taiko_layer.clock.set_length(state.taiko_layer.audio.length().as_millis() as u32);
-
-                // Build instances
-                state.taiko.rebuild_pending = true;
             }
         }
     }
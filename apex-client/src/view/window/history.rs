@@ -0,0 +1,80 @@
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::state::AppState;
+
+pub struct HistoryWindow {
+    open : bool,
+}
+
+impl HistoryWindow {
+    pub fn new() -> Self {
+        return Self {
+            open : false,
+        };
+    }
+}
+
+impl Window<&mut AppState> for HistoryWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "History";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(true)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .default_size(egui::vec2(240.0, 240.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.add_enabled(state.taiko_layer.history.can_undo(), egui::Button::new("Undo")).clicked() {
+                state.taiko_layer.undo(&mut state.taiko);
+            }
+
+            if ui.add_enabled(state.taiko_layer.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                state.taiko_layer.redo(&mut state.taiko);
+            }
+        });
+
+        ui.separator();
+
+        // Clicking an entry undoes or redoes everything up to and including it
+        let history = &state.taiko_layer.history;
+        let position = history.position();
+        let mut jump = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            // Once old commands are dropped, the oldest state is no longer the opened beatmap
+            let first = match history.dropped() {
+                0 => String::from("Opened beatmap"),
+                1 => String::from("1 older edit"),
+                dropped => format!("{dropped} older edits"),
+            };
+
+            if ui.selectable_label(position == 0, first).clicked() {
+                jump = Some(0);
+            }
+
+            for (idx, command) in history.commands().iter().enumerate() {
+                // Undone commands are greyed out
+                let mut text = egui::RichText::new(&command.name);
+                if idx >= position { text = text.weak(); }
+
+                if ui.selectable_label(position == idx + 1, text).clicked() {
+                    jump = Some(idx + 1);
+                }
+            }
+        });
+
+        if let Some(position) = jump {
+            state.taiko_layer.jump_to(&mut state.taiko, position);
+        }
+    }
+}
@@ -1,2 +1,12 @@
 pub mod timeline;
-pub mod file_dialog;
\ No newline at end of file
+pub mod file_dialog;
+pub mod save_dialog;
+pub mod difficulty_picker;
+pub mod performance;
+pub mod checks;
+pub mod compare;
+pub mod timing;
+pub mod velocity;
+pub mod history;
+pub mod transform;
+pub mod properties;
\ No newline at end of file
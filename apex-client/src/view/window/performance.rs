@@ -0,0 +1,104 @@
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::{state::AppState, taiko::performance::{self, Mods, PerformanceAttributes}};
+
+pub struct PerformanceWindow {
+    open     : bool,
+    mods     : Mods,
+    misses   : usize,
+
+    /// Recalculated when the settings or the beatmap change
+    table    : Option<Vec<(f64, PerformanceAttributes)>>,
+    revision : u64,
+}
+
+impl PerformanceWindow {
+    pub fn new() -> Self {
+        return Self {
+            open     : false,
+            mods     : Mods::default(),
+            misses   : 0,
+            table    : None,
+            revision : 0,
+        };
+    }
+}
+
+impl Window<&mut AppState> for PerformanceWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Performance";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(false)
+            .resizable(false)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let Some(beatmap) = &state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        let mods = self.mods;
+        let misses = self.misses;
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.mods.easy, "EZ");
+            ui.toggle_value(&mut self.mods.half_time, "HT");
+            ui.toggle_value(&mut self.mods.hidden, "HD");
+            ui.toggle_value(&mut self.mods.hard_rock, "HR");
+            ui.toggle_value(&mut self.mods.double_time, "DT");
+            ui.toggle_value(&mut self.mods.flashlight, "FL");
+        });
+
+        // Mutually exclusive mods, the one enabled last wins
+        if self.mods.easy && self.mods.hard_rock {
+            if mods.easy { self.mods.easy = false; } else { self.mods.hard_rock = false; }
+        }
+
+        if self.mods.half_time && self.mods.double_time {
+            if mods.half_time { self.mods.half_time = false; } else { self.mods.double_time = false; }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Misses");
+            ui.add(egui::DragValue::new(&mut self.misses).clamp_range(0 ..= usize::MAX));
+        });
+
+        if self.mods != mods || self.misses != misses || self.revision != state.taiko.revision {
+            self.revision = state.taiko.revision;
+            self.table = None;
+        }
+
+        let table = self.table.get_or_insert_with(|| performance::table(beatmap, self.mods, self.misses));
+        ui.separator();
+
+        egui::Grid::new("performance")
+          .num_columns(4)
+          .spacing([24.0, 4.0])
+          .striped(true)
+          .show(ui, |ui| {
+            ui.strong("Accuracy");
+            ui.strong("Total");
+            ui.strong("Difficulty");
+            ui.strong("Accuracy pp");
+            ui.end_row();
+
+            for (accuracy, attributes) in table.iter() {
+                ui.label(format!("{:.0}%", accuracy * 100.0));
+                ui.label(format!("{:.2}pp", attributes.total));
+                ui.label(format!("{:.2}", attributes.difficulty));
+                ui.label(format!("{:.2}", attributes.accuracy));
+                ui.end_row();
+            }
+        });
+    }
+}
@@ -0,0 +1,243 @@
+use wcore::{graphics::{gui::window::Window, context::Graphics}, time::Time};
+
+use crate::{state::AppState, taiko::{history::{Properties, Snapshot}, parser}};
+
+/// Ranges the osu! editor allows
+const DIFFICULTY_RANGE: std::ops::RangeInclusive<f32> = 0.0 ..= 10.0;
+const SLIDER_MULTIPLIER_RANGE: std::ops::RangeInclusive<f64> = 0.4 ..= 3.6;
+
+/// Difficulty names end up in file names, so these are left out
+const VERSION_FORBIDDEN: &[char] = &['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+type Check = fn(&str) -> Option<&'static str>;
+
+pub struct PropertiesWindow {
+    open   : bool,
+
+    /// Values as typed, only valid ones are written to the beatmap
+    draft  : Option<Properties>,
+    tags   : String,
+
+    /// Beatmap values the draft was taken from, changes made elsewhere (undo, loading) replace the draft
+    synced : Option<Properties>,
+}
+
+impl PropertiesWindow {
+    pub fn new() -> Self {
+        return Self {
+            open   : false,
+            draft  : None,
+            tags   : String::new(),
+            synced : None,
+        };
+    }
+}
+
+impl Window<&mut AppState> for PropertiesWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Beatmap properties";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(false)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let length = state.taiko_layer.get_length();
+        let now = Time::from_ms((state.taiko_layer.playhead(&state.taiko).to_seconds() * 1000.0).round().clamp(0.0, length as f64));
+        let Some(beatmap) = &mut state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        let current = Properties::of(beatmap);
+        if self.synced.as_ref() != Some(&current) {
+            self.tags = current.metadata.tags.join(" ");
+            self.draft = Some(current.clone());
+            self.synced = Some(current.clone());
+        }
+
+        let Some(draft) = &mut self.draft else { return };
+        let mut ended = false;
+
+        ui.strong("Metadata");
+        egui::Grid::new("properties_metadata")
+          .num_columns(3)
+          .spacing([8.0, 4.0])
+          .show(ui, |ui| {
+            let metadata = &mut draft.metadata;
+            ended |= text_row(ui, "Title", &mut metadata.title, romanized);
+            ended |= text_row(ui, "Title (unicode)", &mut metadata.title_unicode, anything);
+            ended |= text_row(ui, "Artist", &mut metadata.artist, romanized);
+            ended |= text_row(ui, "Artist (unicode)", &mut metadata.artist_unicode, anything);
+            ended |= text_row(ui, "Creator", &mut metadata.creator, romanized);
+            ended |= text_row(ui, "Difficulty name", &mut metadata.version, version);
+            ended |= text_row(ui, "Source", &mut metadata.source, anything);
+            ended |= text_row(ui, "Tags", &mut self.tags, anything);
+        });
+
+        ui.separator();
+        ui.strong("Difficulty");
+        egui::Grid::new("properties_difficulty")
+          .num_columns(2)
+          .spacing([8.0, 4.0])
+          .show(ui, |ui| {
+            // Converts are saved as osu!standard beatmaps until turned into taiko ones
+            if draft.general.converted {
+                ui.label("Mode");
+                ui.horizontal(|ui| {
+                    ui.label("osu!standard convert");
+                    if ui.button("Convert to taiko").on_hover_text("Saves the converted objects as a taiko beatmap").clicked() {
+                        draft.general.converted = false;
+                        ended = true;
+                    }
+                });
+                ui.end_row();
+            }
+
+            let general = &draft.general;
+            let difficulty = &mut draft.difficulty;
+            ui.label("HP drain");
+            let hp = ui.add(egui::DragValue::new(&mut difficulty.hp_drain_rate).speed(0.1).clamp_range(DIFFICULTY_RANGE).max_decimals(1));
+            ui.end_row();
+
+            ui.label("Overall difficulty");
+            let od = ui.add(egui::DragValue::new(&mut difficulty.overall_difficulty).speed(0.1).clamp_range(DIFFICULTY_RANGE).max_decimals(1));
+            ui.end_row();
+
+            // Edited as the file states it, converts scale it up when loading
+            ui.label("Slider multiplier");
+            let sm = ui.horizontal(|ui| {
+                let mut value = parser::file_slider_multiplier(general, difficulty);
+                let response = ui.add(egui::DragValue::new(&mut value).speed(0.01).max_decimals(2));
+                if response.changed() {
+                    difficulty.slider_multiplier = if general.converted { value * parser::LEGACY_VELOCITY_MULTIPLIER } else { value };
+                }
+
+                if let Some(problem) = slider_multiplier(value) {
+                    ui.colored_label(ui.visuals().error_fg_color, problem);
+                }
+
+                response
+            }).inner;
+            ui.end_row();
+
+            // Preview time has to be within the song
+            ui.label("Preview time");
+            let preview = ui.horizontal(|ui| {
+                let mut enabled = draft.general.preview_time.is_some();
+                if ui.checkbox(&mut enabled, "").changed() {
+                    draft.general.preview_time = enabled.then_some(now);
+                }
+
+                let mut preview_ms = draft.general.preview_time.map(|x| x.to_seconds() * 1000.0).unwrap_or(0.0);
+                let response = ui.add_enabled(enabled, egui::DragValue::new(&mut preview_ms).speed(10.0).clamp_range(0.0 ..= length as f64).max_decimals(0).suffix("ms"));
+                if response.changed() && enabled {
+                    draft.general.preview_time = Some(Time::from_ms(preview_ms.round()));
+                }
+
+                if ui.add_enabled(enabled, egui::Button::new("Use current time")).clicked() {
+                    draft.general.preview_time = Some(now);
+                }
+
+                response
+            }).inner;
+            ui.end_row();
+
+            for response in [hp, od, sm, preview] {
+                ended |= response.drag_released() || response.lost_focus();
+            }
+        });
+
+        // Invalid text stays in the draft, the beatmap keeps its last valid value
+        let mut next = current.clone();
+        let fields = [
+            (&mut next.metadata.title,          &draft.metadata.title,          romanized as Check),
+            (&mut next.metadata.title_unicode,  &draft.metadata.title_unicode,  anything),
+            (&mut next.metadata.artist,         &draft.metadata.artist,         romanized),
+            (&mut next.metadata.artist_unicode, &draft.metadata.artist_unicode, anything),
+            (&mut next.metadata.creator,        &draft.metadata.creator,        romanized),
+            (&mut next.metadata.version,        &draft.metadata.version,        version),
+            (&mut next.metadata.source,         &draft.metadata.source,         anything),
+        ];
+
+        for (value, typed, check) in fields {
+            if check(typed).is_none() { value.clone_from(typed); }
+        }
+
+        next.metadata.tags = self.tags.split_whitespace().map(String::from).collect();
+        next.difficulty = draft.difficulty.clone();
+        if slider_multiplier(parser::file_slider_multiplier(&draft.general, &draft.difficulty)).is_some() {
+            next.difficulty.slider_multiplier = current.difficulty.slider_multiplier;
+        }
+
+        next.general.preview_time = draft.general.preview_time;
+        next.general.converted = draft.general.converted;
+        next.velocity_multiplier = next.difficulty.slider_multiplier as f32;
+
+        if next != current {
+            let before = Snapshot::properties(beatmap);
+            next.set(beatmap);
+            state.taiko_layer.history.record("Edit properties", true, before, beatmap);
+
+            // Objects are placed with the slider multiplier baked in
+            state.taiko_layer.edited(&mut state.taiko);
+
+            // Keeps the difficulty menu in sync
+            if let Some(set) = &mut state.beatmap_set && let Some(difficulty) = set.difficulties.get_mut(set.active) {
+                difficulty.version.clone_from(&next.metadata.version);
+            }
+
+            self.synced = Some(next);
+        }
+
+        if ended {
+            state.taiko_layer.history.seal();
+        }
+    }
+}
+
+/// Text field with the reason its value is refused next to it, returns whether editing ended
+fn text_row(ui: &mut egui::Ui, label: &str, value: &mut String, check: Check) -> bool {
+    ui.label(label);
+    let response = ui.text_edit_singleline(value);
+    match check(value) {
+        Some(problem) => ui.colored_label(ui.visuals().error_fg_color, problem),
+        None          => ui.label(""),
+    };
+
+    ui.end_row();
+    return response.lost_focus();
+}
+
+fn romanized(text: &str) -> Option<&'static str> {
+    if text.trim().is_empty() { return Some("can't be empty") }
+    if !text.chars().all(|x| x.is_ascii() && !x.is_ascii_control()) { return Some("only allows ASCII, the unicode field takes the rest") }
+    return None;
+}
+
+fn version(text: &str) -> Option<&'static str> {
+    if text.trim().is_empty() { return Some("can't be empty") }
+    if text.trim() != text { return Some("can't start or end with spaces") }
+    if text.contains(VERSION_FORBIDDEN) || text.chars().any(char::is_control) { return Some("can't contain \\ / : * ? \" < > |") }
+    return None;
+}
+
+/// Checks the value as written to the file, converts can be a little off from dividing theirs back
+fn slider_multiplier(value: f64) -> Option<&'static str> {
+    if !SLIDER_MULTIPLIER_RANGE.contains(&((value * 1e6).round() / 1e6)) { return Some("has to be between 0.4 and 3.6") }
+    return None;
+}
+
+fn anything(_: &str) -> Option<&'static str> {
+    return None;
+}
@@ -0,0 +1,82 @@
+use egui_file::FileDialog;
+use log::{info, warn, error};
+use wcore::graphics::{gui::{view::View, window::Window}, context::Graphics};
+
+use crate::{state::AppState, taiko::{writer, tja_writer, lint::SNAP_DIVISORS}};
+
+pub struct SaveDialogWindow {
+    open    : bool,
+    dialog  : FileDialog,
+
+    /// Subdivisions per beat TJA charts are quantized to
+    divisor : u32,
+}
+
+impl SaveDialogWindow {
+    pub fn new() -> Self {
+        return Self {
+            open    : false,
+            dialog  : FileDialog::save_file(None),
+            divisor : tja_writer::DEFAULT_DIVISOR,
+        };
+    }
+}
+
+impl Window<()> for SaveDialogWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Save beatmap";
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; if value { self.dialog.open(); } }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: (), view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) { }
+}
+
+// Hand-rolling a window view impl
+impl View<&mut AppState> for SaveDialogWindow {
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ctx: &egui::Context) {
+        // Only used for TJA charts, shown next to the dialog while it is open
+        if self.dialog.show(ctx).visible() {
+            egui::Window::new("TJA export")
+              .collapsible(false)
+              .resizable(false)
+              .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 32.0))
+              .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Subdivision");
+                    egui::ComboBox::from_id_source("tja_divisor")
+                      .width(48.0)
+                      .selected_text(format!("1/{}", self.divisor))
+                      .show_ui(ui, |ui| {
+                        for divisor in SNAP_DIVISORS {
+                            ui.selectable_value(&mut self.divisor, divisor, format!("1/{divisor}"));
+                        }
+                    });
+                }).response.on_hover_text("Objects off this grid are moved to it and reported");
+            });
+        }
+
+        if self.dialog.selected()
+        && let Some(path) = self.dialog.path()
+        && let Some(beatmap) = &state.taiko_layer.beatmap {
+            // TJA charts can't represent everything, the rest is quantized and reported
+            let data = if path.extension().is_some_and(|x| x.eq_ignore_ascii_case("tja")) {
+                let (data, warnings) = tja_writer::write(beatmap, self.divisor);
+                for warning in warnings {
+                    warn!("{}: {warning}", path.display());
+                }
+
+                data
+            } else { writer::write(beatmap) };
+
+            match std::fs::write(&path, data) {
+                Ok(_)      => info!("Saved beatmap to {}", path.display()),
+                Err(error) => error!("Failed to save beatmap to {}: {error}", path.display()),
+            }
+        }
+    }
+}
@@ -1,23 +1,36 @@
-use egui::{Align2, vec2, Button, Slider};
-use wcore::graphics::{gui::window::Window, context::Graphics};
+use egui::{Align2, vec2, Button, Slider, Color32, Sense, Stroke, pos2, Rect};
+use wcore::{graphics::{gui::window::Window, context::Graphics}, time::Time, color::Color};
 
-use crate::layer::taiko::TaikoLayer;
+use crate::{layer::taiko::{TaikoLayer, TaikoState}, taiko::{lint::SNAP_DIVISORS, taiko_circle::{TaikoColor, TaikoKind}}};
 
 const OFFSET: f32 = 12.0;
 
+/// Height of the strip with the grid and objects
+const STRIP_HEIGHT: f32 = 48.0;
+
+const SELECTED_COLOR: Color32 = Color32::from_rgb(255, 200, 60);
+
 pub struct TimelineWindow {
-    was_playing: bool,
+    was_playing : bool,
+
+    /// Amount of time visible in the strip
+    span_ms     : f64,
+
+    /// Time a range selection in the strip started at
+    range_start : Option<f64>,
 }
 
 impl TimelineWindow {
     pub fn new() -> Self {
         return Self {
-            was_playing: false,
+            was_playing : false,
+            span_ms     : 4000.0,
+            range_start : None,
         };
     }
 }
 
-impl Window<&mut TaikoLayer> for TimelineWindow {
+impl Window<(&mut TaikoLayer, &mut TaikoState)> for TimelineWindow {
     type Title = &'static str;
     fn title() -> Self::Title {
         return "Timeline";
@@ -36,12 +49,12 @@ impl Window<&mut TaikoLayer> for TimelineWindow {
     }
 
     #[allow(unused_variables)]
-    fn show(&mut self, state: &mut TaikoLayer, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+    fn show(&mut self, (state, settings): (&mut TaikoLayer, &mut TaikoState), view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
         let time = state.get_time().to_ms();
         let length = state.get_length();
-        
+
         ui.horizontal(|ui| {
-            ui.set_enabled(state.beatmap.is_some());
+            ui.set_enabled(length > 0);
 
             // Play button
             let play_button_text = if state.is_paused() { "▶" } else { "⏸" };
@@ -50,11 +63,28 @@ impl Window<&mut TaikoLayer> for TimelineWindow {
                 state.toggle_paused();
             };
 
+            // Record button, drum keys place objects while it's on
+            let record_text = egui::RichText::new("⏺").color(if state.editor.recording { Color32::from_rgb(230, 60, 60) } else { ui.visuals().text_color() });
+            let record_button = ui.add_sized(vec2(24.0, ui.available_height()), Button::new(record_text));
+            if record_button.on_hover_text("Record taps (R), D/K for kat and F/J for don").clicked() {
+                state.toggle_recording(settings);
+            }
+
             // Time display
             ui.label(&format!("{:02}:{:02}:{:03} / {:02}:{:02}:{:03}",
                   time / (60 * 1000),   time / 1000 % 60,   time % 1000,
                 length / (60 * 1000), length / 1000 % 60, length % 1000));
 
+            // Snap divisor
+            egui::ComboBox::from_id_source("timeline_snap")
+              .width(48.0)
+              .selected_text(format!("1/{}", settings.snap_divisor))
+              .show_ui(ui, |ui| {
+                for divisor in SNAP_DIVISORS {
+                    ui.selectable_value(&mut settings.snap_divisor, divisor, format!("1/{divisor}"));
+                }
+            });
+
             // Time slider
             let slider_width = ui.available_width();
             let style = ui.style_mut();
@@ -62,7 +92,7 @@ impl Window<&mut TaikoLayer> for TimelineWindow {
 
             let mut time64 = time;
             let slider = Slider::new(&mut time64, 0 ..= (length as u64)).show_value(false);
-            let slider = ui.add(slider);               
+            let slider = ui.add(slider);
 
             if slider.drag_started() {
                 self.was_playing = state.is_paused();
@@ -73,9 +103,139 @@ impl Window<&mut TaikoLayer> for TimelineWindow {
                 state.set_time(time64 as u32);
             }
 
-            if slider.drag_released() && state.beatmap.is_some() {
+            if slider.drag_released() && length > 0 {
                 state.set_paused(self.was_playing);
             }
-        }); 
+        });
+
+        self.show_strip(state, settings, ui);
+    }
+}
+
+impl TimelineWindow {
+    /// Grid, objects and the playhead around the current time
+    fn show_strip(&mut self, state: &mut TaikoLayer, settings: &mut TaikoState, ui: &mut egui::Ui) {
+        let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), STRIP_HEIGHT), Sense::click_and_drag());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let now = state.get_time().to_seconds() * 1000.0 - settings.audio_offset as f64;
+        let Some(beatmap) = &state.beatmap else { return };
+
+        let (start, end) = (now - self.span_ms / 2.0, now + self.span_ms / 2.0);
+        let x_at = |time: f64| rect.left() + ((time - start) / self.span_ms) as f32 * rect.width();
+        let time_at = |x: f32| start + ((x - rect.left()) / rect.width()) as f64 * self.span_ms;
+
+        // Ticks of every timing point in view, each one stops where the next begins
+        let divisor = settings.snap_divisor.max(1);
+        for (idx, point) in beatmap.timing.iter().enumerate() {
+            let origin = point.time.to_seconds() * 1000.0;
+            let limit = beatmap.timing.get(idx + 1).map(|x| x.time.to_seconds() * 1000.0).unwrap_or(f64::INFINITY).min(end);
+            let step = point.beat_length() / divisor as f64;
+            if !step.is_finite() || step <= 0.0 || limit < start { continue }
+
+            let mut tick = ((start.max(origin) - origin) / step).ceil() as i64;
+            loop {
+                let time = origin + tick as f64 * step;
+                if time >= limit { break }
+
+                let (color, height) = tick_style(tick, divisor, point.meter.max(1));
+                let x = x_at(time);
+                painter.line_segment([pos2(x, rect.bottom()), pos2(x, rect.bottom() - rect.height() * height)], Stroke::new(1.0, color));
+                tick += 1;
+            }
+        }
+
+        // Objects, drawn back to front so earlier ones stay on top like on the conveyor
+        let center = rect.center().y;
+        let first = beatmap.objects.partition_point(|x| x.end_time().to_seconds() * 1000.0 < start);
+        let visible = beatmap.objects[first ..].iter().take_while(|x| x.time.to_seconds() * 1000.0 <= end).count();
+        for (idx, object) in beatmap.objects.iter().enumerate().skip(first).take(visible).rev() {
+            let x = x_at(object.time.to_seconds() * 1000.0);
+            let radius = if object.big { 7.0 } else { 5.0 };
+            let outline = if state.editor.selection.binary_search(&idx).is_ok() { Stroke::new(2.0, SELECTED_COLOR) } else { Stroke::new(1.0, Color32::WHITE) };
+            match object.kind {
+                TaikoKind::Circle => {
+                    let color = if object.color == TaikoColor::KAT { settings.kat_color } else { settings.don_color };
+                    painter.circle(pos2(x, center), radius, to_color32(color), outline);
+                }
+
+                TaikoKind::Drumroll { .. } | TaikoKind::Swell { .. } => {
+                    let color = if matches!(object.kind, TaikoKind::Drumroll { .. }) { settings.roll_color } else { settings.swell_color };
+                    let end_x = x_at(object.end_time().to_seconds() * 1000.0);
+                    let body = Rect::from_min_max(pos2(x, center - radius), pos2(end_x.max(x), center + radius));
+                    painter.rect_filled(body, radius, to_color32(color));
+                    painter.circle(pos2(x, center), radius, to_color32(color), outline);
+                }
+            }
+        }
+
+        // Playhead
+        let x = x_at(now);
+        painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], Stroke::new(2.0, Color32::from_rgb(230, 90, 80)));
+
+        // Range being selected
+        let pointer = response.interact_pointer_pos().map(|x| time_at(x.x));
+        if response.drag_started() {
+            self.range_start = pointer;
+        }
+
+        if let Some(from) = self.range_start && let Some(to) = pointer {
+            let range = Rect::from_x_y_ranges(x_at(from.min(to)) ..= x_at(from.max(to)), rect.y_range());
+            painter.rect_filled(range, 0.0, SELECTED_COLOR.linear_multiply(0.15));
+        }
+
+        // Clicking seeks to the closest tick, dragging selects the objects in between, ctrl adds to the selection
+        if response.clicked() && let Some(pointer) = pointer {
+            let time = beatmap.snap(Time::from_ms(pointer), divisor);
+            state.seek(settings, time);
+        }
+
+        // Presses count as drags right away in egui, a short one is still a click
+        if response.drag_released() && let Some(from) = self.range_start.take() && let Some(to) = pointer && !response.clicked() {
+            let add = ui.input(|x| x.modifiers.command);
+            state.select_range(settings, Time::from_ms(from.min(to)), Time::from_ms(from.max(to)), add);
+        }
+
+        // Scrolling steps through the ticks, ctrl zooms instead
+        if response.hovered() {
+            let (scroll, zoom) = ui.input(|x| (x.scroll_delta.y, x.modifiers.command));
+            if zoom && scroll != 0.0 {
+                self.span_ms = (self.span_ms * if scroll > 0.0 { 0.8 } else { 1.25 }).clamp(500.0, 60_000.0);
+            } else if scroll > 0.0 {
+                state.timeline_move_back(settings, 1.0);
+            } else if scroll < 0.0 {
+                state.timeline_move_forward(settings, 1.0);
+            }
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Color and relative height of a tick, `tick` counts 1/`divisor` beats from the timing point
+fn tick_style(tick: i64, divisor: u32, meter: u32) -> (Color32, f32) {
+    let divisor = divisor as i64;
+    if tick.rem_euclid(divisor) == 0 {
+        let beat = tick.div_euclid(divisor);
+        return if beat.rem_euclid(meter as i64) == 0 { (Color32::WHITE, 1.0) } else { (Color32::from_gray(200), 0.7) };
+    }
+
+    // Colors of the reduced fraction, same as the osu! editor
+    let fraction = divisor / gcd(tick.rem_euclid(divisor), divisor);
+    return match fraction {
+        2  => (Color32::from_rgb(230, 80, 80), 0.55),
+        3  => (Color32::from_rgb(180, 110, 230), 0.5),
+        4  => (Color32::from_rgb(80, 140, 230), 0.45),
+        6  => (Color32::from_rgb(230, 160, 230), 0.4),
+        8  => (Color32::from_rgb(230, 200, 80), 0.35),
+        12 => (Color32::from_rgb(150, 150, 150), 0.3),
+        _  => (Color32::from_gray(110), 0.25),
+    };
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    return if b == 0 { a } else { gcd(b, a % b) };
+}
+
+fn to_color32(color: Color) -> Color32 {
+    return Color32::from_rgb((color.r * 255.0).round() as u8, (color.g * 255.0).round() as u8, (color.b * 255.0).round() as u8);
+}
@@ -0,0 +1,211 @@
+use wcore::{graphics::{gui::window::Window, context::Graphics}, time::Time};
+
+use crate::{state::AppState, taiko::{parser::TimingPoint, history::Snapshot}};
+
+/// Taps further apart than this start a new measurement
+const TAP_RESET_SECONDS: f64 = 2.0;
+
+pub struct TimingWindow {
+    open     : bool,
+    selected : Option<usize>,
+
+    /// Times of the latest taps in seconds
+    taps     : Vec<f64>,
+}
+
+impl TimingWindow {
+    pub fn new() -> Self {
+        return Self {
+            open     : false,
+            selected : None,
+            taps     : vec![],
+        };
+    }
+
+    /// Average of the tapped intervals
+    fn tapped_bpm(&self) -> Option<f64> {
+        let (first, last) = (self.taps.first()?, self.taps.last()?);
+        if self.taps.len() < 2 { return None }
+        return Some(60.0 * (self.taps.len() - 1) as f64 / (last - first));
+    }
+}
+
+impl Window<&mut AppState> for TimingWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Timing";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(true)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let now = state.taiko_layer.get_time().to_seconds() * 1000.0 - state.taiko.audio_offset as f64;
+        let Some(beatmap) = &mut state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        // Taken right before the first change, nothing is copied while the window just sits there
+        let mut before = None::<Snapshot>;
+        let mut edit = None::<(&str, bool)>;
+        let mut resort = false;
+
+        ui.horizontal(|ui| {
+            // New points continue the active one
+            if ui.button("Add at current time").clicked() {
+                let timing = &beatmap.timing;
+                let active = timing.iter().rev().find(|x| x.time.to_seconds() * 1000.0 <= now).or(timing.first());
+                let point = TimingPoint {
+                    time         : Time::from_ms(now.round()),
+                    bpm          : active.map(|x| x.bpm).unwrap_or(120.0),
+                    meter        : active.map(|x| x.meter).unwrap_or(4),
+                    sample_set   : active.map(|x| x.sample_set).unwrap_or(0),
+                    sample_index : active.map(|x| x.sample_index).unwrap_or(0),
+                    volume       : active.map(|x| x.volume).unwrap_or(100),
+                    effects      : 0,
+                };
+
+                before.get_or_insert_with(|| Snapshot::timing(beatmap));
+                let idx = beatmap.timing.partition_point(|x| x.time <= point.time);
+                beatmap.timing.insert(idx, point);
+                self.selected = Some(idx);
+                edit = Some(("Add timing point", false));
+            }
+
+            let selected = self.selected.filter(|x| *x < beatmap.timing.len());
+            if ui.add_enabled(selected.is_some(), egui::Button::new("Remove")).clicked() && let Some(idx) = selected {
+                before.get_or_insert_with(|| Snapshot::timing(beatmap));
+                beatmap.timing.remove(idx);
+                self.selected = None;
+                edit = Some(("Remove timing point", false));
+            }
+        });
+
+        ui.separator();
+        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            egui::Grid::new("timing_points")
+              .num_columns(4)
+              .spacing([16.0, 4.0])
+              .striped(true)
+              .show(ui, |ui| {
+                ui.strong("#");
+                ui.strong("Offset");
+                ui.strong("BPM");
+                ui.strong("Meter");
+                ui.end_row();
+
+                // Widgets edit copies, which are written back once something changed
+                for idx in 0 .. beatmap.timing.len() {
+                    if ui.selectable_label(self.selected == Some(idx), (idx + 1).to_string()).clicked() {
+                        self.selected = Some(idx);
+                    }
+
+                    let point = &beatmap.timing[idx];
+                    let original = point.time.to_seconds() * 1000.0;
+                    let (mut offset, mut bpm, mut meter) = (original, point.bpm, point.meter);
+
+                    let offset_response = ui.add(egui::DragValue::new(&mut offset).speed(1.0).max_decimals(0).suffix("ms"));
+                    let bpm_response = ui.add(egui::DragValue::new(&mut bpm).speed(0.1).clamp_range(1.0 ..= 10_000.0).max_decimals(3));
+                    let meter_response = ui.add(egui::DragValue::new(&mut meter).clamp_range(1 ..= 16).suffix("/4"));
+                    for response in [offset_response, bpm_response, meter_response] {
+                        if response.changed() {
+                            edit = Some(("Edit timing point", true));
+                            before.get_or_insert_with(|| Snapshot::timing(beatmap));
+
+                            let point = &mut beatmap.timing[idx];
+                            if offset != original { point.time = Time::from_ms(offset.round()); }
+                            point.bpm = bpm;
+                            point.meter = meter;
+                        }
+
+                        // Order is only restored once editing is done, so rows don't jump around while dragging.
+                        // That is also where the edit ends in the history.
+                        if response.drag_released() || response.lost_focus() {
+                            resort = true;
+                        }
+                    }
+
+                    ui.end_row();
+                }
+            });
+        });
+
+        // Offset nudging, works while the song plays
+        ui.separator();
+        let selected = self.selected.filter(|x| *x < beatmap.timing.len());
+        ui.add_enabled_ui(selected.is_some(), |ui| ui.horizontal(|ui| {
+            ui.label("Nudge");
+            for delta in [-10.0, -1.0, 1.0, 10.0] {
+                if ui.button(format!("{delta:+}ms")).clicked() && let Some(idx) = selected {
+                    before.get_or_insert_with(|| Snapshot::timing(beatmap));
+                    let point = &mut beatmap.timing[idx];
+                    point.time = Time::from_ms(point.time.to_seconds() * 1000.0 + delta);
+                    edit = Some(("Nudge timing point", false));
+                    resort = true;
+                }
+            }
+        }));
+
+        // Tap to BPM, taps are timed with the UI clock
+        ui.horizontal(|ui| {
+            if ui.button("Tap").clicked() {
+                let time = ui.input(|x| x.time);
+                if self.taps.last().is_some_and(|x| time - x > TAP_RESET_SECONDS) {
+                    self.taps.clear();
+                }
+
+                self.taps.push(time);
+            }
+
+            match self.tapped_bpm() {
+                Some(bpm) => ui.label(format!("{bpm:.2}bpm ({} taps)", self.taps.len())),
+                None      => ui.label("Tap along to the beat"),
+            };
+
+            let bpm = self.tapped_bpm();
+            if ui.add_enabled(bpm.is_some() && selected.is_some(), egui::Button::new("Apply")).clicked()
+            && let Some(bpm) = bpm && let Some(idx) = selected {
+                before.get_or_insert_with(|| Snapshot::timing(beatmap));
+                beatmap.timing[idx].bpm = (bpm * 100.0).round() / 100.0;
+                edit = Some(("Apply tapped BPM", false));
+            }
+
+            if ui.button("Reset").clicked() {
+                self.taps.clear();
+            }
+        });
+
+        if resort {
+            before.get_or_insert_with(|| Snapshot::timing(beatmap));
+            let timing = &mut beatmap.timing;
+            let selected = self.selected.and_then(|x| timing.get(x)).map(|x| (x.time, x.bpm));
+            timing.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+            self.selected = selected.and_then(|(time, bpm)| timing.iter().position(|x| x.time == time && x.bpm == bpm));
+        }
+
+        // Sorting once a drag ends belongs to the same step in the history
+        if resort && edit.is_none() {
+            edit = Some(("Edit timing point", true));
+        }
+
+        // Barlines and scroll positions depend on timing
+        if let Some((name, merge)) = edit && let Some(before) = before {
+            state.taiko_layer.history.record(name, merge, before, beatmap);
+            state.taiko_layer.edited(&mut state.taiko);
+        }
+
+        if resort {
+            state.taiko_layer.history.seal();
+        }
+    }
+}
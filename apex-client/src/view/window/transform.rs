@@ -0,0 +1,81 @@
+use wcore::graphics::{gui::window::Window, context::Graphics};
+
+use crate::{state::AppState, taiko::transform::Transform};
+
+pub struct TransformWindow {
+    open    : bool,
+    ratio   : f64,
+
+    /// Shown when the last transform was refused
+    refused : bool,
+}
+
+impl TransformWindow {
+    pub fn new() -> Self {
+        return Self {
+            open    : false,
+            ratio   : 2.0,
+            refused : false,
+        };
+    }
+}
+
+impl Window<&mut AppState> for TransformWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Transform";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(false)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let selected = state.taiko_layer.editor.selection.len();
+        ui.label(format!("{selected} objects selected"));
+        ui.separator();
+
+        let mut transform = None;
+        ui.add_enabled_ui(selected > 0, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Swap don/kat").clicked() { transform = Some(Transform::SwapColors); }
+                if ui.button("Make big").clicked()     { transform = Some(Transform::Big(true)); }
+                if ui.button("Make small").clicked()   { transform = Some(Transform::Big(false)); }
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Reverse").clicked() { transform = Some(Transform::Reverse); }
+                if ui.button("Mirror around playhead").clicked() {
+                    transform = Some(Transform::Mirror(state.taiko_layer.playhead(&state.taiko)));
+                }
+            });
+
+            // Distances from the first selected object, snapped to the current divisor
+            ui.horizontal(|ui| {
+                ui.label("Scale");
+                ui.add(egui::DragValue::new(&mut self.ratio).speed(0.01).clamp_range(0.1 ..= 10.0).max_decimals(3).prefix("×"));
+                for (text, ratio) in [("½", 0.5), ("¾", 0.75), ("1½", 1.5), ("2", 2.0)] {
+                    if ui.small_button(text).clicked() { self.ratio = ratio; }
+                }
+
+                if ui.button("Apply").clicked() { transform = Some(Transform::Scale(self.ratio)); }
+            });
+        });
+
+        if let Some(transform) = transform {
+            self.refused = !state.taiko_layer.transform_selection(&mut state.taiko, transform);
+        }
+
+        if self.refused && selected > 0 {
+            ui.colored_label(ui.visuals().warn_fg_color, "Objects would overlap, nothing was changed");
+        }
+    }
+}
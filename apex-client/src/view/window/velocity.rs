@@ -0,0 +1,286 @@
+use egui::{Color32, plot::{Plot, Line, Points, VLine, PlotPoints, MarkerShape}};
+use wcore::{graphics::{gui::window::Window, context::Graphics}, time::Time};
+
+use crate::{state::AppState, taiko::{lint::SNAP_DIVISORS, velocity::{self, Interpolation}, history::Snapshot}};
+
+/// Points further than this from the pointer can't be grabbed, in pixels
+const GRAB_DISTANCE: f32 = 8.0;
+
+const MIN_VELOCITY: f64 = 0.01;
+const MAX_VELOCITY: f64 = 10.0;
+
+pub struct VelocityWindow {
+    open     : bool,
+    selected : Option<usize>,
+    dragging : Option<usize>,
+    divisor  : u32,
+
+    // Interpolation tool
+    start         : f64, // ms
+    end           : f64, // ms
+    from          : f64,
+    to            : f64,
+    interpolation : Interpolation,
+}
+
+impl VelocityWindow {
+    pub fn new() -> Self {
+        return Self {
+            open     : false,
+            selected : None,
+            dragging : None,
+            divisor  : 4,
+
+            start         : 0.0,
+            end           : 0.0,
+            from          : 1.0,
+            to            : 2.0,
+            interpolation : Interpolation::Linear,
+        };
+    }
+}
+
+impl Window<&mut AppState> for VelocityWindow {
+    type Title = &'static str;
+    fn title() -> Self::Title {
+        return "Scroll velocity";
+    }
+
+    fn build<'b>(window: egui::Window<'b>, _ctx: &'_ egui::Context) -> egui::Window<'b> {
+        window
+            .collapsible(true)
+            .resizable(true)
+            .default_pos(egui::pos2(8.0, 32.0))
+            .default_size(egui::vec2(480.0, 360.0))
+            .title_bar(true)
+    }
+
+    fn set_visible(&mut self, value: bool) { self.open = value; }
+    fn get_visible(&self) -> bool { return self.open; }
+
+    #[allow(unused_variables)]
+    fn show(&mut self, state: &mut AppState, view: &wgpu::TextureView, graphics: &mut Graphics, ui: &mut egui::Ui) {
+        let now = state.taiko_layer.get_time().to_seconds() * 1000.0 - state.taiko.audio_offset as f64;
+        let Some(beatmap) = &mut state.taiko_layer.beatmap else {
+            ui.label("No taiko beatmap loaded");
+            return;
+        };
+
+        // Taken right before the first change, nothing is copied while the window just sits there
+        let mut before = None::<Snapshot>;
+        let mut edit = None::<(&str, bool)>;
+        let mut resort = false;
+
+        ui.horizontal(|ui| {
+            ui.label("Snap");
+            egui::ComboBox::from_id_source("velocity_snap")
+                .selected_text(format!("1/{}", self.divisor))
+                .show_ui(ui, |ui| {
+                    for divisor in SNAP_DIVISORS {
+                        ui.selectable_value(&mut self.divisor, divisor, format!("1/{divisor}"));
+                    }
+                });
+
+            if ui.button("Add at current time").clicked() {
+                let time = beatmap.snap(Time::from_ms(now), self.divisor);
+                let velocity = beatmap.velocity_at(time);
+                let point = velocity::point_at(beatmap, time, velocity);
+                before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+                self.selected = Some(velocity::insert(beatmap, point));
+                edit = Some(("Add velocity point", false));
+            }
+        });
+
+        // Velocity over time, uninherited timing points reset it back to 1.0
+        let mut times = beatmap.velocity.iter().map(|x| x.time.to_seconds())
+            .chain(beatmap.timing.iter().map(|x| x.time.to_seconds()))
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.total_cmp(b));
+
+        let end = beatmap.objects.last().map(|x| x.end_time().to_seconds()).unwrap_or(0.0);
+        let mut steps = vec![];
+        let mut previous = 1.0;
+        for time in times.iter().copied().chain([end]) {
+            let velocity = beatmap.velocity_at(Time::from_ms(time * 1000.0));
+            steps.push([time, previous]);
+            steps.push([time, velocity]);
+            previous = velocity;
+        }
+
+        let points = beatmap.velocity.iter().map(|x| [x.time.to_seconds(), x.velocity]).collect::<Vec<_>>();
+        let selected = self.selected.and_then(|x| points.get(x)).copied();
+        let response = Plot::new("velocity_graph")
+            .height(200.0)
+            .allow_drag(false)
+            .allow_double_click_reset(true)
+            .include_y(0.0)
+            .include_y(2.0)
+            .show(ui, |plot| {
+                plot.line(Line::new(PlotPoints::new(steps)).color(Color32::from_rgb(120, 180, 240)));
+                plot.points(Points::new(points.clone()).radius(3.0).color(Color32::from_rgb(240, 240, 240)));
+                if let Some(point) = selected {
+                    plot.points(Points::new(vec![point]).radius(5.0).shape(MarkerShape::Diamond).color(Color32::from_rgb(240, 190, 80)));
+                }
+
+                plot.vline(VLine::new(now / 1000.0).color(Color32::from_rgb(230, 90, 80)));
+
+                // Closest point under the pointer, measured on screen
+                let pointer = plot.pointer_coordinate();
+                let hovered = pointer.and_then(|pointer| {
+                    let pointer = plot.screen_from_plot(pointer);
+                    points.iter().enumerate()
+                        .map(|(idx, x)| (idx, plot.screen_from_plot(egui::plot::PlotPoint::new(x[0], x[1])).distance(pointer)))
+                        .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(idx, _)| idx)
+                });
+
+                (pointer, hovered)
+            });
+
+        let (pointer, hovered) = response.inner;
+        let response = response.response.interact(egui::Sense::click());
+
+        // Left click selects or adds, right click removes, points can be dragged around
+        if response.clicked() && let Some(pointer) = pointer {
+            if hovered.is_some() {
+                self.selected = hovered;
+            } else {
+                let time = beatmap.snap(Time::from_ms(pointer.x * 1000.0), self.divisor);
+                let velocity = round_velocity(pointer.y);
+                let point = velocity::point_at(beatmap, time, velocity);
+                before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+                self.selected = Some(velocity::insert(beatmap, point));
+                edit = Some(("Add velocity point", false));
+            }
+        }
+
+        if response.secondary_clicked() && let Some(idx) = hovered {
+            before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+            beatmap.velocity.remove(idx);
+            self.selected = None;
+            edit = Some(("Delete velocity point", false));
+        }
+
+        if response.drag_started() {
+            self.dragging = hovered;
+            self.selected = hovered.or(self.selected);
+        }
+
+        if response.dragged() && let Some(idx) = self.dragging && let Some(pointer) = pointer {
+            let time = beatmap.snap(Time::from_ms(pointer.x * 1000.0), self.divisor);
+            before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+            let point = &mut beatmap.velocity[idx];
+            point.time = time;
+            point.velocity = round_velocity(pointer.y);
+            edit = Some(("Move velocity point", true));
+        }
+
+        if response.drag_released() && self.dragging.take().is_some() {
+            edit = edit.or(Some(("Move velocity point", true)));
+            resort = true;
+        }
+
+        // Selected point
+        ui.separator();
+        let selected = self.selected.filter(|x| *x < beatmap.velocity.len());
+        if let Some(idx) = selected {
+            ui.horizontal(|ui| {
+                // Widgets edit copies, which are written back once something changed
+                let point = &beatmap.velocity[idx];
+                let original = point.time.to_seconds() * 1000.0;
+                let (mut time, mut velocity) = (original, point.velocity);
+
+                let time_response = ui.add(egui::DragValue::new(&mut time).speed(1.0).max_decimals(0).suffix("ms"));
+                let velocity_response = ui.add(egui::DragValue::new(&mut velocity).speed(0.01).clamp_range(MIN_VELOCITY ..= MAX_VELOCITY).suffix("x"));
+                for response in [time_response, velocity_response] {
+                    if response.changed() {
+                        edit = Some(("Edit velocity point", true));
+                        before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+
+                        let point = &mut beatmap.velocity[idx];
+                        if time != original { point.time = Time::from_ms(time.round()); }
+                        point.velocity = velocity;
+                    }
+
+                    if response.drag_released() || response.lost_focus() {
+                        resort = true;
+                    }
+                }
+
+                if ui.button("Delete").clicked() {
+                    before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+                    beatmap.velocity.remove(idx);
+                    self.selected = None;
+                    edit = Some(("Delete velocity point", false));
+                }
+            });
+        } else {
+            ui.label("Click the graph to add a point, right click to remove one");
+        }
+
+        // Interpolation over a range
+        ui.separator();
+        egui::Grid::new("velocity_interpolation").num_columns(3).show(ui, |ui| {
+            ui.label("Start");
+            ui.add(egui::DragValue::new(&mut self.start).speed(1.0).max_decimals(0).suffix("ms"));
+            if ui.button("Current time").clicked() { self.start = beatmap.snap(Time::from_ms(now), self.divisor).to_seconds() * 1000.0; }
+            ui.end_row();
+
+            ui.label("End");
+            ui.add(egui::DragValue::new(&mut self.end).speed(1.0).max_decimals(0).suffix("ms"));
+            if ui.button("Current time").clicked() { self.end = beatmap.snap(Time::from_ms(now), self.divisor).to_seconds() * 1000.0; }
+            ui.end_row();
+
+            ui.label("Velocity");
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.from).speed(0.01).clamp_range(MIN_VELOCITY ..= MAX_VELOCITY).suffix("x"));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut self.to).speed(0.01).clamp_range(MIN_VELOCITY ..= MAX_VELOCITY).suffix("x"));
+            });
+            ui.end_row();
+
+            ui.label("Curve");
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.interpolation, Interpolation::Linear, "Linear");
+                ui.selectable_value(&mut self.interpolation, Interpolation::Exponential, "Exponential");
+            });
+            ui.end_row();
+        });
+
+        let (start, end) = (Time::from_ms(self.start), Time::from_ms(self.end));
+        if ui.add_enabled(self.end > self.start, egui::Button::new(format!("Apply every 1/{} beat", self.divisor))).clicked() {
+            let points = velocity::interpolate(beatmap, start, end, self.from, self.to, self.divisor, self.interpolation);
+            before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+            velocity::replace_range(beatmap, points);
+            self.selected = None;
+            edit = Some(("Interpolate velocity", false));
+        }
+
+        if resort {
+            before.get_or_insert_with(|| Snapshot::velocity(beatmap));
+            let selected = self.selected.and_then(|x| beatmap.velocity.get(x)).map(|x| (x.time, x.velocity));
+            beatmap.velocity.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+            self.selected = selected.and_then(|(time, velocity)| beatmap.velocity.iter().position(|x| x.time == time && x.velocity == velocity));
+        }
+
+        // Sorting once a drag ends belongs to the same step in the history
+        if resort && edit.is_none() {
+            edit = Some(("Edit velocity point", true));
+        }
+
+        // Previewed live on the conveyor
+        if let Some((name, merge)) = edit && let Some(before) = before {
+            state.taiko_layer.history.record(name, merge, before, beatmap);
+            state.taiko_layer.edited(&mut state.taiko);
+        }
+
+        if resort {
+            state.taiko_layer.history.seal();
+        }
+    }
+}
+
+fn round_velocity(velocity: f64) -> f64 {
+    return ((velocity * 100.0).round() / 100.0).clamp(MIN_VELOCITY, MAX_VELOCITY);
+}
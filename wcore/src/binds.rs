@@ -161,6 +161,9 @@ impl Display for KeyCombination {
 pub struct Action<State> {
     pub name: String,
     pub description: String,
+
+    /// Whether holding the key down invokes the action again
+    pub repeat: bool,
     function: Box<dyn FnMut(&mut State) + 'static>,
 }
 
@@ -169,10 +172,17 @@ impl<State> Action<State> {
         return Self {
             name,
             description,
+            repeat: true,
             function: Box::new(function),
         }
     }
 
+    /// Only invoked once per key press
+    pub fn without_repeat(mut self) -> Self {
+        self.repeat = false;
+        return self;
+    }
+
     pub fn invoke(&mut self, state: &mut State) {
         (*self.function)(state);
     }
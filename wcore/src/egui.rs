@@ -40,7 +40,11 @@ impl Egui {
     }
 
     pub fn prepare(&mut self, window: &Window, graphics: &mut Graphics, encoder: &mut wgpu::CommandEncoder, run_ui: impl FnOnce(&mut Graphics, &egui::Context)) -> (Vec<ClippedPrimitive>, Vec<wgpu::CommandBuffer>){
-        let egui_output = self.context.run(self.winit_state.take_egui_input(window), |ui| run_ui(graphics, ui));
+        let mut egui_output = self.context.run(self.winit_state.take_egui_input(window), |ui| run_ui(graphics, ui));
+
+        // Clipboard, cursor icon and links
+        let platform_output = std::mem::take(&mut egui_output.platform_output);
+        self.winit_state.handle_platform_output(window, &self.context, platform_output);
         
         // Free textures
         for id in &egui_output.textures_delta.free {